@@ -0,0 +1,186 @@
+#[cfg(feature = "std")]
+use std::io::{Seek, SeekFrom, Write};
+
+/// The rate at which the emulator's frames and timers advance.
+pub const FRAMES_PER_SECOND: u32 = 60;
+
+/// The default sample rate used for the beep.
+pub const DEFAULT_SAMPLE_RATE: u32 = 44100;
+
+/// The frequency of the beep tone in Hz.
+pub const BEEP_FREQUENCY: f32 = 440.0;
+
+/// The volume of the beep tone, between 0.0 and 1.0.
+pub const BEEP_VOLUME: f32 = 0.25;
+
+/// Generates the square wave which is played while the sound timer is active.
+pub struct SquareWave {
+    pub phase_inc: f32,
+    pub phase: f32,
+    pub volume: f32,
+}
+
+impl SquareWave {
+    /// Creates a new square wave of the given frequency for the given sample rate.
+    pub fn new(frequency: f32, sample_rate: u32, volume: f32) -> Self {
+        SquareWave {
+            phase_inc: frequency / sample_rate as f32,
+            phase: 0.0,
+            volume,
+        }
+    }
+
+    /// Returns the next sample of the wave.
+    pub fn next_sample(&mut self) -> f32 {
+        let sample = if self.phase <= 0.5 {
+            self.volume
+        } else {
+            -self.volume
+        };
+        self.phase = (self.phase + self.phase_inc) % 1.0;
+        sample
+    }
+
+    /// Fills the buffer with the next samples of the wave.
+    pub fn fill(&mut self, out: &mut [f32]) {
+        for x in out.iter_mut() {
+            *x = self.next_sample();
+        }
+    }
+}
+
+/// Returns the amount of audio samples which belong to the given emulated frame.
+///
+/// The amount is computed from the frame index so that rounding never accumulates and
+/// the audio stays aligned with the frames, no matter how long the recording is.
+pub fn samples_for_frame(frame: u64, sample_rate: u32) -> u64 {
+    let rate = sample_rate as u64;
+    let fps = FRAMES_PER_SECOND as u64;
+    (frame + 1) * rate / fps - frame * rate / fps
+}
+
+/// Size of the RIFF/WAVE header written in front of the samples.
+#[cfg(feature = "std")]
+const WAV_HEADER_SIZE: u32 = 44;
+
+/// Records the emulator's sound output into a 16-bit mono PCM WAV file.
+///
+/// Audio is rendered offline, one emulated frame at a time, so the recording
+/// matches the emulation exactly rather than the wall-clock time.
+#[cfg(feature = "std")]
+pub struct WavRecorder<W: Write + Seek> {
+    writer: W,
+    wave: SquareWave,
+    sample_rate: u32,
+    frames_recorded: u64,
+    samples_written: u32,
+}
+
+#[cfg(feature = "std")]
+impl<W: Write + Seek> WavRecorder<W> {
+    /// Creates a new recorder and writes the WAV header to the writer.
+    pub fn new(mut writer: W, sample_rate: u32) -> Result<Self, anyhow::Error> {
+        Self::write_header(&mut writer, sample_rate, 0)?;
+        Ok(WavRecorder {
+            writer,
+            wave: SquareWave::new(BEEP_FREQUENCY, sample_rate, BEEP_VOLUME),
+            sample_rate,
+            frames_recorded: 0,
+            samples_written: 0,
+        })
+    }
+
+    /// Records the audio of one emulated frame.
+    pub fn record_frame(&mut self, beeping: bool) -> Result<(), anyhow::Error> {
+        let samples = samples_for_frame(self.frames_recorded, self.sample_rate);
+        for _ in 0..samples {
+            let sample = self.wave.next_sample();
+            let value = if beeping {
+                (sample * i16::MAX as f32) as i16
+            } else {
+                0
+            };
+            self.writer.write_all(&value.to_le_bytes())?;
+        }
+        self.frames_recorded += 1;
+        self.samples_written += samples as u32;
+        Ok(())
+    }
+
+    /// Returns the number of frames recorded so far.
+    pub fn frames_recorded(&self) -> u64 {
+        self.frames_recorded
+    }
+
+    /// Updates the header with the final sizes and returns the underlying writer.
+    pub fn finish(mut self) -> Result<W, anyhow::Error> {
+        self.writer.seek(SeekFrom::Start(0))?;
+        Self::write_header(&mut self.writer, self.sample_rate, self.samples_written)?;
+        self.writer.seek(SeekFrom::End(0))?;
+        self.writer.flush()?;
+        Ok(self.writer)
+    }
+
+    fn write_header(writer: &mut W, sample_rate: u32, samples: u32) -> Result<(), anyhow::Error> {
+        let data_size = samples * 2;
+        writer.write_all(b"RIFF")?;
+        writer.write_all(&(WAV_HEADER_SIZE - 8 + data_size).to_le_bytes())?;
+        writer.write_all(b"WAVE")?;
+        writer.write_all(b"fmt ")?;
+        // PCM format chunk: format 1, mono, 16 bits per sample.
+        writer.write_all(&16u32.to_le_bytes())?;
+        writer.write_all(&1u16.to_le_bytes())?;
+        writer.write_all(&1u16.to_le_bytes())?;
+        writer.write_all(&sample_rate.to_le_bytes())?;
+        writer.write_all(&(sample_rate * 2).to_le_bytes())?;
+        writer.write_all(&2u16.to_le_bytes())?;
+        writer.write_all(&16u16.to_le_bytes())?;
+        writer.write_all(b"data")?;
+        writer.write_all(&data_size.to_le_bytes())?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    extern crate std;
+    #[cfg(feature = "std")]
+    use std::io::Cursor;
+
+    #[test]
+    fn test_square_wave_alternates() {
+        let mut wave = SquareWave::new(1.0, 4, 0.5);
+        let mut out = [0.0; 4];
+
+        wave.fill(&mut out);
+
+        assert_eq!(out, [0.5, 0.5, 0.5, -0.5]);
+    }
+
+    #[test]
+    fn test_samples_for_frame_does_not_drift() {
+        let total: u64 = (0..60).map(|frame| samples_for_frame(frame, 22050)).sum();
+
+        assert_eq!(samples_for_frame(0, 22050), 367);
+        assert_eq!(samples_for_frame(1, 22050), 368);
+        assert_eq!(total, 22050);
+    }
+
+    #[cfg(feature = "std")]
+    #[test]
+    fn test_wav_recorder_writes_frames() {
+        let mut recorder =
+            WavRecorder::new(Cursor::new(std::vec::Vec::new()), DEFAULT_SAMPLE_RATE).unwrap();
+
+        recorder.record_frame(false).unwrap();
+        recorder.record_frame(true).unwrap();
+        let data = recorder.finish().unwrap().into_inner();
+
+        assert_eq!(data.len(), 44 + 735 * 2 * 2);
+        assert_eq!(&data[0..4], b"RIFF");
+        assert_eq!(&data[40..44], &(735u32 * 2 * 2).to_le_bytes());
+        assert!(data[44..44 + 735 * 2].iter().all(|&byte| byte == 0));
+        assert!(data[44 + 735 * 2..].iter().any(|&byte| byte != 0));
+    }
+}
@@ -0,0 +1,151 @@
+use crate::error::Error;
+use core::ops::{Deref, DerefMut};
+use core::str::FromStr;
+
+/// The size of the Chip8 memory.
+pub const MEMORY_SIZE: usize = 4096;
+
+/// The memory bus, every memory access of the CPU goes through it.
+///
+/// Implementations can map devices into the address space, protect regions or trace and
+/// watch accesses, without the instruction handlers knowing about it.
+pub trait Bus {
+    /// Reads the byte at the address for the CPU.
+    fn read(&mut self, address: u16) -> Result<u8, Error> {
+        self.peek(address)
+    }
+
+    /// Reads the byte at the address without side effects, for debuggers and frontends.
+    fn peek(&self, address: u16) -> Result<u8, Error>;
+
+    /// Writes the byte at the address.
+    fn write(&mut self, address: u16, value: u8) -> Result<(), Error>;
+}
+
+/// Selects how the CPU's accesses outside of the ROM's memory are checked.
+///
+/// The checks cover addresses past the end of the 4 KB memory, which `I + X` can reach, and
+/// writes below 0x200 into the area reserved for the interpreter and the font.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum MemoryProtection {
+    /// No checks: the bus rejects addresses it does not map and reserved writes go through.
+    #[default]
+    Off,
+    /// Addresses wrap around at 4 KB like on real hardware and reserved writes go through.
+    Wrap,
+    /// Both stop the emulator with an error.
+    Error,
+    /// Both are logged as warnings, addresses wrap and reserved writes go through.
+    Log,
+}
+
+impl MemoryProtection {
+    /// Returns the name of the mode, as accepted by [`MemoryProtection::from_str`].
+    pub fn name(&self) -> &'static str {
+        match self {
+            MemoryProtection::Off => "off",
+            MemoryProtection::Wrap => "wrap",
+            MemoryProtection::Error => "error",
+            MemoryProtection::Log => "log",
+        }
+    }
+}
+
+impl FromStr for MemoryProtection {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        [
+            MemoryProtection::Off,
+            MemoryProtection::Wrap,
+            MemoryProtection::Error,
+            MemoryProtection::Log,
+        ]
+        .into_iter()
+        .find(|mode| mode.name().eq_ignore_ascii_case(s))
+        .ok_or_else(|| Error::parse(s, "off, wrap, error or log"))
+    }
+}
+
+/// The default bus, a flat 4 KB RAM.
+#[derive(Debug, Clone)]
+pub struct Ram {
+    memory: [u8; MEMORY_SIZE],
+}
+
+impl Ram {
+    /// Creates a new RAM filled with zeroes.
+    pub const fn new() -> Self {
+        Ram {
+            memory: [0; MEMORY_SIZE],
+        }
+    }
+}
+
+impl Default for Ram {
+    fn default() -> Self {
+        Ram::new()
+    }
+}
+
+impl Deref for Ram {
+    type Target = [u8];
+
+    fn deref(&self) -> &Self::Target {
+        &self.memory
+    }
+}
+
+impl DerefMut for Ram {
+    fn deref_mut(&mut self) -> &mut Self::Target {
+        &mut self.memory
+    }
+}
+
+impl Bus for Ram {
+    fn peek(&self, address: u16) -> Result<u8, Error> {
+        self.memory
+            .get(address as usize)
+            .copied()
+            .ok_or(Error::AddressOutOfRange { address })
+    }
+
+    fn write(&mut self, address: u16, value: u8) -> Result<(), Error> {
+        let byte = self
+            .memory
+            .get_mut(address as usize)
+            .ok_or(Error::AddressOutOfRange { address })?;
+        *byte = value;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_ram_read_write() {
+        let mut ram = Ram::new();
+
+        ram.write(0xFFF, 0xAB).unwrap();
+
+        assert_eq!(ram.read(0xFFF), Ok(0xAB));
+        assert_eq!(ram[0xFFF], 0xAB);
+        assert_eq!(
+            ram.write(0x1000, 1),
+            Err(Error::AddressOutOfRange { address: 0x1000 })
+        );
+        assert_eq!(
+            ram.peek(0x1000),
+            Err(Error::AddressOutOfRange { address: 0x1000 })
+        );
+    }
+
+    #[test]
+    fn test_memory_protection_from_str() {
+        assert_eq!("wrap".parse(), Ok(MemoryProtection::Wrap));
+        assert_eq!("Error".parse(), Ok(MemoryProtection::Error));
+        assert!("strict".parse::<MemoryProtection>().is_err());
+    }
+}
@@ -0,0 +1,196 @@
+use crate::octo;
+use crate::palette::{Palette, Rgb};
+use crate::quirks::Quirks;
+use crate::rom::{KeyHints, RomSettings};
+use anyhow::anyhow;
+use serde::Deserialize;
+use std::io::Read;
+
+/// The keypad keys the Octo IDE maps its arrow keys and space to.
+const OCTO_KEYS: KeyHints = KeyHints {
+    up: Some(0x5),
+    down: Some(0x8),
+    left: Some(0x7),
+    right: Some(0x9),
+    a: Some(0x6),
+    b: None,
+};
+
+/// The program and options the Octo IDE embeds in its cartridges.
+#[derive(Debug, Deserialize)]
+struct Payload {
+    program: String,
+    #[serde(default)]
+    options: Options,
+}
+
+/// The options of an Octo program. The quirks which are missing are turned off, like in Octo.
+#[derive(Debug, Default, Deserialize)]
+#[serde(rename_all = "camelCase", default)]
+struct Options {
+    tickrate: Option<u32>,
+    fill_color: Option<String>,
+    background_color: Option<String>,
+    shift_quirks: bool,
+    load_store_quirks: bool,
+    vf_order_quirks: bool,
+    clip_quirks: bool,
+    jump_quirks: bool,
+    logic_quirks: bool,
+    v_blank_quirks: bool,
+}
+
+impl Options {
+    fn quirks(&self) -> Quirks {
+        Quirks {
+            shift: self.shift_quirks,
+            memory_leave_i_unchanged: self.load_store_quirks,
+            wrap: !self.clip_quirks,
+            jump: self.jump_quirks,
+            vblank: self.v_blank_quirks,
+            logic: self.logic_quirks,
+            vf_order: self.vf_order_quirks,
+            ..Quirks::none()
+        }
+    }
+
+    fn palette(&self) -> Option<Palette> {
+        let background = Rgb::from_hex(self.background_color.as_deref()?)?;
+        let pixel = Rgb::from_hex(self.fill_color.as_deref()?)?;
+        Some(Palette::new(background, pixel))
+    }
+}
+
+/// A program loaded from an Octo cartridge, a GIF image which hides the Octo source code of
+/// the program and its options in the low nibbles of its pixels.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Cartridge {
+    /// The Octo source code of the program.
+    pub source: String,
+    /// The assembled program.
+    pub rom: Vec<u8>,
+    /// The quirks, tick rate, colors and keys the program was published with.
+    pub settings: RomSettings,
+}
+
+impl Cartridge {
+    /// Decodes the cartridge GIF and assembles its program.
+    pub fn read(gif: impl Read) -> Result<Self, anyhow::Error> {
+        let payload: Payload = serde_json::from_slice(&decode_payload(gif)?)
+            .map_err(|error| anyhow!("the cartridge has invalid options: {error}"))?;
+        let rom = octo::assemble(&payload.program)
+            .map_err(|error| anyhow!("the cartridge's program does not assemble: {error}"))?;
+        let options = &payload.options;
+        Ok(Cartridge {
+            rom,
+            settings: RomSettings {
+                platform: None,
+                quirks: Some(options.quirks()),
+                tick_rate: options.tickrate.filter(|tick_rate| *tick_rate > 0),
+                palette: options.palette(),
+                keys: Some(OCTO_KEYS),
+            },
+            source: payload.program,
+        })
+    }
+}
+
+/// Returns the bytes hidden in the pixels of the GIF frames: a 4 byte big endian length and
+/// that many bytes of JSON.
+fn decode_payload(gif: impl Read) -> Result<Vec<u8>, anyhow::Error> {
+    let mut options = gif::DecodeOptions::new();
+    options.set_color_output(gif::ColorOutput::Indexed);
+    let mut decoder = options.read_info(gif)?;
+    let mut nibbles = Vec::new();
+    while let Some(frame) = decoder.read_next_frame()? {
+        nibbles.extend(frame.buffer.iter().map(|index| index & 0xF));
+    }
+    let mut bytes = nibbles.chunks_exact(2).map(|pair| pair[0] << 4 | pair[1]);
+    let length = u32::from_be_bytes([
+        bytes.next().unwrap_or(0),
+        bytes.next().unwrap_or(0),
+        bytes.next().unwrap_or(0),
+        bytes.next().unwrap_or(0),
+    ]) as usize;
+    let payload: Vec<u8> = bytes.take(length).collect();
+    if length == 0 || payload.len() < length {
+        return Err(anyhow!("the image is not an Octo cartridge"));
+    }
+    Ok(payload)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Encodes the payload like Octo, in the low nibbles of 128x64 frames.
+    fn cartridge(json: &str) -> Vec<u8> {
+        let mut data = (json.len() as u32).to_be_bytes().to_vec();
+        data.extend_from_slice(json.as_bytes());
+        let mut pixels: Vec<u8> = data
+            .iter()
+            .flat_map(|byte| [byte >> 4, byte & 0xF])
+            .collect();
+        let frame_size = 128 * 64;
+        pixels.resize(pixels.len().div_ceil(frame_size) * frame_size, 0);
+        let palette: Vec<u8> = (0..=255).flat_map(|index| [index, index, index]).collect();
+        let mut gif = Vec::new();
+        {
+            let mut encoder = gif::Encoder::new(&mut gif, 128, 64, &palette).unwrap();
+            for frame in pixels.chunks(frame_size) {
+                // the label drawn in the high nibbles does not matter
+                let frame: Vec<u8> = frame.iter().map(|nibble| nibble | 0x30).collect();
+                encoder
+                    .write_frame(&gif::Frame::from_indexed_pixels(128, 64, frame, None))
+                    .unwrap();
+            }
+        }
+        gif
+    }
+
+    #[test]
+    fn test_read_cartridge() {
+        let json = r##"{
+            "program": ": main\n  v0 := 1\n  loop again",
+            "options": {
+                "tickrate": 20,
+                "fillColor": "#FFCC00",
+                "backgroundColor": "#996600",
+                "shiftQuirks": true,
+                "clipQuirks": true,
+                "maxSize": 3215
+            }
+        }"##;
+
+        let cartridge = Cartridge::read(cartridge(json).as_slice()).unwrap();
+
+        assert_eq!(cartridge.rom, [0x60, 0x01, 0x12, 0x02]);
+        assert_eq!(
+            cartridge.settings.quirks,
+            Some(Quirks {
+                shift: true,
+                ..Quirks::none()
+            })
+        );
+        assert_eq!(cartridge.settings.tick_rate, Some(20));
+        assert_eq!(cartridge.settings.palette, Some(Palette::OCTO));
+    }
+
+    #[test]
+    fn test_read_large_cartridge() {
+        let program = format!(": main\n{}", "  clear\n".repeat(1500));
+        let json = format!(r#"{{ "program": {program:?} }}"#);
+
+        let cartridge = Cartridge::read(cartridge(&json).as_slice()).unwrap();
+
+        assert_eq!(cartridge.rom.len(), 3000);
+        assert!(cartridge.settings.quirks.unwrap().wrap);
+    }
+
+    #[test]
+    fn test_read_invalid_cartridge() {
+        assert!(Cartridge::read(cartridge("{}").as_slice()).is_err());
+        assert!(Cartridge::read(cartridge(r#"{ "program": "hires" }"#).as_slice()).is_err());
+        assert!(Cartridge::read(&b"GIF89a"[..]).is_err());
+    }
+}
@@ -0,0 +1,55 @@
+/// The commands of the frontends, bound to keys outside of the keypad so they never reach
+/// the ROM.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Command {
+    /// Stops the emulation, or returns to the ROM browser.
+    Quit,
+    /// Restarts the ROM, keeping the memory it wrote, see [`Emulator::soft_reset`].
+    ///
+    /// [`Emulator::soft_reset`]: crate::emulator::Emulator::soft_reset
+    SoftReset,
+    /// Restores the power-on state with the ROM, see [`Emulator::reset`].
+    ///
+    /// [`Emulator::reset`]: crate::emulator::Emulator::reset
+    HardReset,
+    /// Pauses or resumes the emulation.
+    TogglePause,
+    /// Pauses and runs one more frame, see [`RunLoop::advance_frame`].
+    ///
+    /// [`RunLoop::advance_frame`]: crate::run::RunLoop::advance_frame
+    FrameAdvance,
+    /// Pauses and runs one more instruction, see [`RunLoop::step_instruction`].
+    ///
+    /// [`RunLoop::step_instruction`]: crate::run::RunLoop::step_instruction
+    StepInstruction,
+    /// Starts or stops fast-forwarding.
+    FastForward(bool),
+    /// Loads the ROM file again, for example after rebuilding it.
+    ReloadRom,
+    /// Saves a screenshot.
+    Screenshot,
+    /// Starts a recording, or stops and saves the running one.
+    ToggleRecording,
+    /// Shows or hides the frames and instructions per second and the quirks preset.
+    ToggleOverlay,
+}
+
+impl Command {
+    /// Returns what the command does, for help texts and logs.
+    pub fn description(&self) -> &'static str {
+        match self {
+            Command::Quit => "quit",
+            Command::SoftReset => "soft reset",
+            Command::HardReset => "hard reset",
+            Command::TogglePause => "pause or resume",
+            Command::FrameAdvance => "advance one frame",
+            Command::StepInstruction => "step one instruction",
+            Command::FastForward(true) => "start fast-forwarding",
+            Command::FastForward(false) => "stop fast-forwarding",
+            Command::ReloadRom => "reload the ROM",
+            Command::Screenshot => "save a screenshot",
+            Command::ToggleRecording => "start or stop recording",
+            Command::ToggleOverlay => "show or hide the statistics",
+        }
+    }
+}
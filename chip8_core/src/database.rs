@@ -0,0 +1,388 @@
+use crate::bus::Bus;
+use crate::emulator::Emulator;
+use crate::palette::{Palette, Rgb};
+use crate::quirks::{Platform, Quirks};
+use crate::random::Rng;
+use crate::rom::{KeyHints, RomSettings};
+use log::{info, warn};
+use serde::Deserialize;
+use std::collections::HashMap;
+use std::path::Path;
+
+/// The metadata of the ROMs in the repository, in the chip-8-database format.
+const BUNDLED_PROGRAMS: &str = include_str!("../database/programs.json");
+
+/// Returns the SHA-1 hash of the ROM in lowercase hex, the key of the chip-8-database.
+pub fn rom_sha1(rom: impl IntoIterator<Item = u8>) -> String {
+    let rom: Vec<u8> = rom.into_iter().collect();
+    sha1_smol::Sha1::from(rom).digest().to_string()
+}
+
+/// A program of the chip-8-database's `programs.json`.
+#[derive(Debug, Deserialize)]
+struct ProgramEntry {
+    title: String,
+    description: Option<String>,
+    #[serde(default)]
+    authors: Vec<String>,
+    roms: HashMap<String, RomEntry>,
+}
+
+/// A ROM of a program, keyed by its SHA-1 hash.
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct RomEntry {
+    file: Option<String>,
+    #[serde(default)]
+    platforms: Vec<String>,
+    #[serde(default)]
+    quirky_platforms: HashMap<String, HashMap<String, bool>>,
+    tickrate: Option<u32>,
+    #[serde(default)]
+    keys: HashMap<String, u8>,
+    colors: Option<ColorsEntry>,
+}
+
+#[derive(Debug, Deserialize)]
+struct ColorsEntry {
+    #[serde(default)]
+    pixels: Vec<String>,
+}
+
+/// Returns the keypad keys of the ROM's directions and buttons.
+fn key_hints(keys: &HashMap<String, u8>) -> KeyHints {
+    let key = |name: &str| keys.get(name).copied().filter(|key| *key <= 0xF);
+    KeyHints {
+        up: key("up"),
+        down: key("down"),
+        left: key("left"),
+        right: key("right"),
+        a: key("a"),
+        b: key("b"),
+    }
+}
+
+/// What the database knows about a ROM.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RomInfo {
+    pub title: String,
+    pub description: Option<String>,
+    pub authors: Vec<String>,
+    /// The file name the ROM is usually distributed under.
+    pub file: Option<String>,
+    /// The first platform of the ROM the emulator knows, if any.
+    pub platform: Option<Platform>,
+    /// The quirks of the platform, with the ROM's exceptions applied.
+    pub quirks: Option<Quirks>,
+    /// The amount of instructions per frame the ROM runs best at.
+    pub tick_rate: Option<u32>,
+    pub keys: KeyHints,
+    /// The colors of the ROM's background and pixels.
+    pub palette: Option<Palette>,
+}
+
+impl RomInfo {
+    fn new(program: &ProgramEntry, rom: &RomEntry) -> Self {
+        let platform = rom
+            .platforms
+            .iter()
+            .find_map(|platform| platform.parse::<Platform>().ok());
+        let quirks = platform.map(|platform| {
+            let mut quirks = platform.quirks();
+            let exceptions = rom
+                .quirky_platforms
+                .get(platform.id())
+                .into_iter()
+                .flatten();
+            for (name, value) in exceptions {
+                match quirks.get_mut(name) {
+                    Some(quirk) => *quirk = *value,
+                    None => warn!("Unknown quirk {name} of {}", program.title),
+                }
+            }
+            quirks
+        });
+        let palette = match rom.colors.as_ref().map(|colors| colors.pixels.as_slice()) {
+            Some([background, pixel, ..]) => Rgb::from_hex(background)
+                .zip(Rgb::from_hex(pixel))
+                .map(|(background, pixel)| Palette::new(background, pixel)),
+            _ => None,
+        };
+        RomInfo {
+            title: program.title.clone(),
+            description: program.description.clone(),
+            authors: program.authors.clone(),
+            file: rom.file.clone(),
+            platform,
+            quirks,
+            tick_rate: rom
+                .tickrate
+                .or_else(|| platform.map(|platform| platform.tick_rate())),
+            keys: key_hints(&rom.keys),
+            palette,
+        }
+    }
+
+    /// Returns the settings the database has for the ROM.
+    pub fn settings(&self) -> RomSettings {
+        RomSettings {
+            platform: self.platform,
+            quirks: self.quirks,
+            tick_rate: self.tick_rate,
+            palette: self.palette,
+            keys: Some(self.keys),
+        }
+    }
+
+    /// Configures the emulator with the ROM's quirks and tick rate.
+    pub fn apply<R: Rng, B: Bus>(&self, emulator: &mut Emulator<R, B>) {
+        self.settings().apply(emulator);
+    }
+}
+
+/// Settings which win over those of the ROM file and the ROM database, such as the command
+/// line's.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct RomOverrides {
+    pub quirks: Option<Quirks>,
+    /// The amount of instructions per frame.
+    pub tick_rate: Option<u32>,
+}
+
+/// A ROM metadata database keyed by the SHA-1 hash of the ROMs, read from the
+/// `programs.json` of the community chip-8-database.
+#[derive(Debug, Clone, Default)]
+pub struct RomDatabase {
+    roms: HashMap<String, RomInfo>,
+}
+
+impl RomDatabase {
+    /// Returns the database of the ROMs bundled with the emulator.
+    pub fn bundled() -> Self {
+        RomDatabase::parse(BUNDLED_PROGRAMS).expect("the bundled ROM database is valid")
+    }
+
+    /// Parses the contents of a `programs.json` file.
+    pub fn parse(json: &str) -> Result<Self, anyhow::Error> {
+        let programs: Vec<ProgramEntry> = serde_json::from_str(json)?;
+        let roms = programs
+            .iter()
+            .flat_map(|program| {
+                program
+                    .roms
+                    .iter()
+                    .map(|(sha1, rom)| (sha1.to_ascii_lowercase(), RomInfo::new(program, rom)))
+            })
+            .collect();
+        Ok(RomDatabase { roms })
+    }
+
+    /// Loads a `programs.json` file.
+    pub fn load(path: &Path) -> Result<Self, anyhow::Error> {
+        RomDatabase::parse(&std::fs::read_to_string(path)?)
+    }
+
+    /// Returns the ROM with the given SHA-1 hash.
+    pub fn lookup(&self, sha1: &str) -> Option<&RomInfo> {
+        self.roms.get(&sha1.to_ascii_lowercase())
+    }
+
+    /// Returns the ROM with the given bytes.
+    pub fn lookup_rom(&self, rom: impl IntoIterator<Item = u8>) -> Option<&RomInfo> {
+        self.lookup(&rom_sha1(rom))
+    }
+
+    /// Configures the emulator for its loaded ROM and returns the ROM's settings: those of the
+    /// ROM file, such as an Octo cartridge's, with the database's filling the ones it does not
+    /// have. The overrides win over both.
+    pub fn configure<R: Rng, B: Bus>(
+        &self,
+        emulator: &mut Emulator<R, B>,
+        settings: &RomSettings,
+        overrides: RomOverrides,
+    ) -> RomSettings {
+        let settings = match self.lookup_rom(emulator.rom()) {
+            Some(info) => {
+                info!("Recognized {}", info.title);
+                settings.or(info.settings())
+            }
+            None => {
+                info!("The ROM is not in the ROM database");
+                *settings
+            }
+        };
+        settings.apply(emulator);
+        if let Some(quirks) = overrides.quirks {
+            emulator.set_quirks(quirks);
+        }
+        if let Some(tick_rate) = overrides.tick_rate {
+            emulator.set_instructions_per_frame(tick_rate);
+        }
+        info!(
+            "Running {} instructions per frame with the quirks {}",
+            emulator.instructions_per_frame(),
+            emulator.quirks()
+        );
+        settings
+    }
+
+    /// Returns the amount of ROMs in the database.
+    pub fn len(&self) -> usize {
+        self.roms.len()
+    }
+
+    /// Returns true if the database has no ROMs.
+    pub fn is_empty(&self) -> bool {
+        self.roms.is_empty()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::bus::Ram;
+    use crate::error::Error;
+    use crate::random::{RandomMode, RandomSource};
+
+    const PROGRAMS: &str = r##"[
+      {
+        "title": "Test",
+        "release": "2024",
+        "roms": {
+          "A9993E364706816ABA3E25717850C26C9CD0D89D": {
+            "file": "test.ch8",
+            "platforms": ["megachip8", "superchip"],
+            "quirkyPlatforms": { "superchip": { "jump": false, "logic": true } },
+            "keys": { "up": 5, "a": 6, "player2Up": 8 },
+            "colors": { "pixels": ["#000000", "#ff8000"], "buzzer": "#ffffff" }
+          }
+        }
+      }
+    ]"##;
+
+    #[test]
+    fn test_rom_sha1() {
+        assert_eq!(
+            rom_sha1(*b"abc"),
+            "a9993e364706816aba3e25717850c26c9cd0d89d"
+        );
+    }
+
+    #[test]
+    fn test_lookup_rom() {
+        let database = RomDatabase::parse(PROGRAMS).unwrap();
+
+        let info = database.lookup_rom(*b"abc").unwrap();
+
+        assert_eq!(info.title, "Test");
+        assert_eq!(info.platform, Some(Platform::Superchip));
+        assert_eq!(
+            info.quirks,
+            Some(Quirks {
+                jump: false,
+                logic: true,
+                ..Platform::Superchip.quirks()
+            })
+        );
+        assert_eq!(info.tick_rate, Some(Platform::Superchip.tick_rate()));
+        assert_eq!(info.keys.up, Some(5));
+        assert_eq!(info.keys.a, Some(6));
+        assert_eq!(info.palette.unwrap().pixel, Rgb::new(0xFF, 0x80, 0x00));
+        assert!(database.lookup_rom(*b"abd").is_none());
+    }
+
+    #[test]
+    fn test_configure() {
+        let database = RomDatabase::parse(PROGRAMS).unwrap();
+        let superchip = RomSettings::for_extension("sc8");
+        let mut emulator = Emulator::new();
+        emulator.load_rom(&b"abc"[..]).unwrap();
+
+        let settings = database.configure(&mut emulator, &superchip, RomOverrides::default());
+
+        assert_eq!(settings.palette.unwrap().pixel, Rgb::new(0xFF, 0x80, 0x00));
+        assert_eq!(settings.keys.unwrap().up, Some(5));
+        assert!(!emulator.quirks().jump);
+        let overrides = RomOverrides {
+            quirks: Some(Quirks::none()),
+            tick_rate: Some(7),
+        };
+        database.configure(&mut emulator, &superchip, overrides);
+        assert_eq!(emulator.quirks(), Quirks::none());
+        assert_eq!(emulator.instructions_per_frame(), 7);
+
+        let mut emulator = Emulator::new();
+        emulator.load_rom(&b"abd"[..]).unwrap();
+        let settings = database.configure(&mut emulator, &superchip, RomOverrides::default());
+        assert_eq!(settings, superchip);
+        assert_eq!(emulator.quirks(), Platform::Superchip.quirks());
+    }
+
+    /// A bus with a device over the first byte of the ROM, which always reads 0.
+    struct DeviceBus(Ram);
+
+    impl Bus for DeviceBus {
+        fn peek(&self, address: u16) -> Result<u8, Error> {
+            match address {
+                0x200 => Ok(0),
+                _ => self.0.peek(address),
+            }
+        }
+
+        fn write(&mut self, address: u16, value: u8) -> Result<(), Error> {
+            self.0.write(address, value)
+        }
+    }
+
+    #[test]
+    fn test_configure_looks_up_the_rom_as_loaded() {
+        let database = RomDatabase::parse(PROGRAMS).unwrap();
+        let mut emulator = Emulator::with_bus(
+            RandomSource::new(RandomMode::XorShift, 1),
+            DeviceBus(Ram::new()),
+        );
+        emulator.load_rom(&b"abc"[..]).unwrap();
+
+        let settings = database.configure(
+            &mut emulator,
+            &RomSettings::default(),
+            RomOverrides::default(),
+        );
+
+        assert_eq!(settings.platform, Some(Platform::Superchip));
+    }
+
+    #[test]
+    fn test_configure_prefers_the_rom_file() {
+        let database = RomDatabase::parse(PROGRAMS).unwrap();
+        let cartridge = RomSettings {
+            quirks: Some(Quirks::none()),
+            tick_rate: Some(100),
+            palette: Some(Palette::LCD),
+            keys: Some(KeyHints::default()),
+            ..RomSettings::default()
+        };
+        let mut emulator = Emulator::new();
+        emulator.load_rom(&b"abc"[..]).unwrap();
+
+        let settings = database.configure(&mut emulator, &cartridge, RomOverrides::default());
+
+        assert_eq!(settings.palette, Some(Palette::LCD));
+        assert_eq!(settings.keys, Some(KeyHints::default()));
+        assert_eq!(settings.platform, Some(Platform::Superchip));
+        assert_eq!(emulator.quirks(), Quirks::none());
+        assert_eq!(emulator.instructions_per_frame(), 100);
+    }
+
+    #[test]
+    fn test_bundled_database() {
+        let database = RomDatabase::bundled();
+        let rom = std::fs::read("../roms/ibm-logo.ch8").unwrap();
+
+        let info = database.lookup_rom(rom).unwrap();
+
+        assert_eq!(info.title, "IBM Logo");
+        assert_eq!(info.platform, Some(Platform::OriginalChip8));
+        assert_eq!(database.len(), 3);
+    }
+}
@@ -1,5 +1,241 @@
+use crate::palette::Palette;
+
 /// Represents the display's width in pixels.
 pub const DISPLAY_WIDTH: usize = 64;
 
 /// Represents the display's height pixels.
 pub const DISPLAY_HEIGHT: usize = 32;
+
+/// A rectangular region of the display, in pixels.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct DirtyRect {
+    pub x: usize,
+    pub y: usize,
+    pub width: usize,
+    pub height: usize,
+}
+
+impl DirtyRect {
+    /// The region covering the whole display.
+    pub const FULL: DirtyRect = DirtyRect {
+        x: 0,
+        y: 0,
+        width: DISPLAY_WIDTH,
+        height: DISPLAY_HEIGHT,
+    };
+
+    /// Creates a region covering a single pixel.
+    pub fn pixel(x: usize, y: usize) -> Self {
+        DirtyRect {
+            x,
+            y,
+            width: 1,
+            height: 1,
+        }
+    }
+
+    /// Returns the smallest region which covers both regions.
+    pub fn union(&self, other: &DirtyRect) -> DirtyRect {
+        let x = self.x.min(other.x);
+        let y = self.y.min(other.y);
+        let right = (self.x + self.width).max(other.x + other.width);
+        let bottom = (self.y + self.height).max(other.y + other.height);
+        DirtyRect {
+            x,
+            y,
+            width: right - x,
+            height: bottom - y,
+        }
+    }
+
+    /// Returns true if the pixel is inside the region.
+    pub fn contains(&self, x: usize, y: usize) -> bool {
+        x >= self.x && x < self.x + self.width && y >= self.y && y < self.y + self.height
+    }
+}
+
+/// The display's pixels packed one bit per pixel, with one `u64` word per row.
+///
+/// The leftmost pixel of a row is stored in the most significant bit, so sprite rows
+/// can be XORed into the display a row at a time.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Framebuffer {
+    rows: [u64; DISPLAY_HEIGHT],
+}
+
+impl Framebuffer {
+    /// Creates a new framebuffer with all pixels turned off.
+    pub const fn new() -> Self {
+        Framebuffer {
+            rows: [0; DISPLAY_HEIGHT],
+        }
+    }
+
+    /// Turns off all the pixels.
+    pub fn clear(&mut self) {
+        self.rows = [0; DISPLAY_HEIGHT];
+    }
+
+    /// Returns true if the pixel is lit.
+    pub fn get(&self, x: usize, y: usize) -> bool {
+        self.rows[y] & Self::mask(x) != 0
+    }
+
+    /// Turns the pixel on or off.
+    pub fn set(&mut self, x: usize, y: usize, lit: bool) {
+        if lit {
+            self.rows[y] |= Self::mask(x);
+        } else {
+            self.rows[y] &= !Self::mask(x);
+        }
+    }
+
+    /// Returns the packed pixels of a row.
+    pub fn row(&self, y: usize) -> u64 {
+        self.rows[y]
+    }
+
+    /// XORs the 8 pixels of a sprite row into the display at the given position, wrapping
+    /// around the right edge. Returns the mask of the pixels which were flipped and whether
+    /// any lit pixel was turned off.
+    pub fn xor_sprite_row(&mut self, x: usize, y: usize, sprite_row: u8) -> (u64, bool) {
+        let mask =
+            ((sprite_row as u64) << (DISPLAY_WIDTH - 8)).rotate_right((x % DISPLAY_WIDTH) as u32);
+        let row = &mut self.rows[y % DISPLAY_HEIGHT];
+        let collision = *row & mask != 0;
+        *row ^= mask;
+        (mask, collision)
+    }
+
+    /// XORs the 8 pixels of a sprite row into the display at the given position, clipping
+    /// the pixels past the right edge. Returns the same as [`Framebuffer::xor_sprite_row`].
+    pub fn xor_sprite_row_clipped(&mut self, x: usize, y: usize, sprite_row: u8) -> (u64, bool) {
+        let mask = ((sprite_row as u64) << (DISPLAY_WIDTH - 8)) >> (x % DISPLAY_WIDTH);
+        let row = &mut self.rows[y % DISPLAY_HEIGHT];
+        let collision = *row & mask != 0;
+        *row ^= mask;
+        (mask, collision)
+    }
+
+    /// Returns an iterator over the packed rows, from top to bottom.
+    pub fn rows(&self) -> impl Iterator<Item = u64> + '_ {
+        self.rows.iter().copied()
+    }
+
+    /// Returns an iterator over all the pixels in row-major order.
+    pub fn pixels(&self) -> impl Iterator<Item = bool> + '_ {
+        self.rows
+            .iter()
+            .flat_map(|row| (0..DISPLAY_WIDTH).map(move |x| row & Self::mask(x) != 0))
+    }
+
+    /// Returns an iterator over the `(x, y)` coordinates of the lit pixels.
+    pub fn lit_pixels(&self) -> impl Iterator<Item = (usize, usize)> + '_ {
+        self.rows.iter().enumerate().flat_map(|(y, row)| {
+            (0..DISPLAY_WIDTH)
+                .filter(move |x| row & Self::mask(*x) != 0)
+                .map(move |x| (x, y))
+        })
+    }
+
+    /// Converts the pixels into RGBA bytes using the palette's colors.
+    pub fn to_rgba(&self, palette: &Palette, out: &mut [u8; DISPLAY_WIDTH * DISPLAY_HEIGHT * 4]) {
+        for (pixel, rgba) in self.pixels().zip(out.chunks_exact_mut(4)) {
+            let color = if pixel {
+                palette.pixel
+            } else {
+                palette.background
+            };
+            rgba.copy_from_slice(&[color.r, color.g, color.b, 0xFF]);
+        }
+    }
+
+    /// Converts the pixels into 8-bit grayscale, 0xFF for lit pixels and 0 otherwise.
+    pub fn to_grayscale(&self, out: &mut [u8; DISPLAY_WIDTH * DISPLAY_HEIGHT]) {
+        for (pixel, gray) in self.pixels().zip(out.iter_mut()) {
+            *gray = if pixel { 0xFF } else { 0 };
+        }
+    }
+
+    /// Returns the bit mask of the column inside a row.
+    fn mask(x: usize) -> u64 {
+        1 << (DISPLAY_WIDTH - 1 - x)
+    }
+}
+
+impl Default for Framebuffer {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_dirty_rect_union() {
+        let region = DirtyRect::pixel(3, 4).union(&DirtyRect::pixel(10, 1));
+
+        assert_eq!(
+            region,
+            DirtyRect {
+                x: 3,
+                y: 1,
+                width: 8,
+                height: 4
+            }
+        );
+        assert!(region.contains(10, 4));
+        assert!(!region.contains(11, 4));
+        assert_eq!(region.union(&DirtyRect::FULL), DirtyRect::FULL);
+    }
+
+    #[test]
+    fn test_framebuffer_get_set() {
+        let mut framebuffer = Framebuffer::new();
+
+        framebuffer.set(0, 0, true);
+        framebuffer.set(63, 31, true);
+
+        assert!(framebuffer.get(0, 0));
+        assert!(framebuffer.get(63, 31));
+        assert!(!framebuffer.get(1, 0));
+        assert_eq!(framebuffer.row(0), 1 << 63);
+        assert_eq!(framebuffer.row(31), 1);
+        assert_eq!(framebuffer.lit_pixels().count(), 2);
+
+        framebuffer.set(0, 0, false);
+        assert_eq!(framebuffer.lit_pixels().next(), Some((63, 31)));
+        assert_eq!(core::mem::size_of::<Framebuffer>(), 256);
+    }
+
+    #[test]
+    fn test_framebuffer_xor_sprite_row_wraps() {
+        let mut framebuffer = Framebuffer::new();
+
+        let (mask, collision) = framebuffer.xor_sprite_row(60, 2, 0b1111_0001);
+        assert_eq!(mask, 0b1111 | (1 << 60));
+        assert!(!collision);
+        assert!(framebuffer.get(63, 2));
+        assert!(framebuffer.get(3, 2));
+
+        let (_, collision) = framebuffer.xor_sprite_row(63, 2, 0b1000_0000);
+        assert!(collision);
+        assert!(!framebuffer.get(63, 2));
+    }
+
+    #[test]
+    fn test_framebuffer_conversions() {
+        let mut framebuffer = Framebuffer::new();
+        framebuffer.set(1, 0, true);
+        let mut rgba = [0; DISPLAY_WIDTH * DISPLAY_HEIGHT * 4];
+        let mut grayscale = [0; DISPLAY_WIDTH * DISPLAY_HEIGHT];
+
+        framebuffer.to_rgba(&Palette::CLASSIC_GREEN, &mut rgba);
+        framebuffer.to_grayscale(&mut grayscale);
+
+        assert_eq!(rgba[0..8], [0, 0, 0, 0xFF, 0, 0xFF, 0, 0xFF]);
+        assert_eq!(grayscale[0..3], [0, 0xFF, 0]);
+    }
+}
@@ -1,39 +1,54 @@
 #[cfg(feature = "std")]
 use std::time::Instant;
 
-use crate::display::{DISPLAY_HEIGHT, DISPLAY_WIDTH};
+#[cfg(feature = "std")]
+use crate::profile::Profiler;
+#[cfg(feature = "std")]
+use crate::trace::{TraceRecord, TraceWriter};
+
+use crate::bus::{Bus, MemoryProtection, Ram, MEMORY_SIZE};
+use crate::font::FONT_SPRITES;
+use crate::display::{DirtyRect, Framebuffer, DISPLAY_HEIGHT, DISPLAY_WIDTH};
 use crate::instruction::{Instruction, ProcessorInstruction};
+use crate::quirks::Quirks;
 use crate::read::Reader;
-use crate::stack::Stack;
-use anyhow::anyhow;
+use crate::stack::{Stack, StackError, StackMode, MAX_STACK_CAPACITY};
+use crate::error::Error;
 use log::{debug, info, trace, warn};
-use rand::Rng;
-
-const MEMORY_SIZE: usize = 4096;
+use crate::random::{RandomMode, RandomSource, Rng};
+
+/// The default amount of instructions executed during one 60 Hz frame.
+pub const INSTRUCTIONS_PER_FRAME: u32 = 8;
+/// The address where ROMs are loaded and execution starts.
+const ROM_START: usize = 0x200;
+/// The size of the largest ROM which fits in the memory.
+pub const MAX_ROM_SIZE: usize = MEMORY_SIZE - ROM_START;
+/// The address of the font sprites.
+const FONT_START: u16 = 0xF0;
+/// The end of the call stack in memory, which grows downwards, in [`StackMode::Memory`].
+const VIP_STACK_TOP: usize = 0xED0;
+/// The lowest address the call stack can reach in [`StackMode::Memory`].
+const VIP_STACK_BOTTOM: usize = 0xEA0;
 const NUMBER_OF_REGISTERS: usize = 16;
-const FONT_SPRITES: [u8; 80] = [
-    0xF0, 0x90, 0x90, 0x90, 0xF0, // 0
-    0x20, 0x60, 0x20, 0x20, 0x70, // 1
-    0xF0, 0x10, 0xF0, 0x80, 0xF0, // 2
-    0xF0, 0x10, 0xF0, 0x10, 0xF0, // 3
-    0x90, 0x90, 0xF0, 0x10, 0x10, // 4
-    0xF0, 0x80, 0xF0, 0x10, 0xF0, // 5
-    0xF0, 0x80, 0xF0, 0x90, 0xF0, // 6
-    0xF0, 0x10, 0x20, 0x40, 0x40, // 7
-    0xF0, 0x90, 0xF0, 0x90, 0xF0, // 8
-    0xF0, 0x90, 0xF0, 0x10, 0xF0, // 9
-    0xF0, 0x90, 0xF0, 0x90, 0x90, // A
-    0xE0, 0x90, 0xE0, 0x90, 0xE0, // B
-    0xF0, 0x80, 0x80, 0x80, 0xF0, // C
-    0xE0, 0x90, 0x90, 0x90, 0xE0, // D
-    0xF0, 0x80, 0xF0, 0x80, 0xF0, // E
-    0xF0, 0x80, 0xF0, 0x80, 0x80, // F
-];
 
 /// Emulator emulates the Chip8 CPU.
-pub struct Emulator {
-    /// Memory represents the chip8_core's memory.
-    memory: [u8; MEMORY_SIZE],
+///
+/// The random numbers come from `R`, a seeded [`RandomSource`] unless another [`Rng`] is
+/// injected with [`Emulator::with_rng`]. The memory is accessed through the bus `B`, a flat
+/// [`Ram`] unless another [`Bus`] is plugged in with [`Emulator::with_bus`].
+pub struct Emulator<R: Rng = RandomSource, B: Bus = Ram> {
+    /// Memory represents the chip8_core's memory, all accesses go through the bus.
+    memory: B,
+    /// How the CPU's accesses outside of the ROM's memory are checked.
+    memory_protection: MemoryProtection,
+    /// The behaviours of the interpreter the ROM was written for.
+    quirks: Quirks,
+    /// The amount of instructions executed per frame.
+    instructions_per_frame: u32,
+    /// The amount of instructions of the current frame which already ran, 0 between frames.
+    frame_position: u32,
+    /// The amount of instructions executed since the emulator was created.
+    instructions_executed: u64,
     /// Registers holds the general purpose registers.
     registers: [u8; NUMBER_OF_REGISTERS],
     /// The index register store memory addresses.
@@ -45,22 +60,84 @@ pub struct Emulator {
     /// The sound timer register. It is decremented at a rate of 60 Hz until it reaches 0.
     /// It plays a beeping sound when it's value is different from 0.
     sound_timer: u8,
-    /// The stack pointer register.
+    /// The stack pointer register, the amount of return addresses on the call stack.
     stack_pointer: u8,
-    /// The stack of the chip8_core.
+    /// The stack of the chip8_core. Its capacity limits the call depth in both stack modes.
     stack: Stack<u16>,
+    /// Where the return addresses are kept.
+    stack_mode: StackMode,
     /// Holds the display data, each bit corresponds to a pixel.
-    display_data: [bool; DISPLAY_WIDTH * DISPLAY_HEIGHT],
+    display_data: Framebuffer,
+    /// The region of the display changed since the frontend last took it, None if unchanged.
+    dirty_region: Option<DirtyRect>,
     /// Tracks the last key pressed by the user.
     last_key_pressed: Option<u8>,
+    /// The loaded ROM as it was read, restored by [`Emulator::reset`]. Without std the
+    /// memory is too scarce for a second copy.
+    #[cfg(feature = "std")]
+    rom_data: Vec<u8>,
+    /// The random number generator.
+    rng: R,
+    /// Records the executed instructions, see [`Emulator::set_trace`].
+    #[cfg(feature = "std")]
+    trace: Option<TraceWriter>,
+    /// Counts the executed instructions, see [`Emulator::set_profiler`].
+    #[cfg(feature = "std")]
+    profiler: Option<Profiler>,
 }
 
 impl Emulator {
     /// Creates a new `Emulator` instance.
     ///
+    /// The random number generator is seeded from the operating system's randomness, or
+    /// with 0 without std.
     pub fn new() -> Emulator {
+        #[cfg(feature = "std")]
+        let rng = RandomSource::from_entropy();
+        #[cfg(not(feature = "std"))]
+        let rng = RandomSource::new(RandomMode::XorShift, 0);
+        Emulator::with_rng(rng)
+    }
+
+    /// Creates a new `Emulator` instance whose random numbers are generated from the seed.
+    pub fn with_seed(mode: RandomMode, seed: u64) -> Emulator {
+        Emulator::with_rng(RandomSource::new(mode, seed))
+    }
+
+    /// Reseeds the random number generator, keeping its mode.
+    pub fn set_seed(&mut self, seed: u64) {
+        self.rng = RandomSource::new(self.rng.mode(), seed);
+    }
+
+    /// Replaces the random number generator by a seeded generator of the given mode.
+    pub fn set_random_mode(&mut self, mode: RandomMode, seed: u64) {
+        self.rng = RandomSource::new(mode, seed);
+    }
+
+    /// Returns the random number generator.
+    pub fn rng(&self) -> &RandomSource {
+        &self.rng
+    }
+}
+
+impl<R: Rng> Emulator<R> {
+    /// Creates a new `Emulator` instance drawing its random numbers from `rng`.
+    pub fn with_rng(rng: R) -> Emulator<R> {
+        Emulator::with_bus(rng, Ram::new())
+    }
+}
+
+impl<R: Rng, B: Bus> Emulator<R, B> {
+    /// Creates a new `Emulator` instance drawing its random numbers from `rng` and accessing
+    /// the memory through `bus`.
+    pub fn with_bus(rng: R, bus: B) -> Emulator<R, B> {
         let mut emulator = Emulator {
-            memory: [0; MEMORY_SIZE],
+            memory: bus,
+            memory_protection: MemoryProtection::Off,
+            quirks: Quirks::default(),
+            instructions_per_frame: INSTRUCTIONS_PER_FRAME,
+            frame_position: 0,
+            instructions_executed: 0,
             registers: [0; NUMBER_OF_REGISTERS],
             index_register: 0,
             program_counter: 0,
@@ -68,8 +145,17 @@ impl Emulator {
             sound_timer: 0,
             stack_pointer: 0,
             stack: Stack::new(),
-            display_data: [false; DISPLAY_WIDTH * DISPLAY_HEIGHT],
-            last_key_pressed: None
+            stack_mode: StackMode::Internal,
+            display_data: Framebuffer::new(),
+            dirty_region: Some(DirtyRect::FULL),
+            last_key_pressed: None,
+            #[cfg(feature = "std")]
+            rom_data: Vec::new(),
+            rng,
+            #[cfg(feature = "std")]
+            trace: None,
+            #[cfg(feature = "std")]
+            profiler: None,
         };
 
         emulator.load_font_data();
@@ -79,25 +165,348 @@ impl Emulator {
 
     fn load_font_data(&mut self) {
         info!("Loading font data...");
-        FONT_SPRITES
-            .iter()
-            .enumerate()
-            .for_each(|i| self.memory[0xf0 + i.0] = *i.1);
+        for (offset, byte) in FONT_SPRITES.iter().enumerate() {
+            if let Err(error) = self.memory.write(FONT_START + offset as u16, *byte) {
+                warn!("Failed to load the font: {error}");
+                return;
+            }
+        }
         info!("Loaded font data into memory at 0xf0.");
     }
 
-    pub fn execute_and_fetch(&mut self) -> Result<(), anyhow::Error> {
-        for _ in 0..=7 {
-            // fetch instruction & decode it
-            let instruction = self.fetch_instruction()?;
-            self.program_counter += 2;
+    /// Returns the memory bus.
+    pub fn bus(&self) -> &B {
+        &self.memory
+    }
+
+    /// Returns the memory bus, for example to configure its devices.
+    pub fn bus_mut(&mut self) -> &mut B {
+        &mut self.memory
+    }
+
+    /// Sets how the CPU's accesses outside of the ROM's memory are checked.
+    pub fn set_memory_protection(&mut self, protection: MemoryProtection) {
+        self.memory_protection = protection;
+    }
+
+    /// Returns how the CPU's accesses outside of the ROM's memory are checked.
+    pub fn memory_protection(&self) -> MemoryProtection {
+        self.memory_protection
+    }
+
+    /// Sets the behaviours of the interpreter the ROM was written for.
+    pub fn set_quirks(&mut self, quirks: Quirks) {
+        self.quirks = quirks;
+    }
+
+    /// Returns the behaviours of the interpreter the ROM was written for.
+    pub fn quirks(&self) -> Quirks {
+        self.quirks
+    }
+
+    /// Sets the amount of instructions executed per frame, at least 1.
+    pub fn set_instructions_per_frame(&mut self, instructions_per_frame: u32) {
+        self.instructions_per_frame = instructions_per_frame.max(1);
+    }
+
+    /// Returns the amount of instructions executed per frame.
+    pub fn instructions_per_frame(&self) -> u32 {
+        self.instructions_per_frame
+    }
+
+    /// Reads a byte of memory for the CPU.
+    fn read_memory(&mut self, address: u16) -> Result<u8, Error> {
+        let address = self.check_address(address, false)?;
+        self.memory.read(address)
+    }
+
+    /// Writes a byte of memory for the CPU.
+    fn write_memory(&mut self, address: u16, value: u8) -> Result<(), Error> {
+        let address = self.check_address(address, true)?;
+        self.memory.write(address, value)
+    }
+
+    /// Applies the memory protection to an access and returns the address to access.
+    fn check_address(&self, address: u16, write: bool) -> Result<u16, Error> {
+        let protection = self.memory_protection;
+        if protection == MemoryProtection::Off {
+            return Ok(address);
+        }
+        let mut address = address;
+        if address as usize >= MEMORY_SIZE {
+            match protection {
+                MemoryProtection::Error => return Err(Error::AddressOutOfRange { address }),
+                MemoryProtection::Log => warn!(
+                    "Access to {address:04x} past the end of memory at {:04x}",
+                    self.program_counter.wrapping_sub(2)
+                ),
+                _ => {}
+            }
+            address %= MEMORY_SIZE as u16;
+        }
+        if write && (address as usize) < ROM_START {
+            match protection {
+                MemoryProtection::Error => return Err(Error::ReservedWrite { address }),
+                MemoryProtection::Log => warn!(
+                    "Write into the reserved memory at {address:04x} from {:04x}",
+                    self.program_counter.wrapping_sub(2)
+                ),
+                _ => {}
+            }
+        }
+        Ok(address)
+    }
+
+    /// Configures the call stack, which is emptied.
+    ///
+    /// The capacity is the maximum call depth: 16 for CHIP-8, at most 24 in memory.
+    pub fn configure_stack(
+        &mut self,
+        mode: StackMode,
+        capacity: usize,
+    ) -> Result<(), Error> {
+        let max_capacity = match mode {
+            StackMode::Internal => MAX_STACK_CAPACITY,
+            StackMode::Memory => (VIP_STACK_TOP - VIP_STACK_BOTTOM) / 2,
+        };
+        if capacity == 0 || capacity > max_capacity {
+            return Err(Error::InvalidStackCapacity {
+                capacity,
+                max_capacity,
+            });
+        }
+        self.stack = Stack::with_capacity(capacity);
+        self.stack_mode = mode;
+        self.stack_pointer = 0;
+        Ok(())
+    }
+
+    /// Returns where the call stack is kept.
+    pub fn stack_mode(&self) -> StackMode {
+        self.stack_mode
+    }
+
+    /// Returns the maximum call depth.
+    pub fn stack_capacity(&self) -> usize {
+        self.stack.capacity()
+    }
 
-            // execute
-            self.execute_instruction(instruction)?;
+    /// Returns the amount of return addresses on the call stack.
+    pub fn stack_pointer(&self) -> u8 {
+        self.stack_pointer
+    }
+
+    /// Returns the return addresses on the call stack, from the outermost call to the innermost.
+    pub fn stack_frames(&self) -> impl Iterator<Item = u16> + '_ {
+        (0..self.stack_pointer as usize).map(|depth| match self.stack_mode {
+            StackMode::Internal => self.stack.elements()[depth],
+            StackMode::Memory => {
+                let slot = Self::memory_stack_slot(depth);
+                let high = self.memory.peek(slot).unwrap_or_default();
+                let low = self.memory.peek(slot + 1).unwrap_or_default();
+                u16::from_be_bytes([high, low])
+            }
+        })
+    }
+
+    /// Returns the address of the return address at the given depth in [`StackMode::Memory`].
+    fn memory_stack_slot(depth: usize) -> u16 {
+        (VIP_STACK_TOP - 2 * (depth + 1)) as u16
+    }
+
+    /// Returns the error of a failed stack operation by the current instruction.
+    fn stack_error(&self, error: StackError) -> Error {
+        Error::Stack {
+            error,
+            address: self.program_counter.wrapping_sub(2),
+        }
+    }
+
+    fn push_stack(&mut self, address: u16) -> Result<(), Error> {
+        if self.stack_pointer as usize >= self.stack.capacity() {
+            return Err(self.stack_error(StackError::Overflow));
+        }
+        match self.stack_mode {
+            StackMode::Internal => self
+                .stack
+                .push(address)
+                .map_err(|error| self.stack_error(error))?,
+            StackMode::Memory => {
+                let slot = Self::memory_stack_slot(self.stack_pointer as usize);
+                let [high, low] = address.to_be_bytes();
+                self.write_memory(slot, high)?;
+                self.write_memory(slot + 1, low)?;
+            }
         }
+        self.stack_pointer += 1;
         Ok(())
     }
 
+    fn pop_stack(&mut self) -> Result<u16, Error> {
+        if self.stack_pointer == 0 {
+            return Err(self.stack_error(StackError::Underflow));
+        }
+        self.stack_pointer -= 1;
+        match self.stack_mode {
+            StackMode::Internal => self.stack.pop().map_err(|error| self.stack_error(error)),
+            StackMode::Memory => {
+                let slot = Self::memory_stack_slot(self.stack_pointer as usize);
+                let high = self.read_memory(slot)?;
+                let low = self.read_memory(slot + 1)?;
+                Ok(u16::from_be_bytes([high, low]))
+            }
+        }
+    }
+
+    /// Runs the instructions of a frame, or the rest of the frame [`Emulator::step`] started.
+    pub fn execute_and_fetch(&mut self) -> Result<(), Error> {
+        while !self.execute_next_instruction()? {}
+        Ok(())
+    }
+
+    /// Runs one 60 Hz frame: the timers count down, then the instructions of the frame run.
+    ///
+    /// A frame which [`Emulator::step`] started is finished without counting down again.
+    pub fn run_frame(&mut self) -> Result<(), Error> {
+        if self.frame_position == 0 {
+            self.handle_timers();
+        }
+        self.execute_and_fetch()
+    }
+
+    /// Runs a single instruction, counting the timers down when it starts a frame, so stepping
+    /// through a frame instruction by instruction matches [`Emulator::run_frame`].
+    pub fn step(&mut self) -> Result<(), Error> {
+        if self.frame_position == 0 {
+            self.handle_timers();
+        }
+        self.execute_next_instruction()?;
+        Ok(())
+    }
+
+    /// Returns the address of the next instruction.
+    pub fn program_counter(&self) -> u16 {
+        self.program_counter
+    }
+
+    /// Returns the general purpose registers V0 to VF.
+    pub fn registers(&self) -> &[u8; NUMBER_OF_REGISTERS] {
+        &self.registers
+    }
+
+    /// Returns the index register I, the address memory instructions read and write.
+    pub fn index_register(&self) -> u16 {
+        self.index_register
+    }
+
+    /// Returns the delay timer, which counts down to 0 once per frame.
+    pub fn delay_timer(&self) -> u8 {
+        self.delay_timer
+    }
+
+    /// Returns the sound timer, which counts down to 0 once per frame and beeps while above 0.
+    pub fn sound_timer(&self) -> u8 {
+        self.sound_timer
+    }
+
+    /// Returns the amount of instructions of the current frame which already ran.
+    pub fn frame_position(&self) -> u32 {
+        self.frame_position
+    }
+
+    /// Records the instructions executed from now on into the trace, or stops recording.
+    #[cfg(feature = "std")]
+    pub fn set_trace(&mut self, trace: Option<TraceWriter>) {
+        self.trace = trace;
+    }
+
+    /// Stops recording and returns the trace, to [`TraceWriter::finish`] it.
+    #[cfg(feature = "std")]
+    pub fn take_trace(&mut self) -> Option<TraceWriter> {
+        self.trace.take()
+    }
+
+    /// Profiles the instructions executed from now on, or stops profiling.
+    #[cfg(feature = "std")]
+    pub fn set_profiler(&mut self, profiler: Option<Profiler>) {
+        self.profiler = profiler;
+    }
+
+    /// Returns the profiler, if any.
+    #[cfg(feature = "std")]
+    pub fn profiler(&self) -> Option<&Profiler> {
+        self.profiler.as_ref()
+    }
+
+    /// Stops profiling and returns the profiler, to report its counts.
+    #[cfg(feature = "std")]
+    pub fn take_profiler(&mut self) -> Option<Profiler> {
+        self.profiler.take()
+    }
+
+    /// Returns the amount of instructions executed since the emulator was created, resets
+    /// included, for measuring the instructions per second.
+    pub fn instructions_executed(&self) -> u64 {
+        self.instructions_executed
+    }
+
+    /// Fetches and executes the next instruction and returns true if it ended the frame.
+    fn execute_next_instruction(&mut self) -> Result<bool, Error> {
+        // fetch instruction & decode it
+        let address = self.program_counter;
+        let instruction = self.fetch_instruction()?;
+        let opcode = instruction.raw();
+        let processor_instruction = instruction.processor_instruction();
+        trace!("{address:04X} {opcode:04X} {processor_instruction}");
+        self.program_counter += 2;
+        let draws = matches!(processor_instruction, ProcessorInstruction::Draw { .. });
+
+        // execute
+        #[cfg(feature = "std")]
+        let before = self
+            .trace
+            .as_ref()
+            .filter(|trace| trace.wants(address, &processor_instruction))
+            .map(|_| (self.registers, self.index_register));
+        let result = self.execute_instruction(instruction);
+        // a failed instruction is recorded too, the trace tells where the ROM went wrong
+        #[cfg(feature = "std")]
+        if let (Some(trace), Some((registers, index_register))) = (self.trace.as_mut(), before) {
+            trace.record(&TraceRecord::new(
+                self.instructions_executed,
+                address,
+                opcode,
+                (&registers, index_register),
+                (&self.registers, self.index_register),
+            ));
+            if let Err(error) = &result {
+                trace.record_failure(error);
+            }
+        }
+        result?;
+        #[cfg(feature = "std")]
+        if let Some(mut profiler) = self.profiler.take() {
+            // a blocking key wait runs the same instruction again
+            let waited = self.program_counter == address;
+            profiler.record(address, &processor_instruction, self.stack_frames(), waited);
+            self.profiler = Some(profiler);
+        }
+
+        // with the vblank quirk the next instruction runs after the display refreshed
+        self.instructions_executed += 1;
+        self.frame_position += 1;
+        let frame_over =
+            self.frame_position >= self.instructions_per_frame || (draws && self.quirks.vblank);
+        if frame_over {
+            self.frame_position = 0;
+            #[cfg(feature = "std")]
+            if let Some(profiler) = self.profiler.as_mut() {
+                profiler.end_frame();
+            }
+        }
+        Ok(frame_over)
+    }
+
     /// Handles the timers logic.
     pub fn handle_timers(&mut self) {
         // Handle timers
@@ -114,6 +523,11 @@ impl Emulator {
         self.sound_timer > 0
     }
 
+    /// Returns the key currently pressed.
+    pub fn key_pressed(&self) -> Option<u8> {
+        self.last_key_pressed
+    }
+
     /// Handle the input
     pub fn handle_input(&mut self, key_pressed: Option<u16>) {
         if let Some(key_pressed) = key_pressed {
@@ -124,59 +538,75 @@ impl Emulator {
     }
 
     /// Executes the instruction
-    fn execute_instruction(&mut self, instruction: Instruction) -> Result<(), anyhow::Error> {
+    fn execute_instruction(&mut self, instruction: Instruction) -> Result<(), Error> {
         match instruction.processor_instruction() {
             ProcessorInstruction::ClearScreen => {
-                trace!("Clear display");
-                self.display_data = [false; DISPLAY_WIDTH * DISPLAY_HEIGHT];
+                self.display_data.clear();
+                self.mark_dirty(DirtyRect::FULL);
             }
             ProcessorInstruction::Jump { address } => {
-                trace!("Jump to address {:04x}", address);
                 self.program_counter = address
             }
             ProcessorInstruction::SetRegister { register, data } => {
-                trace!("Set register {} to data {:04x}", register, data);
                 self.registers[register as usize] = data
             }
             ProcessorInstruction::AddValueToRegister { register, value } => {
-                trace!("Add to register {} data {:04x}", register, value);
                 let (result, _) = self.registers[register as usize].overflowing_add(value);
                 self.registers[register as usize] = result;
             }
             ProcessorInstruction::SetIndexRegister { data } => {
-                trace!("Set index register to data {:04x}", data);
                 self.index_register = data;
             }
             ProcessorInstruction::Draw { vx, vy, rows } => {
-                trace!("Draw vx_register={vx} vy_register={vy} pixels={rows}");
-                let x_coordinate = self.registers[vx as usize];
-                let y_coordinate = self.registers[vy as usize];
+                // The coordinates wrap, the sprite is clipped at the edges without the wrap quirk
+                let x_coordinate = self.registers[vx as usize] as usize % DISPLAY_WIDTH;
+                let y_coordinate = self.registers[vy as usize] as usize % DISPLAY_HEIGHT;
 
                 // Keep track if any pixels were flipped
                 let mut flipped = false;
+                // Keep track of the region which changed
+                let mut changed_region: Option<DirtyRect> = None;
 
                 // Iterate over each row of our sprite
-                for y_line in 0..rows {
+                for y_line in 0..rows as usize {
                     // Determine which memory address our row's data is stored
-                    let addr = self.index_register + y_line as u16;
-                    let pixels = self.memory[addr as usize];
-                    // Iterate over each column in our row
-                    for x_line in 0..8 {
-                        // Use a mask to fetch current pixel's bit. Only flip if a 1
-                        if (pixels & (0b1000_0000 >> x_line)) != 0 {
-                            // Sprites should wrap around screen, so apply modulo
-                            let x = (x_coordinate + x_line) as usize % DISPLAY_WIDTH;
-                            let y = (y_coordinate + y_line) as usize % DISPLAY_HEIGHT;
-
-                            // Get our pixel's index for our 1D screen array
-                            let index = x + DISPLAY_WIDTH * y;
-                            // Check if we're about to flip the pixel and set
-                            flipped |= self.display_data[index];
-                            self.display_data[index] ^= true;
-                        }
+                    // The rows clipped at the bottom are not read
+                    if !self.quirks.wrap && y_coordinate + y_line >= DISPLAY_HEIGHT {
+                        break;
+                    }
+                    let addr = self.index_register.wrapping_add(y_line as u16);
+                    let pixels = self.read_memory(addr)?;
+                    let y = (y_coordinate + y_line) % DISPLAY_HEIGHT;
+                    // XOR the whole row, the framebuffer wraps it around the right edge
+                    let (mask, collision) = if self.quirks.wrap {
+                        self.display_data.xor_sprite_row(x_coordinate, y, pixels)
+                    } else {
+                        self.display_data
+                            .xor_sprite_row_clipped(x_coordinate, y, pixels)
+                    };
+                    flipped |= collision;
+
+                    if mask != 0 {
+                        // The leftmost pixel is in the most significant bit
+                        let first = mask.leading_zeros() as usize;
+                        let last = DISPLAY_WIDTH - 1 - mask.trailing_zeros() as usize;
+                        let row_region = DirtyRect {
+                            x: first,
+                            y,
+                            width: last - first + 1,
+                            height: 1,
+                        };
+                        changed_region = Some(match changed_region {
+                            Some(region) => region.union(&row_region),
+                            None => row_region,
+                        });
                     }
                 }
 
+                if let Some(region) = changed_region {
+                    self.mark_dirty(region);
+                }
+
                 if flipped {
                     self.registers[0xF] = 1;
                 } else {
@@ -184,35 +614,31 @@ impl Emulator {
                 }
             }
             ProcessorInstruction::Return => {
-                let value = self.stack.pop().unwrap();
-                trace!("Return to {value:04x}");
+                let value = self.pop_stack()?;
                 self.program_counter = value;
             }
             ProcessorInstruction::Call { address } => {
-                trace!("Call {address:04x}");
                 // Save PC to the stack
-                self.stack.push(self.program_counter);
+                self.push_stack(self.program_counter)?;
                 // Set PC to subroutine address
                 self.program_counter = address;
             }
             ProcessorInstruction::Set { vx, vy } => {
-                trace!("Set VX={vx:04x} VY={vy:04x}");
                 self.registers[vx as usize] = self.registers[vy as usize];
             }
             ProcessorInstruction::BinaryOr { vx, vy } => {
-                trace!("BinaryOr VX={vx:04x} VY={vy:04x}");
-                self.registers[vx as usize] |= self.registers[vy as usize]
+                self.registers[vx as usize] |= self.registers[vy as usize];
+                self.reset_flag_after_logic();
             }
             ProcessorInstruction::BinaryAnd { vx, vy } => {
-                trace!("BinaryAnd VX={vx:04x} VY={vy:04x}");
-                self.registers[vx as usize] &= self.registers[vy as usize]
+                self.registers[vx as usize] &= self.registers[vy as usize];
+                self.reset_flag_after_logic();
             }
             ProcessorInstruction::BinaryXor { vx, vy } => {
-                trace!("BinaryXor VX={vx:04x} VY={vy:04x}");
-                self.registers[vx as usize] ^= self.registers[vy as usize]
+                self.registers[vx as usize] ^= self.registers[vy as usize];
+                self.reset_flag_after_logic();
             }
             ProcessorInstruction::Add { vx, vy } => {
-                trace!("Add VX={vx:04x} VY={vy:04x}");
                 let (result, overflow) =
                     self.registers[vx as usize].overflowing_add(self.registers[vy as usize]);
 
@@ -224,65 +650,52 @@ impl Emulator {
                 }
             }
             ProcessorInstruction::SubtractVX { vx, vy } => {
-                trace!("SubtractVX VX={vx:04x} VY={vy:04x}");
-                if self.registers[vx as usize] > self.registers[vy as usize] {
-                    self.registers[0xF] = 1
-                } else {
-                    // The register 0xF will be 0 if there's an underflow.
-                    self.registers[0xF] = 0
-                }
-                let (result, _) =
-                    self.registers[vx as usize].overflowing_sub(self.registers[vy as usize]);
-                self.registers[vx as usize] = result;
+                // VF is NOT borrow: 1 unless there's an underflow, so equal values set it
+                let flag = (self.registers[vx as usize] >= self.registers[vy as usize]) as u8;
+                let result = self.registers[vx as usize].wrapping_sub(self.registers[vy as usize]);
+                self.write_result_and_flag(vx, result, flag);
             }
             ProcessorInstruction::SubtractVY { vx, vy } => {
-                trace!("SubtractVY VX={vx:04x} VY={vy:04x}");
-                if self.registers[vy as usize] > self.registers[vx as usize] {
-                    self.registers[0xF] = 1
-                } else {
-                    // The register 0xF will be 0 if there's an underflow.
-                    self.registers[0xF] = 0
-                }
-                let (result, _) =
-                    self.registers[vy as usize].overflowing_sub(self.registers[vx as usize]);
-                self.registers[vx as usize] = result;
+                // VF is NOT borrow: 1 unless there's an underflow, so equal values set it
+                let flag = (self.registers[vy as usize] >= self.registers[vx as usize]) as u8;
+                let result = self.registers[vy as usize].wrapping_sub(self.registers[vx as usize]);
+                self.write_result_and_flag(vx, result, flag);
             }
             ProcessorInstruction::ShiftLeft { vx, vy } => {
-                trace!("ShiftLeft VX={vx:04x} VY={vy:04x}");
-                self.registers[0xF] = (self.registers[vx as usize] >> 7) & 1;
-                self.registers[vx as usize] <<= 1;
+                let source = self.shift_source(vx, vy);
+                let flag = (self.registers[source as usize] >> 7) & 1;
+                let result = self.registers[source as usize] << 1;
+                self.write_result_and_flag(vx, result, flag);
             }
             ProcessorInstruction::ShiftRight { vx, vy } => {
-                trace!("ShiftRight VX={vx:04x} VY={vy:04x}");
-                self.registers[0xF] = self.registers[vx as usize] & 0x1;
-                self.registers[vx as usize] >>= 1;
+                let source = self.shift_source(vx, vy);
+                let flag = self.registers[source as usize] & 0x1;
+                let result = self.registers[source as usize] >> 1;
+                self.write_result_and_flag(vx, result, flag);
             }
             ProcessorInstruction::JumpWithOffset { address } => {
-                let offset = self.registers[0x0];
-                trace!("Jump With offset Address={address:04x} Offset={offset:04x}");
+                // With the jump quirk BXNN adds VX instead of V0
+                let register = if self.quirks.jump { (address >> 8) & 0xF } else { 0 };
+                let offset = self.registers[register as usize];
 
                 self.program_counter = address + offset as u16
             }
             ProcessorInstruction::GenerateRandomNumber { vx, mask } => {
-                trace!("Generate random number");
-                self.registers[vx as usize] = rand::thread_rng().gen_range(0x00..0xFF) & mask
+                self.registers[vx as usize] = self.rng.next_byte() & mask
             }
             ProcessorInstruction::SkipEqualVXData { vx, data } => {
-                trace!("SkipEqualVXData");
                 let vx_data = self.registers[vx as usize];
                 if vx_data == data {
                     self.program_counter += 2
                 }
             }
             ProcessorInstruction::SkipNotEqualVXData { vx, data } => {
-                trace!("SkipNotEqualVXData");
                 let vx_data = self.registers[vx as usize];
                 if vx_data != data {
                     self.program_counter += 2
                 }
             }
             ProcessorInstruction::SkipEqualVXVY { vx, vy } => {
-                trace!("SkipNotEqualVXData");
                 let vx_data = self.registers[vx as usize];
                 let vy_data = self.registers[vy as usize];
                 if vx_data == vy_data {
@@ -290,7 +703,6 @@ impl Emulator {
                 }
             }
             ProcessorInstruction::SkipNotEqualVXVY { vx, vy } => {
-                trace!("SkipNotEqualVXVY");
                 let vx_data = self.registers[vx as usize];
                 let vy_data = self.registers[vy as usize];
                 if vx_data != vy_data {
@@ -298,19 +710,15 @@ impl Emulator {
                 }
             }
             ProcessorInstruction::SetVXToDelayTimer { vx } => {
-                trace!("SetVXToDelayTimer");
                 self.registers[vx as usize] = self.delay_timer
             }
             ProcessorInstruction::SetDelayTimer { vx } => {
-                trace!("SetDelayTimer");
                 self.delay_timer = self.registers[vx as usize]
             }
             ProcessorInstruction::SetSoundTimer { vx } => {
-                trace!("SetSoundTimer");
                 self.sound_timer = self.registers[vx as usize]
             }
             ProcessorInstruction::AddToIndex { vx } => {
-                trace!("AddToIndex");
                 let (result, overflow) = self
                     .index_register
                     .overflowing_add(self.registers[vx as usize] as u16);
@@ -322,32 +730,30 @@ impl Emulator {
                 }
             }
             ProcessorInstruction::FontCharacter { vx } => {
-                trace!("FontCharacter");
-                self.index_register = 0xF0 + (self.registers[vx as usize] as u16 & 0xF) * 5u16;
+                self.index_register = FONT_START + (self.registers[vx as usize] as u16 & 0xF) * 5u16;
             }
             ProcessorInstruction::BinaryCodedDecimalConversion { vx } => {
-                trace!("BinaryCodedDecimalConversion");
                 let number = self.registers[vx as usize];
-                self.memory[self.index_register as usize] = number / 100;
-                self.memory[self.index_register as usize + 1] = (number / 10) % 10;
-                self.memory[self.index_register as usize + 2] = ((number) % 100) % 10;
+                let address = self.index_register;
+                self.write_memory(address, number / 100)?;
+                self.write_memory(address.wrapping_add(1), (number / 10) % 10)?;
+                self.write_memory(address.wrapping_add(2), ((number) % 100) % 10)?;
             }
             ProcessorInstruction::LoadMemory { vx } => {
-                trace!("LoadMemory");
                 for i in 0..=vx {
-                    let memory_index = (self.index_register + (i as u16)) as usize;
-                    self.registers[i as usize] = self.memory[memory_index];
+                    let memory_index = self.index_register.wrapping_add(i as u16);
+                    self.registers[i as usize] = self.read_memory(memory_index)?;
                 }
+                self.increment_index_after_memory(vx);
             }
             ProcessorInstruction::StoreMemory { vx } => {
-                trace!("StoreMemory");
                 for i in 0..=vx {
-                    let memory_index = (self.index_register + (i as u16)) as usize;
-                    self.memory[memory_index] = self.registers[i as usize];
+                    let memory_index = self.index_register.wrapping_add(i as u16);
+                    self.write_memory(memory_index, self.registers[i as usize])?;
                 }
+                self.increment_index_after_memory(vx);
             }
             ProcessorInstruction::GetKeyBlocking { vx } => {
-                trace!("GetKeyBlocking");
                 if let Some(key) = self.last_key_pressed {
                     self.registers[vx as usize] = key;
                 } else {
@@ -355,7 +761,6 @@ impl Emulator {
                 }
             }
             ProcessorInstruction::SkipIfKeyIsPressed { vx } => {
-                trace!("SkipIfKeyIsPressed");
                 if let Some(key) = self.last_key_pressed {
                     if self.registers[vx as usize] == key {
                         self.program_counter += 2;
@@ -363,7 +768,6 @@ impl Emulator {
                 }
             }
             ProcessorInstruction::SkipIfKeyIsNotPressed { vx } => {
-                trace!("SkipIfKeyIsNotPressed");
                 if let Some(key) = self.last_key_pressed {
                     if self.registers[vx as usize] != key {
                         self.program_counter += 2;
@@ -379,36 +783,172 @@ impl Emulator {
         Ok(())
     }
 
-    /// Fetches the current instruction from the memory without incrementing the program counter.
-    fn fetch_instruction(&self) -> Result<Instruction, anyhow::Error> {
-        if self.program_counter as usize >= self.memory.len() {
-            return Err(anyhow!("program_counter is out of range"));
+    /// Returns the register shifted by `8XY6` and `8XYE`, VX with the shift quirk and VY without.
+    fn shift_source(&self, vx: u8, vy: u8) -> u8 {
+        if self.quirks.shift {
+            vx
+        } else {
+            vy
         }
+    }
 
-        Ok(Instruction::new([
-            self.memory[self.program_counter as usize],
-            self.memory[self.program_counter as usize + 1],
-        ]))
+    /// Writes the result of a subtraction or shift, computed from the original operands, to VX
+    /// and its flag to VF.
+    ///
+    /// With the vfOrder quirk the flag is written first, so a VF destination keeps the result
+    /// instead of the flag.
+    fn write_result_and_flag(&mut self, vx: u8, result: u8, flag: u8) {
+        if self.quirks.vf_order {
+            self.registers[0xF] = flag;
+            self.registers[vx as usize] = result;
+        } else {
+            self.registers[vx as usize] = result;
+            self.registers[0xF] = flag;
+        }
+    }
+
+    /// Resets VF after `8XY1`, `8XY2` and `8XY3` with the logic quirk.
+    fn reset_flag_after_logic(&mut self) {
+        if self.quirks.logic {
+            self.registers[0xF] = 0;
+        }
+    }
+
+    /// Moves I past the registers loaded or stored by `FX55` and `FX65`, as the quirks say.
+    fn increment_index_after_memory(&mut self, vx: u8) {
+        if self.quirks.memory_leave_i_unchanged {
+            return;
+        }
+        let increment = if self.quirks.memory_increment_by_x {
+            vx as u16
+        } else {
+            vx as u16 + 1
+        };
+        self.index_register = self.index_register.wrapping_add(increment);
+    }
+
+    /// Fetches the current instruction from the memory without incrementing the program counter.
+    fn fetch_instruction(&mut self) -> Result<Instruction, Error> {
+        let out_of_range = |_| Error::ProgramCounterOutOfRange {
+            address: self.program_counter,
+        };
+        let high = self.memory.read(self.program_counter).map_err(out_of_range)?;
+        let low = self
+            .memory
+            .read(self.program_counter.wrapping_add(1))
+            .map_err(out_of_range)?;
+
+        Ok(Instruction::new([high, low]))
+    }
+
+    /// Marks a region of the display as changed.
+    fn mark_dirty(&mut self, region: DirtyRect) {
+        self.dirty_region = Some(match self.dirty_region {
+            Some(dirty_region) => dirty_region.union(&region),
+            None => region,
+        });
+    }
+
+    /// Returns true if the display changed since the dirty region was last taken.
+    pub fn is_display_dirty(&self) -> bool {
+        self.dirty_region.is_some()
+    }
+
+    /// Returns the region of the display which changed since it was last taken.
+    pub fn dirty_region(&self) -> Option<DirtyRect> {
+        self.dirty_region
+    }
+
+    /// Returns the region of the display which changed and marks the display as clean.
+    ///
+    /// Frontends call this once per frame and skip rendering when it returns None.
+    pub fn take_dirty_region(&mut self) -> Option<DirtyRect> {
+        self.dirty_region.take()
     }
 
     /// Returns the display buffer of the emulator.
-    pub fn get_display_buffer(&self) -> [bool; 2048] {
-        self.display_data
+    pub fn get_display_buffer(&self) -> &Framebuffer {
+        &self.display_data
     }
 
     /// Loads the ROM found at the rom path in the chip8_core's RAM memory.
-    pub fn load_rom<T>(&mut self, mut rom: T) -> Result<(), anyhow::Error>
+    pub fn load_rom<T>(&mut self, mut rom: T) -> Result<(), Error>
     where
         T: Reader,
     {
-        let amount = rom.read(&mut self.memory[0x200..])?;
+        let mut buffer = [0; MEMORY_SIZE - ROM_START];
+        let mut amount = 0;
+        // readers may return less than asked for, read until the ROM is over
+        loop {
+            if amount == buffer.len() {
+                if rom.read(&mut [0])? > 0 {
+                    return Err(Error::RomTooLarge {
+                        available: buffer.len(),
+                    });
+                }
+                break;
+            }
+            match rom.read(&mut buffer[amount..])? {
+                0 => break,
+                read => amount += read,
+            }
+        }
+        for (offset, byte) in buffer[..amount].iter().enumerate() {
+            self.memory.write((ROM_START + offset) as u16, *byte)?;
+        }
+        #[cfg(feature = "std")]
+        {
+            self.rom_data = buffer[..amount].to_vec();
+        }
 
         debug!("Loaded ROM of size {amount} into memory");
 
         // Set program counter to start of memory
-        self.program_counter = 0x200;
+        self.program_counter = ROM_START as u16;
+        Ok(())
+    }
+
+    /// Restarts the loaded ROM like the reset button of a computer: the registers, timers,
+    /// call stack and display are cleared while the memory keeps what the ROM wrote.
+    pub fn soft_reset(&mut self) {
+        self.registers = [0; NUMBER_OF_REGISTERS];
+        self.index_register = 0;
+        self.program_counter = ROM_START as u16;
+        self.frame_position = 0;
+        self.delay_timer = 0;
+        self.sound_timer = 0;
+        self.stack = Stack::with_capacity(self.stack.capacity());
+        self.stack_pointer = 0;
+        self.display_data.clear();
+        self.dirty_region = Some(DirtyRect::FULL);
+        self.last_key_pressed = None;
+    }
+
+    /// Restores the power-on state with the loaded ROM, as it was before it ran.
+    ///
+    /// The settings, such as the quirks and the stack mode, are kept, and the random numbers
+    /// start over from the seed. Without std the ROM is not kept, create a new emulator and
+    /// load the ROM again instead.
+    #[cfg(feature = "std")]
+    pub fn reset(&mut self) -> Result<(), Error> {
+        self.soft_reset();
+        self.rng.restart();
+        for address in 0..MEMORY_SIZE as u16 {
+            self.memory.write(address, 0)?;
+        }
+        self.load_font_data();
+        for (offset, byte) in self.rom_data.iter().enumerate() {
+            self.memory.write((ROM_START + offset) as u16, *byte)?;
+        }
         Ok(())
     }
+
+    /// Returns the bytes of the loaded ROM as they were read, whatever the ROM or the bus
+    /// changed in memory since.
+    #[cfg(feature = "std")]
+    pub fn rom(&self) -> impl Iterator<Item = u8> + '_ {
+        self.rom_data.iter().copied()
+    }
 }
 
 impl Default for Emulator {
@@ -436,13 +976,20 @@ pub fn tick(last_tick_time: &mut Option<Instant>) -> bool {
 }
 
 #[cfg(test)]
+// the tests predate the lints
+#[allow(clippy::unused_io_amount, clippy::bool_comparison, clippy::assertions_on_constants, clippy::bool_assert_comparison)]
 mod tests {
     use super::*;
+    use crate::quirks::Platform;
+    #[cfg(feature = "std")]
     use crate::read::StdFileReader;
     use pretty_assertions::assert_eq;
     extern crate std;
+    #[cfg(feature = "std")]
     use std::fs::File;
+    #[cfg(feature = "std")]
     use std::io::{Read, Seek, SeekFrom};
+    use std::vec::Vec;
 
     #[test]
     fn test_load_font_data() {
@@ -450,6 +997,7 @@ mod tests {
         assert_eq!(emulator.memory[0xf0..0xf0 + 80], FONT_SPRITES)
     }
 
+    #[cfg(feature = "std")]
     #[test]
     fn test_load_rom_ibm_logo() {
         // Setup
@@ -467,7 +1015,8 @@ mod tests {
             .expect("failed to load ROM");
 
         // Assert
-        assert_eq!(emulator.memory[0x200..0x200 + 132], rom_file_data)
+        assert_eq!(emulator.memory[0x200..0x200 + 132], rom_file_data);
+        assert_eq!(emulator.rom().collect::<Vec<_>>(), rom_file_data)
     }
 
     #[test]
@@ -475,7 +1024,9 @@ mod tests {
         // Setup
         let mut emulator = Emulator::new();
         for i in 10..30 {
-            emulator.display_data[i] = true;
+            emulator
+                .display_data
+                .set(i % DISPLAY_WIDTH, i / DISPLAY_WIDTH, true);
         }
 
         // Test
@@ -484,10 +1035,7 @@ mod tests {
             .expect("Failed to execute");
 
         // Assert
-        assert!(emulator
-            .display_data
-            .iter()
-            .all(|&pixel| { pixel == false }))
+        assert!(emulator.display_data.pixels().all(|pixel| { pixel == false }))
     }
 
     #[test]
@@ -504,9 +1052,10 @@ mod tests {
     #[test]
     fn test_execute_set_register() {
         let mut emulator = Emulator::new();
+        let mut rng = crate::random::XorShift::new(0);
 
         for i in 0x0..=0xF {
-            let random_data: u8 = rand::thread_rng().gen_range(0x00..0xFF);
+            let random_data: u8 = rng.next_byte();
             emulator
                 .execute_instruction(Instruction::new([0x60 + i, random_data]))
                 .expect("Failed to execute");
@@ -554,10 +1103,39 @@ mod tests {
             .execute_instruction(Instruction::new([0xD0, 0x01]))
             .expect("Failed to execute");
 
+        let pixels: [bool; 6] = core::array::from_fn(|x| emulator.display_data.get(x, 0));
+        assert_eq!(pixels, [true, true, true, true, false, false])
+    }
+
+    #[test]
+    fn test_display_dirty_region() {
+        let mut emulator = Emulator::new();
+        assert_eq!(emulator.take_dirty_region(), Some(DirtyRect::FULL));
+        assert!(!emulator.is_display_dirty());
+
+        emulator.index_register = 0xF0;
+        emulator.registers[0] = 62;
+        emulator.registers[1] = 3;
+        emulator
+            .execute_instruction(Instruction::new([0xD0, 0x12]))
+            .expect("Failed to execute");
+
+        // The sprite wraps around the right edge of the display.
         assert_eq!(
-            emulator.display_data[0..=5],
-            [true, true, true, true, false, false]
-        )
+            emulator.take_dirty_region(),
+            Some(DirtyRect {
+                x: 0,
+                y: 3,
+                width: DISPLAY_WIDTH,
+                height: 2
+            })
+        );
+        assert_eq!(emulator.take_dirty_region(), None);
+
+        emulator
+            .execute_instruction(Instruction::new([0x00, 0xE0]))
+            .expect("Failed to execute");
+        assert_eq!(emulator.dirty_region(), Some(DirtyRect::FULL));
     }
 
     #[test]
@@ -571,18 +1149,255 @@ mod tests {
 
         assert_eq!(emulator.program_counter, 0xABC);
         assert_eq!(emulator.stack.peek().unwrap(), &0x200);
+        assert_eq!(emulator.stack_pointer(), 1);
     }
 
     #[test]
     fn test_execute_return() {
         let mut emulator = Emulator::new();
-        emulator.stack.push(0x269);
+        emulator.push_stack(0x269).unwrap();
 
         emulator
             .execute_instruction(Instruction::new([0x00, 0xEE]))
             .expect("Failed to execute");
 
         assert_eq!(emulator.program_counter, 0x269);
+        assert_eq!(emulator.stack_pointer(), 0);
+    }
+
+    #[test]
+    fn test_execute_stack_overflow_and_underflow() {
+        let mut emulator = Emulator::new();
+        emulator.configure_stack(StackMode::Internal, 2).unwrap();
+        emulator.program_counter = 0x202;
+
+        emulator
+            .execute_instruction(Instruction::new([0x00, 0xEE]))
+            .expect_err("Return with an empty stack");
+        for _ in 0..2 {
+            emulator
+                .execute_instruction(Instruction::new([0x22, 0x02]))
+                .expect("Failed to execute");
+        }
+        emulator
+            .execute_instruction(Instruction::new([0x22, 0x02]))
+            .expect_err("Call with a full stack");
+
+        assert_eq!(emulator.stack_frames().collect::<Vec<_>>(), [0x202, 0x202]);
+    }
+
+    #[test]
+    fn test_load_rom_rejects_large_roms() {
+        let fits = [0xAA; MEMORY_SIZE - ROM_START];
+        let too_large = [0xAA; MEMORY_SIZE - ROM_START + 1];
+        let mut emulator = Emulator::new();
+
+        emulator.load_rom(&fits[..]).expect("Failed to load");
+        assert_eq!(emulator.memory.peek(0xFFF), Ok(0xAA));
+        assert_eq!(
+            emulator.load_rom(&too_large[..]),
+            Err(Error::RomTooLarge {
+                available: MEMORY_SIZE - ROM_START
+            })
+        );
+    }
+
+    #[test]
+    fn test_step_keeps_timers_in_sync() {
+        let mut emulator = Emulator::new();
+        emulator.set_instructions_per_frame(3);
+        emulator.load_rom(&[0x12, 0x00][..]).expect("Failed to load");
+        emulator.delay_timer = 10;
+
+        emulator.step().expect("Failed to step");
+        emulator.step().expect("Failed to step");
+        assert_eq!(emulator.delay_timer, 9);
+        assert_eq!(emulator.frame_position(), 2);
+
+        emulator.run_frame().expect("Failed to run");
+        assert_eq!(emulator.delay_timer, 9);
+        assert_eq!(emulator.frame_position(), 0);
+
+        emulator.run_frame().expect("Failed to run");
+        assert_eq!(emulator.delay_timer, 8);
+        assert_eq!(emulator.instructions_executed(), 6);
+    }
+
+    #[test]
+    fn test_soft_reset_keeps_memory() {
+        let mut emulator = Emulator::new();
+        emulator.load_rom(&[0x60, 0x2A, 0xF0, 0x55][..]).expect("Failed to load");
+        emulator.index_register = 0x300;
+        emulator.execute_and_fetch().expect("Failed to execute");
+
+        emulator.soft_reset();
+
+        assert_eq!(emulator.program_counter, 0x200);
+        assert_eq!(emulator.registers[0x0], 0);
+        assert_eq!(emulator.index_register, 0);
+        assert_eq!(emulator.memory.peek(0x300), Ok(0x2A));
+    }
+
+    #[cfg(feature = "std")]
+    #[test]
+    fn test_reset_restores_rom() {
+        let mut emulator = Emulator::new();
+        // overwrites its first instruction
+        emulator.load_rom(&[0xA2, 0x00, 0xF0, 0x55][..]).expect("Failed to load");
+        emulator.execute_and_fetch().expect("Failed to execute");
+        assert_eq!(emulator.memory.peek(0x200), Ok(0));
+        assert_eq!(emulator.rom().collect::<Vec<_>>(), [0xA2, 0x00, 0xF0, 0x55]);
+
+        emulator.reset().expect("Failed to reset");
+
+        assert_eq!(emulator.memory.peek(0x200), Ok(0xA2));
+        assert_eq!(emulator.memory.peek(FONT_START), Ok(0xF0));
+        assert_eq!(emulator.program_counter, 0x200);
+        assert!(emulator.is_display_dirty());
+    }
+
+    #[cfg(feature = "std")]
+    #[test]
+    fn test_reset_restarts_random_numbers() {
+        let mut emulator = Emulator::with_seed(RandomMode::Vip, 7);
+        emulator.load_rom(&[0xCA, 0xFF][..]).expect("Failed to load");
+        let random_numbers = |emulator: &mut Emulator| {
+            (0..4)
+                .map(|_| {
+                    emulator
+                        .execute_instruction(Instruction::new([0xCA, 0xFF]))
+                        .expect("Failed to execute");
+                    emulator.registers[0xA]
+                })
+                .collect::<Vec<_>>()
+        };
+        let first = random_numbers(&mut emulator);
+
+        emulator.reset().expect("Failed to reset");
+
+        assert_eq!(random_numbers(&mut emulator), first);
+    }
+
+    #[test]
+    fn test_memory_protection() {
+        let mut emulator = Emulator::new();
+        emulator.index_register = 0xFFF;
+        let load = || Instruction::new([0xF1, 0x65]);
+        let store_at_font = || Instruction::new([0xF0, 0x55]);
+
+        emulator
+            .execute_instruction(load())
+            .expect_err("Read past the end of memory");
+
+        emulator.set_memory_protection(MemoryProtection::Wrap);
+        emulator.memory[0] = 0xAB;
+        emulator.execute_instruction(load()).expect("Failed to execute");
+        assert_eq!(emulator.registers[1], 0xAB);
+        emulator.index_register = 0xF0;
+        emulator
+            .execute_instruction(store_at_font())
+            .expect("Failed to execute");
+
+        emulator.set_memory_protection(MemoryProtection::Error);
+        assert_eq!(
+            emulator.execute_instruction(store_at_font()),
+            Err(Error::ReservedWrite { address: 0xF0 })
+        );
+        emulator.index_register = 0xFFF;
+        assert_eq!(
+            emulator.execute_instruction(load()),
+            Err(Error::AddressOutOfRange { address: 0x1000 })
+        );
+
+        emulator.set_memory_protection(MemoryProtection::Log);
+        emulator.execute_instruction(load()).expect("Failed to execute");
+    }
+
+    #[test]
+    fn test_clipped_sprite_rows_are_not_read() {
+        let mut emulator = Emulator::new();
+        emulator.set_memory_protection(MemoryProtection::Error);
+        emulator.set_quirks(Quirks {
+            wrap: false,
+            ..Quirks::default()
+        });
+        // the last byte of memory drawn on the last line, the rows below it are clipped
+        emulator.index_register = 0xFFF;
+        emulator.registers[1] = 31;
+
+        emulator
+            .execute_instruction(Instruction::new([0xD0, 0x15]))
+            .expect("Failed to draw");
+
+        emulator.registers[1] = 30;
+        assert_eq!(
+            emulator.execute_instruction(Instruction::new([0xD0, 0x15])),
+            Err(Error::AddressOutOfRange { address: 0x1000 })
+        );
+    }
+
+    #[test]
+    fn test_execute_through_custom_bus() {
+        /// Counts the writes and rejects the ones below the font.
+        struct ProtectedRam {
+            ram: Ram,
+            writes: usize,
+        }
+        impl Bus for ProtectedRam {
+            fn peek(&self, address: u16) -> Result<u8, Error> {
+                self.ram.peek(address)
+            }
+            fn write(&mut self, address: u16, value: u8) -> Result<(), Error> {
+                self.writes += 1;
+                if address < 0xF0 {
+                    return Err(Error::AddressOutOfRange { address });
+                }
+                self.ram.write(address, value)
+            }
+        }
+        let bus = ProtectedRam {
+            ram: Ram::new(),
+            writes: 0,
+        };
+        let mut emulator = Emulator::with_bus(RandomSource::new(RandomMode::XorShift, 0), bus);
+        assert_eq!(emulator.bus().writes, 80, "the font is written through the bus");
+        emulator.registers[0] = 123;
+
+        emulator.index_register = 0x300;
+        emulator
+            .execute_instruction(Instruction::new([0xF0, 0x33]))
+            .expect("Failed to execute");
+        emulator.index_register = 0x10;
+        let result = emulator.execute_instruction(Instruction::new([0xF0, 0x55]));
+
+        assert_eq!(emulator.bus().ram[0x300..0x303], [1, 2, 3]);
+        assert_eq!(result, Err(Error::AddressOutOfRange { address: 0x10 }));
+    }
+
+    #[test]
+    fn test_execute_call_with_stack_in_memory() {
+        let mut emulator = Emulator::new();
+        emulator.configure_stack(StackMode::Memory, 12).unwrap();
+        emulator.program_counter = 0x204;
+
+        emulator
+            .execute_instruction(Instruction::new([0x23, 0x00]))
+            .expect("Failed to execute");
+        emulator
+            .execute_instruction(Instruction::new([0x24, 0x00]))
+            .expect("Failed to execute");
+
+        assert_eq!(emulator.memory[0xECE..0xED0], [0x02, 0x04]);
+        assert_eq!(emulator.memory[0xECC..0xECE], [0x03, 0x00]);
+        assert_eq!(emulator.stack_frames().collect::<Vec<_>>(), [0x204, 0x300]);
+        assert!(emulator.stack.is_empty());
+
+        emulator
+            .execute_instruction(Instruction::new([0x00, 0xEE]))
+            .expect("Failed to execute");
+
+        assert_eq!(emulator.program_counter, 0x300);
+        assert!(emulator.configure_stack(StackMode::Memory, 25).is_err());
     }
 
     #[test]
@@ -671,7 +1486,29 @@ mod tests {
             .execute_instruction(Instruction::new([0x81, 0xF7]))
             .expect("Failed to execute");
 
-        assert_eq!(emulator.registers[0x1], 0xFA);
+        // the result is computed from VF before the flag overwrites it
+        assert_eq!(emulator.registers[0x1], 0x2);
+        assert_eq!(emulator.registers[0xF], 1);
+    }
+
+    #[test]
+    fn test_execute_subtract_equal_values_sets_no_borrow() {
+        let mut emulator = Emulator::new();
+        emulator.registers[0x1] = 0x7;
+        emulator.registers[0x2] = 0x7;
+
+        emulator
+            .execute_instruction(Instruction::new([0x81, 0x25]))
+            .expect("Failed to execute");
+        assert_eq!(emulator.registers[0x1], 0);
+        assert_eq!(emulator.registers[0xF], 1);
+
+        emulator.registers[0x1] = 0x7;
+        emulator
+            .execute_instruction(Instruction::new([0x81, 0x27]))
+            .expect("Failed to execute");
+        assert_eq!(emulator.registers[0x1], 0);
+        assert_eq!(emulator.registers[0xF], 1);
     }
 
     #[test]
@@ -712,6 +1549,99 @@ mod tests {
         assert_eq!(emulator.program_counter, 0xABE);
     }
 
+    #[test]
+    fn test_execute_with_original_chip8_quirks() {
+        let mut emulator = Emulator::new();
+        emulator.set_quirks(Platform::OriginalChip8.quirks());
+        emulator.registers[0x1] = 0x7;
+        emulator.registers[0x2] = 0x81;
+        emulator.registers[0xF] = 0x5;
+        emulator.index_register = 0x300;
+
+        emulator
+            .execute_instruction(Instruction::new([0x81, 0x26]))
+            .expect("Failed to execute");
+        assert_eq!(emulator.registers[0x1], 0x40);
+        assert_eq!(emulator.registers[0xF], 1);
+
+        emulator
+            .execute_instruction(Instruction::new([0x81, 0x21]))
+            .expect("Failed to execute");
+        assert_eq!(emulator.registers[0xF], 0);
+
+        emulator
+            .execute_instruction(Instruction::new([0xF2, 0x55]))
+            .expect("Failed to execute");
+        assert_eq!(emulator.index_register, 0x303);
+    }
+
+    #[test]
+    fn test_execute_jump_with_vx_quirk() {
+        let mut emulator = Emulator::new();
+        emulator.set_quirks(Platform::Superchip.quirks());
+        emulator.registers[0x0] = 0x2;
+        emulator.registers[0xA] = 0x4;
+
+        emulator
+            .execute_instruction(Instruction::new([0xBA, 0xBC]))
+            .expect("Failed to execute");
+
+        assert_eq!(emulator.program_counter, 0xAC0);
+    }
+
+    #[test]
+    fn test_execute_subtract_without_vf_order_quirk() {
+        let mut emulator = Emulator::new();
+        emulator.set_quirks(Quirks::none());
+        emulator.registers[0x1] = 0x7;
+        emulator.registers[0xF] = 0x9;
+
+        emulator
+            .execute_instruction(Instruction::new([0x8F, 0x15]))
+            .expect("Failed to execute");
+        assert_eq!(emulator.registers[0xF], 1);
+
+        emulator
+            .execute_instruction(Instruction::new([0x81, 0x17]))
+            .expect("Failed to execute");
+        assert_eq!(emulator.registers[0x1], 0);
+        assert_eq!(emulator.registers[0xF], 1);
+    }
+
+    #[test]
+    fn test_execute_draw_clips_without_wrap_quirk() {
+        let mut emulator = Emulator::new();
+        emulator.set_quirks(Quirks::none());
+        emulator.index_register = 0xF0;
+        emulator.registers[0x0] = 62;
+        emulator.registers[0x1] = 30;
+
+        emulator
+            .execute_instruction(Instruction::new([0xD0, 0x15]))
+            .expect("Failed to execute");
+
+        assert_eq!(emulator.display_data.lit_pixels().count(), 3);
+        assert!(emulator.display_data.get(63, 30));
+        assert!(!emulator.display_data.get(0, 30));
+        assert!(!emulator.display_data.get(62, 0));
+    }
+
+    #[test]
+    fn test_vblank_quirk_ends_frame_after_draw() {
+        let rom: [u8; 8] = [0xD0, 0x01, 0x70, 0x01, 0x70, 0x01, 0x12, 0x00];
+        let mut emulator = Emulator::new();
+        emulator.load_rom(&rom[..]).expect("Failed to load");
+        emulator.set_quirks(Platform::OriginalChip8.quirks());
+        emulator.set_instructions_per_frame(15);
+
+        emulator.execute_and_fetch().expect("Failed to execute");
+        assert_eq!(emulator.program_counter, 0x202);
+
+        emulator.set_quirks(Quirks::default());
+        emulator.execute_and_fetch().expect("Failed to execute");
+        assert_eq!(emulator.registers[0x0], 4 * 2);
+    }
+
     #[test]
     fn test_execute_random_number() {
         let mut emulator = Emulator::new();
@@ -723,6 +1653,45 @@ mod tests {
         assert!(emulator.registers[0xA] <= 0xBC)
     }
 
+    #[test]
+    fn test_execute_random_number_is_seeded() {
+        let mut first = Emulator::with_seed(RandomMode::XorShift, 42);
+        let mut second = Emulator::new();
+        second.set_seed(42);
+
+        for _ in 0..10 {
+            first
+                .execute_instruction(Instruction::new([0xCA, 0xFF]))
+                .expect("Failed to execute");
+            second
+                .execute_instruction(Instruction::new([0xCA, 0xFF]))
+                .expect("Failed to execute");
+            assert_eq!(first.registers[0xA], second.registers[0xA]);
+        }
+        assert_eq!(second.rng().seed(), 42);
+    }
+
+    #[test]
+    fn test_execute_random_number_from_injected_rng() {
+        struct Constant(u8);
+        impl Rng for Constant {
+            fn next_byte(&mut self) -> u8 {
+                self.0
+            }
+        }
+        let mut emulator = Emulator::with_rng(Constant(0xFF));
+
+        emulator
+            .execute_instruction(Instruction::new([0xCA, 0xFF]))
+            .expect("Failed to execute");
+        emulator
+            .execute_instruction(Instruction::new([0xCB, 0x0F]))
+            .expect("Failed to execute");
+
+        assert_eq!(emulator.registers[0xA], 0xFF);
+        assert_eq!(emulator.registers[0xB], 0x0F);
+    }
+
     #[test]
     fn test_execute_skip_equal_vx_data() {
         let mut emulator = Emulator::new();
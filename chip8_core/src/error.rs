@@ -0,0 +1,216 @@
+use crate::stack::StackError;
+use core::fmt::{Debug, Display, Formatter, Write};
+
+/// The longest text an [`Error`] keeps, longer texts are cut off.
+pub const ERROR_TEXT_CAPACITY: usize = 40;
+
+/// A short text kept inside an [`Error`], such as a rejected value, so errors stay `Copy` and
+/// do not allocate.
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub struct ErrorText {
+    bytes: [u8; ERROR_TEXT_CAPACITY],
+    len: usize,
+    truncated: bool,
+}
+
+impl ErrorText {
+    /// Copies the text, cut off at a character boundary after [`ERROR_TEXT_CAPACITY`] bytes.
+    pub fn new(text: &str) -> Self {
+        let mut error_text = ErrorText::default();
+        let _ = error_text.write_str(text);
+        error_text
+    }
+
+    /// Returns the kept text.
+    pub fn as_str(&self) -> &str {
+        core::str::from_utf8(&self.bytes[..self.len]).unwrap_or_default()
+    }
+
+    /// Returns true if the text was cut off.
+    pub fn is_truncated(&self) -> bool {
+        self.truncated
+    }
+}
+
+impl Default for ErrorText {
+    fn default() -> Self {
+        ErrorText {
+            bytes: [0; ERROR_TEXT_CAPACITY],
+            len: 0,
+            truncated: false,
+        }
+    }
+}
+
+impl Write for ErrorText {
+    fn write_str(&mut self, text: &str) -> core::fmt::Result {
+        for character in text.chars() {
+            let len = self.len + character.len_utf8();
+            if self.truncated || len > ERROR_TEXT_CAPACITY {
+                self.truncated = true;
+                break;
+            }
+            character.encode_utf8(&mut self.bytes[self.len..len]);
+            self.len = len;
+        }
+        Ok(())
+    }
+}
+
+impl Display for ErrorText {
+    fn fmt(&self, f: &mut Formatter<'_>) -> core::fmt::Result {
+        f.write_str(self.as_str())?;
+        if self.truncated {
+            f.write_str("...")?;
+        }
+        Ok(())
+    }
+}
+
+impl Debug for ErrorText {
+    fn fmt(&self, f: &mut Formatter<'_>) -> core::fmt::Result {
+        write!(f, "\"{self}\"")
+    }
+}
+
+/// The errors of the emulator core. They do not allocate, so the core runs without a heap.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Error {
+    /// The program counter left the memory.
+    ProgramCounterOutOfRange { address: u16 },
+    /// The address is not mapped by the memory bus.
+    AddressOutOfRange { address: u16 },
+    /// The CPU wrote below 0x200, into the area reserved for the interpreter and the font.
+    ReservedWrite { address: u16 },
+    /// A call went deeper than the stack's capacity, or a return found an empty stack.
+    Stack { error: StackError, address: u16 },
+    /// The stack mode does not support the capacity.
+    InvalidStackCapacity {
+        capacity: usize,
+        max_capacity: usize,
+    },
+    /// The ROM does not fit in the memory, which holds the given amount of bytes for it.
+    RomTooLarge { available: usize },
+    /// The ROM archive could not be extracted, holds the error of the archive library if it
+    /// failed.
+    Archive {
+        message: &'static str,
+        source: Option<ErrorText>,
+    },
+    /// A value could not be parsed, holds the value and a description of the accepted values.
+    Parse {
+        value: ErrorText,
+        expected: &'static str,
+    },
+    /// Reading the ROM failed.
+    #[cfg(feature = "std")]
+    Io {
+        kind: std::io::ErrorKind,
+        message: ErrorText,
+    },
+}
+
+impl Error {
+    /// Returns the error for an archive which could not be extracted.
+    pub fn archive(message: &'static str) -> Self {
+        Error::Archive {
+            message,
+            source: None,
+        }
+    }
+
+    /// Returns the error for a value which could not be parsed.
+    pub fn parse(value: &str, expected: &'static str) -> Self {
+        Error::Parse {
+            value: ErrorText::new(value),
+            expected,
+        }
+    }
+}
+
+impl Display for Error {
+    fn fmt(&self, f: &mut Formatter<'_>) -> core::fmt::Result {
+        match self {
+            Error::ProgramCounterOutOfRange { address } => {
+                write!(f, "program counter {address:04x} is out of range")
+            }
+            Error::AddressOutOfRange { address } => {
+                write!(f, "memory address {address:04x} is out of range")
+            }
+            Error::ReservedWrite { address } => {
+                write!(f, "write into the reserved memory at {address:04x}")
+            }
+            Error::Stack { error, address } => write!(f, "{error} at {address:04x}"),
+            Error::InvalidStackCapacity {
+                capacity,
+                max_capacity,
+            } => write!(
+                f,
+                "invalid stack depth {capacity}, expected 1 to {max_capacity}"
+            ),
+            Error::RomTooLarge { available } => {
+                write!(
+                    f,
+                    "the ROM is larger than the {available} bytes of memory available"
+                )
+            }
+            Error::Archive {
+                message,
+                source: None,
+            } => f.write_str(message),
+            Error::Archive {
+                message,
+                source: Some(source),
+            } => write!(f, "{message}: {source}"),
+            Error::Parse { value, expected } => {
+                write!(f, "invalid value {value:?}, expected {expected}")
+            }
+            #[cfg(feature = "std")]
+            Error::Io { message, .. } => write!(f, "failed to read the ROM: {message}"),
+        }
+    }
+}
+
+impl core::error::Error for Error {}
+
+#[cfg(feature = "std")]
+impl From<std::io::Error> for Error {
+    fn from(error: std::io::Error) -> Self {
+        let mut message = ErrorText::default();
+        let _ = write!(message, "{error}");
+        Error::Io {
+            kind: error.kind(),
+            message,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_error_text_is_cut_at_a_character() {
+        let text = ErrorText::new(&"é".repeat(ERROR_TEXT_CAPACITY));
+
+        assert_eq!(text.as_str(), "é".repeat(ERROR_TEXT_CAPACITY / 2));
+        assert!(text.is_truncated());
+        assert!(!ErrorText::new("amber").is_truncated());
+    }
+
+    #[cfg(feature = "std")]
+    #[test]
+    fn test_error_display_keeps_the_value() {
+        let error = Error::parse("purple", "classic or amber");
+
+        assert_eq!(
+            error.to_string(),
+            "invalid value \"purple\", expected classic or amber"
+        );
+        let error = Error::from(std::io::Error::new(
+            std::io::ErrorKind::NotFound,
+            "no such ROM",
+        ));
+        assert_eq!(error.to_string(), "failed to read the ROM: no such ROM");
+    }
+}
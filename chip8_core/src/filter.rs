@@ -0,0 +1,253 @@
+use crate::display::{Framebuffer, DISPLAY_HEIGHT, DISPLAY_WIDTH};
+use crate::error::Error;
+use core::str::FromStr;
+
+/// The intensity of a fully lit pixel.
+pub const MAX_INTENSITY: u8 = 255;
+
+/// The amount of frames a pixel fades over by default in phosphor mode.
+pub const DEFAULT_PHOSPHOR_FRAMES: u8 = 4;
+
+/// Selects how the display filter reduces the flicker.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FilterMode {
+    /// Pixels are shown exactly as the emulator draws them.
+    Off,
+    /// A pixel is lit if it was lit in the current or the previous frame.
+    Or,
+    /// Pixels which turn off fade out over the given amount of frames, like the
+    /// phosphor of a CRT monitor.
+    Phosphor { frames: u8 },
+}
+
+impl FromStr for FilterMode {
+    type Err = Error;
+
+    /// Parses `off`, `or`, `phosphor` or `phosphor:N` where N is the amount of frames.
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (name, frames) = match s.split_once(':') {
+            Some((name, frames)) => (name, Some(frames)),
+            None => (s, None),
+        };
+        match (name, frames) {
+            ("off", None) => Ok(FilterMode::Off),
+            ("or", None) => Ok(FilterMode::Or),
+            ("phosphor", None) => Ok(FilterMode::Phosphor {
+                frames: DEFAULT_PHOSPHOR_FRAMES,
+            }),
+            ("phosphor", Some(frames)) => match frames.parse::<u8>() {
+                Ok(frames) if frames > 0 => Ok(FilterMode::Phosphor { frames }),
+                _ => Err(Error::parse(s, "phosphor:N with N from 1 to 255")),
+            },
+            _ => Err(Error::parse(s, "off, or, phosphor or phosphor:N")),
+        }
+    }
+}
+
+/// Post-processing stage over the emulator's display buffer which reduces the flicker
+/// caused by games erasing and redrawing their sprites with XOR.
+///
+/// The filter turns each frame into pixel intensities, from 0 for an unlit pixel up to
+/// [`MAX_INTENSITY`] for a lit one, which the frontends blend between their colors. It
+/// advances once per emulated frame, so it fades at the same rate whether the frontend is
+/// fast-forwarding or rendering at a different rate.
+pub struct DisplayFilter {
+    mode: FilterMode,
+    /// The display data of the previous frame.
+    previous: Framebuffer,
+    /// The intensities of the last filtered frame.
+    intensities: [u8; DISPLAY_WIDTH * DISPLAY_HEIGHT],
+    /// True if the next frame changes even when the display data does not.
+    animating: bool,
+}
+
+impl DisplayFilter {
+    /// Creates a new display filter.
+    pub fn new(mode: FilterMode) -> Self {
+        DisplayFilter {
+            mode,
+            previous: Framebuffer::new(),
+            intensities: [0; DISPLAY_WIDTH * DISPLAY_HEIGHT],
+            animating: false,
+        }
+    }
+
+    /// Returns the filter's mode.
+    pub fn mode(&self) -> FilterMode {
+        self.mode
+    }
+
+    /// Returns true while the filter's output keeps changing on unchanged display data,
+    /// for example while pixels fade out.
+    pub fn is_animating(&self) -> bool {
+        self.animating
+    }
+
+    /// Filters the display data of an emulated frame.
+    ///
+    /// Returns true if pixels changed which the display data did not change, such as fading
+    /// ones, so frontends redraw the whole display.
+    pub fn advance(&mut self, display_data: &Framebuffer) -> bool {
+        let animated = self.animating;
+        self.animating = false;
+        self.filter(display_data, true);
+        self.previous = *display_data;
+        animated
+    }
+
+    /// Shows the display data of a frame which has not ended yet, such as one stepped through
+    /// instruction by instruction: lit pixels light up at once and the others keep their
+    /// intensity until the frame ends.
+    pub fn preview(&mut self, display_data: &Framebuffer) {
+        self.filter(display_data, false);
+    }
+
+    fn filter(&mut self, display_data: &Framebuffer, frame_ended: bool) {
+        let previous_pixels = self.previous.pixels();
+        for (index, (pixel, previous)) in display_data.pixels().zip(previous_pixels).enumerate() {
+            self.intensities[index] = match self.mode {
+                FilterMode::Off => Self::intensity(pixel),
+                FilterMode::Or => {
+                    self.animating |= frame_ended && previous && !pixel;
+                    Self::intensity(pixel || previous)
+                }
+                FilterMode::Phosphor { .. } if pixel => MAX_INTENSITY,
+                FilterMode::Phosphor { .. } if !frame_ended => self.intensities[index],
+                FilterMode::Phosphor { frames } => {
+                    let step = MAX_INTENSITY.div_ceil(frames.max(1));
+                    let intensity = self.intensities[index].saturating_sub(step);
+                    self.animating |= intensity > 0;
+                    intensity
+                }
+            };
+        }
+    }
+
+    /// Shows the display data without the afterglow of earlier frames, for example after the
+    /// emulator was reset.
+    pub fn reset(&mut self, display_data: &Framebuffer) {
+        for (index, pixel) in display_data.pixels().enumerate() {
+            self.intensities[index] = Self::intensity(pixel);
+        }
+        self.previous = *display_data;
+        self.animating = false;
+    }
+
+    /// Returns the intensity of each pixel after the last filtered frame.
+    pub fn intensities(&self) -> &[u8; DISPLAY_WIDTH * DISPLAY_HEIGHT] {
+        &self.intensities
+    }
+
+    fn intensity(pixel: bool) -> u8 {
+        if pixel {
+            MAX_INTENSITY
+        } else {
+            0
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn frame_with_pixel(lit: bool) -> Framebuffer {
+        let mut frame = Framebuffer::new();
+        frame.set(0, 0, lit);
+        frame
+    }
+
+    /// Filters a frame and returns the intensity of the pixel.
+    fn advance(filter: &mut DisplayFilter, lit: bool) -> u8 {
+        filter.advance(&frame_with_pixel(lit));
+        filter.intensities()[0]
+    }
+
+    #[test]
+    fn test_filter_off() {
+        let mut filter = DisplayFilter::new(FilterMode::Off);
+
+        assert_eq!(advance(&mut filter, true), MAX_INTENSITY);
+        assert_eq!(advance(&mut filter, false), 0);
+    }
+
+    #[test]
+    fn test_filter_or_keeps_previous_frame() {
+        let mut filter = DisplayFilter::new(FilterMode::Or);
+
+        assert_eq!(advance(&mut filter, true), MAX_INTENSITY);
+        assert_eq!(advance(&mut filter, false), MAX_INTENSITY);
+        assert!(filter.is_animating());
+        assert_eq!(advance(&mut filter, false), 0);
+        assert!(!filter.is_animating());
+    }
+
+    #[test]
+    fn test_filter_phosphor_decays() {
+        let mut filter = DisplayFilter::new(FilterMode::Phosphor { frames: 3 });
+
+        assert_eq!(advance(&mut filter, true), 255);
+        assert_eq!(advance(&mut filter, false), 170);
+        assert_eq!(advance(&mut filter, false), 85);
+        assert!(filter.is_animating());
+        assert_eq!(advance(&mut filter, false), 0);
+        assert!(!filter.is_animating());
+        assert_eq!(advance(&mut filter, true), 255);
+    }
+
+    #[test]
+    fn test_filter_advance_reports_fading_pixels() {
+        let mut filter = DisplayFilter::new(FilterMode::Phosphor { frames: 2 });
+
+        assert!(!filter.advance(&frame_with_pixel(true)));
+        assert!(!filter.advance(&frame_with_pixel(false)));
+        assert!(filter.advance(&frame_with_pixel(false)));
+        assert!(!filter.advance(&frame_with_pixel(false)));
+    }
+
+    #[test]
+    fn test_filter_preview_keeps_the_intensities() {
+        let mut filter = DisplayFilter::new(FilterMode::Phosphor { frames: 3 });
+        advance(&mut filter, true);
+        advance(&mut filter, false);
+
+        filter.preview(&frame_with_pixel(false));
+        assert_eq!(filter.intensities()[0], 170);
+        filter.preview(&frame_with_pixel(true));
+        assert_eq!(filter.intensities()[0], 255);
+
+        let mut filter = DisplayFilter::new(FilterMode::Off);
+        advance(&mut filter, true);
+        filter.preview(&frame_with_pixel(false));
+        assert_eq!(filter.intensities()[0], 0);
+    }
+
+    #[test]
+    fn test_filter_reset_drops_the_afterglow() {
+        let mut filter = DisplayFilter::new(FilterMode::Or);
+        advance(&mut filter, true);
+
+        filter.reset(&frame_with_pixel(false));
+
+        assert_eq!(filter.intensities()[0], 0);
+        assert!(!filter.is_animating());
+    }
+
+    #[test]
+    fn test_filter_mode_from_str() {
+        assert_eq!("off".parse::<FilterMode>().unwrap(), FilterMode::Off);
+        assert_eq!("or".parse::<FilterMode>().unwrap(), FilterMode::Or);
+        assert_eq!(
+            "phosphor".parse::<FilterMode>().unwrap(),
+            FilterMode::Phosphor {
+                frames: DEFAULT_PHOSPHOR_FRAMES
+            }
+        );
+        assert_eq!(
+            "phosphor:8".parse::<FilterMode>().unwrap(),
+            FilterMode::Phosphor { frames: 8 }
+        );
+        assert!("phosphor:0".parse::<FilterMode>().is_err());
+        assert!("blur".parse::<FilterMode>().is_err());
+    }
+}
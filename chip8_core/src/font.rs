@@ -0,0 +1,97 @@
+/// The width in pixels of the font's glyphs, drawn in the high nibble of each row.
+pub const GLYPH_WIDTH: usize = 4;
+/// The height in pixels of the font's glyphs, one byte per row.
+pub const GLYPH_HEIGHT: usize = 5;
+
+/// The hexadecimal digit sprites ROMs draw with `FX29`, loaded into the emulated memory.
+pub const FONT_SPRITES: [u8; 80] = [
+    0xF0, 0x90, 0x90, 0x90, 0xF0, // 0
+    0x20, 0x60, 0x20, 0x20, 0x70, // 1
+    0xF0, 0x10, 0xF0, 0x80, 0xF0, // 2
+    0xF0, 0x10, 0xF0, 0x10, 0xF0, // 3
+    0x90, 0x90, 0xF0, 0x10, 0x10, // 4
+    0xF0, 0x80, 0xF0, 0x10, 0xF0, // 5
+    0xF0, 0x80, 0xF0, 0x90, 0xF0, // 6
+    0xF0, 0x10, 0x20, 0x40, 0x40, // 7
+    0xF0, 0x90, 0xF0, 0x90, 0xF0, // 8
+    0xF0, 0x90, 0xF0, 0x10, 0xF0, // 9
+    0xF0, 0x90, 0xF0, 0x90, 0x90, // A
+    0xE0, 0x90, 0xE0, 0x90, 0xE0, // B
+    0xF0, 0x80, 0x80, 0x80, 0xF0, // C
+    0xE0, 0x90, 0x90, 0x90, 0xE0, // D
+    0xF0, 0x80, 0xF0, 0x80, 0xF0, // E
+    0xF0, 0x80, 0xF0, 0x80, 0x80, // F
+];
+
+/// The letters after F, in the style of the digit sprites.
+const LETTER_SPRITES: [u8; 100] = [
+    0xF0, 0x80, 0xB0, 0x90, 0xF0, // G
+    0x90, 0x90, 0xF0, 0x90, 0x90, // H
+    0xE0, 0x40, 0x40, 0x40, 0xE0, // I
+    0x10, 0x10, 0x10, 0x90, 0x60, // J
+    0x90, 0xA0, 0xC0, 0xA0, 0x90, // K
+    0x80, 0x80, 0x80, 0x80, 0xF0, // L
+    0x90, 0xF0, 0xF0, 0x90, 0x90, // M
+    0x90, 0xD0, 0xB0, 0x90, 0x90, // N
+    0x60, 0x90, 0x90, 0x90, 0x60, // O
+    0xE0, 0x90, 0xE0, 0x80, 0x80, // P
+    0x60, 0x90, 0x90, 0xB0, 0x70, // Q
+    0xE0, 0x90, 0xE0, 0xA0, 0x90, // R
+    0x70, 0x80, 0x60, 0x10, 0xE0, // S
+    0xE0, 0x40, 0x40, 0x40, 0x40, // T
+    0x90, 0x90, 0x90, 0x90, 0x60, // U
+    0xA0, 0xA0, 0xA0, 0xA0, 0x40, // V
+    0x90, 0x90, 0xF0, 0xF0, 0x90, // W
+    0x90, 0x90, 0x60, 0x90, 0x90, // X
+    0xA0, 0xA0, 0x40, 0x40, 0x40, // Y
+    0xF0, 0x10, 0x60, 0x80, 0xF0, // Z
+];
+
+/// The glyph of unknown characters.
+const UNKNOWN_GLYPH: [u8; GLYPH_HEIGHT] = [0xE0, 0x10, 0x60, 0x00, 0x40];
+
+/// Returns the rows of the character's glyph, for frontends drawing text without a font of
+/// their own. Letters are drawn in upper case, unknown characters as a question mark.
+pub fn glyph(character: char) -> [u8; GLYPH_HEIGHT] {
+    let character = character.to_ascii_uppercase();
+    let sprite = |sprites: &[u8], index: usize| {
+        let mut rows = [0; GLYPH_HEIGHT];
+        rows.copy_from_slice(&sprites[index * GLYPH_HEIGHT..(index + 1) * GLYPH_HEIGHT]);
+        rows
+    };
+    match character {
+        '0'..='9' => sprite(&FONT_SPRITES, character as usize - '0' as usize),
+        'A'..='F' => sprite(&FONT_SPRITES, character as usize - 'A' as usize + 10),
+        'G'..='Z' => sprite(&LETTER_SPRITES, character as usize - 'G' as usize),
+        ' ' => [0x00, 0x00, 0x00, 0x00, 0x00],
+        '.' => [0x00, 0x00, 0x00, 0x00, 0x40],
+        ',' => [0x00, 0x00, 0x00, 0x40, 0x80],
+        ':' => [0x00, 0x40, 0x00, 0x40, 0x00],
+        '!' => [0x40, 0x40, 0x40, 0x00, 0x40],
+        '-' => [0x00, 0x00, 0xE0, 0x00, 0x00],
+        '+' => [0x00, 0x40, 0xE0, 0x40, 0x00],
+        '=' => [0x00, 0xF0, 0x00, 0xF0, 0x00],
+        '_' => [0x00, 0x00, 0x00, 0x00, 0xF0],
+        '/' => [0x10, 0x20, 0x20, 0x40, 0x80],
+        '%' => [0x90, 0x10, 0x20, 0x40, 0x90],
+        '(' => [0x20, 0x40, 0x40, 0x40, 0x20],
+        ')' => [0x40, 0x20, 0x20, 0x20, 0x40],
+        '[' => [0x60, 0x40, 0x40, 0x40, 0x60],
+        ']' => [0x60, 0x20, 0x20, 0x20, 0x60],
+        _ => UNKNOWN_GLYPH,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_glyph() {
+        assert_eq!(glyph('0'), [0xF0, 0x90, 0x90, 0x90, 0xF0]);
+        assert_eq!(glyph('f'), glyph('F'));
+        assert_eq!(glyph('F'), [0xF0, 0x80, 0xF0, 0x80, 0x80]);
+        assert_eq!(glyph('Z'), [0xF0, 0x10, 0x60, 0x80, 0xF0]);
+        assert_eq!(glyph('~'), glyph('?'));
+    }
+}
@@ -1,4 +1,3 @@
-use alloc::format;
 use core::fmt;
 use core::fmt::{Display, Formatter, LowerHex};
 /*
@@ -89,6 +88,146 @@ pub enum ProcessorInstruction {
     UnknownInstruction,
 }
 
+impl ProcessorInstruction {
+    /// The name of every instruction type, as returned by [`ProcessorInstruction::name`].
+    pub const NAMES: [&'static str; 35] = [
+        "ClearScreen",
+        "Jump",
+        "SetRegister",
+        "AddValueToRegister",
+        "SetIndexRegister",
+        "Draw",
+        "Call",
+        "Return",
+        "Set",
+        "BinaryOr",
+        "BinaryAnd",
+        "BinaryXor",
+        "Add",
+        "SubtractVX",
+        "SubtractVY",
+        "ShiftRight",
+        "ShiftLeft",
+        "JumpWithOffset",
+        "GenerateRandomNumber",
+        "SkipEqualVXData",
+        "SkipNotEqualVXData",
+        "SkipEqualVXVY",
+        "SkipNotEqualVXVY",
+        "SetVXToDelayTimer",
+        "SetDelayTimer",
+        "SetSoundTimer",
+        "AddToIndex",
+        "FontCharacter",
+        "BinaryCodedDecimalConversion",
+        "StoreMemory",
+        "LoadMemory",
+        "GetKeyBlocking",
+        "SkipIfKeyIsPressed",
+        "SkipIfKeyIsNotPressed",
+        "UnknownInstruction",
+    ];
+
+    /// Returns the name of the instruction type, the name of its variant.
+    pub fn name(&self) -> &'static str {
+        match self {
+            ProcessorInstruction::ClearScreen => "ClearScreen",
+            ProcessorInstruction::Jump { .. } => "Jump",
+            ProcessorInstruction::SetRegister { .. } => "SetRegister",
+            ProcessorInstruction::AddValueToRegister { .. } => "AddValueToRegister",
+            ProcessorInstruction::SetIndexRegister { .. } => "SetIndexRegister",
+            ProcessorInstruction::Draw { .. } => "Draw",
+            ProcessorInstruction::Call { .. } => "Call",
+            ProcessorInstruction::Return => "Return",
+            ProcessorInstruction::Set { .. } => "Set",
+            ProcessorInstruction::BinaryOr { .. } => "BinaryOr",
+            ProcessorInstruction::BinaryAnd { .. } => "BinaryAnd",
+            ProcessorInstruction::BinaryXor { .. } => "BinaryXor",
+            ProcessorInstruction::Add { .. } => "Add",
+            ProcessorInstruction::SubtractVX { .. } => "SubtractVX",
+            ProcessorInstruction::SubtractVY { .. } => "SubtractVY",
+            ProcessorInstruction::ShiftRight { .. } => "ShiftRight",
+            ProcessorInstruction::ShiftLeft { .. } => "ShiftLeft",
+            ProcessorInstruction::JumpWithOffset { .. } => "JumpWithOffset",
+            ProcessorInstruction::GenerateRandomNumber { .. } => "GenerateRandomNumber",
+            ProcessorInstruction::SkipEqualVXData { .. } => "SkipEqualVXData",
+            ProcessorInstruction::SkipNotEqualVXData { .. } => "SkipNotEqualVXData",
+            ProcessorInstruction::SkipEqualVXVY { .. } => "SkipEqualVXVY",
+            ProcessorInstruction::SkipNotEqualVXVY { .. } => "SkipNotEqualVXVY",
+            ProcessorInstruction::SetVXToDelayTimer { .. } => "SetVXToDelayTimer",
+            ProcessorInstruction::SetDelayTimer { .. } => "SetDelayTimer",
+            ProcessorInstruction::SetSoundTimer { .. } => "SetSoundTimer",
+            ProcessorInstruction::AddToIndex { .. } => "AddToIndex",
+            ProcessorInstruction::FontCharacter { .. } => "FontCharacter",
+            ProcessorInstruction::BinaryCodedDecimalConversion { .. } => {
+                "BinaryCodedDecimalConversion"
+            }
+            ProcessorInstruction::StoreMemory { .. } => "StoreMemory",
+            ProcessorInstruction::LoadMemory { .. } => "LoadMemory",
+            ProcessorInstruction::GetKeyBlocking { .. } => "GetKeyBlocking",
+            ProcessorInstruction::SkipIfKeyIsPressed { .. } => "SkipIfKeyIsPressed",
+            ProcessorInstruction::SkipIfKeyIsNotPressed { .. } => "SkipIfKeyIsNotPressed",
+            ProcessorInstruction::UnknownInstruction => "UnknownInstruction",
+        }
+    }
+}
+
+/// Formats the instruction as assembly, with the mnemonics of Cowgod's Chip-8 technical
+/// reference which most disassemblers and emulators print.
+impl Display for ProcessorInstruction {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        match *self {
+            ProcessorInstruction::ClearScreen => write!(f, "CLS"),
+            ProcessorInstruction::Jump { address } => write!(f, "JP {address:03X}"),
+            ProcessorInstruction::SetRegister { register, data } => {
+                write!(f, "LD V{register:X}, {data:02X}")
+            }
+            ProcessorInstruction::AddValueToRegister { register, value } => {
+                write!(f, "ADD V{register:X}, {value:02X}")
+            }
+            ProcessorInstruction::SetIndexRegister { data } => write!(f, "LD I, {data:03X}"),
+            ProcessorInstruction::Draw { vx, vy, rows } => {
+                write!(f, "DRW V{vx:X}, V{vy:X}, {rows:X}")
+            }
+            ProcessorInstruction::Call { address } => write!(f, "CALL {address:03X}"),
+            ProcessorInstruction::Return => write!(f, "RET"),
+            ProcessorInstruction::Set { vx, vy } => write!(f, "LD V{vx:X}, V{vy:X}"),
+            ProcessorInstruction::BinaryOr { vx, vy } => write!(f, "OR V{vx:X}, V{vy:X}"),
+            ProcessorInstruction::BinaryAnd { vx, vy } => write!(f, "AND V{vx:X}, V{vy:X}"),
+            ProcessorInstruction::BinaryXor { vx, vy } => write!(f, "XOR V{vx:X}, V{vy:X}"),
+            ProcessorInstruction::Add { vx, vy } => write!(f, "ADD V{vx:X}, V{vy:X}"),
+            ProcessorInstruction::SubtractVX { vx, vy } => write!(f, "SUB V{vx:X}, V{vy:X}"),
+            ProcessorInstruction::SubtractVY { vx, vy } => write!(f, "SUBN V{vx:X}, V{vy:X}"),
+            ProcessorInstruction::ShiftRight { vx, vy } => write!(f, "SHR V{vx:X}, V{vy:X}"),
+            ProcessorInstruction::ShiftLeft { vx, vy } => write!(f, "SHL V{vx:X}, V{vy:X}"),
+            ProcessorInstruction::JumpWithOffset { address } => write!(f, "JP V0, {address:03X}"),
+            ProcessorInstruction::GenerateRandomNumber { vx, mask } => {
+                write!(f, "RND V{vx:X}, {mask:02X}")
+            }
+            ProcessorInstruction::SkipEqualVXData { vx, data } => {
+                write!(f, "SE V{vx:X}, {data:02X}")
+            }
+            ProcessorInstruction::SkipNotEqualVXData { vx, data } => {
+                write!(f, "SNE V{vx:X}, {data:02X}")
+            }
+            ProcessorInstruction::SkipEqualVXVY { vx, vy } => write!(f, "SE V{vx:X}, V{vy:X}"),
+            ProcessorInstruction::SkipNotEqualVXVY { vx, vy } => write!(f, "SNE V{vx:X}, V{vy:X}"),
+            ProcessorInstruction::SetVXToDelayTimer { vx } => write!(f, "LD V{vx:X}, DT"),
+            ProcessorInstruction::SetDelayTimer { vx } => write!(f, "LD DT, V{vx:X}"),
+            ProcessorInstruction::SetSoundTimer { vx } => write!(f, "LD ST, V{vx:X}"),
+            ProcessorInstruction::AddToIndex { vx } => write!(f, "ADD I, V{vx:X}"),
+            ProcessorInstruction::FontCharacter { vx } => write!(f, "LD F, V{vx:X}"),
+            ProcessorInstruction::BinaryCodedDecimalConversion { vx } => write!(f, "LD B, V{vx:X}"),
+            ProcessorInstruction::StoreMemory { vx } => write!(f, "LD [I], V{vx:X}"),
+            ProcessorInstruction::LoadMemory { vx } => write!(f, "LD V{vx:X}, [I]"),
+            ProcessorInstruction::GetKeyBlocking { vx } => write!(f, "LD V{vx:X}, K"),
+            ProcessorInstruction::SkipIfKeyIsPressed { vx } => write!(f, "SKP V{vx:X}"),
+            ProcessorInstruction::SkipIfKeyIsNotPressed { vx } => write!(f, "SKNP V{vx:X}"),
+            ProcessorInstruction::UnknownInstruction => write!(f, "???"),
+        }
+    }
+}
+
 #[derive(Debug)]
 pub struct Instruction {
     data: u16,
@@ -293,11 +432,12 @@ impl Instruction {
 
 impl Display for Instruction {
     fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
-        f.write_str(&format!(
+        write!(
+            f,
             "<Instruction: [{:02x}{:02x}]>",
             ((self.data & 0xFF00) >> 8u8) as u8,
             (self.data & 0x00FF) as u8
-        ))
+        )
     }
 }
 
@@ -655,4 +795,42 @@ mod tests {
             ProcessorInstruction::GetKeyBlocking { vx: 0xE }
         )
     }
+
+    #[cfg(feature = "std")]
+    #[test]
+    fn test_processor_instruction_mnemonic() {
+        let mnemonic = |data: [u8; 2]| Instruction::new(data).processor_instruction().to_string();
+        assert_eq!(mnemonic([0x00, 0xE0]), "CLS");
+        assert_eq!(mnemonic([0x6A, 0x02]), "LD VA, 02");
+        assert_eq!(mnemonic([0xA2, 0x2A]), "LD I, 22A");
+        assert_eq!(mnemonic([0xD0, 0x1F]), "DRW V0, V1, F");
+        assert_eq!(mnemonic([0x53, 0x40]), "SE V3, V4");
+        assert_eq!(mnemonic([0xF3, 0x65]), "LD V3, [I]");
+    }
+
+    #[test]
+    fn test_processor_instruction_name() {
+        let instruction = Instruction::new([0x53, 0x40]).processor_instruction();
+        assert_eq!(instruction.name(), "SkipEqualVXVY");
+        assert!(ProcessorInstruction::NAMES.contains(&instruction.name()));
+    }
+
+    #[test]
+    fn test_processor_instruction_names_match_the_variants() {
+        let mut decoded = [false; ProcessorInstruction::NAMES.len()];
+        for opcode in 0..=u16::MAX {
+            let name = Instruction::new(opcode.to_be_bytes())
+                .processor_instruction()
+                .name();
+            let index = ProcessorInstruction::NAMES
+                .iter()
+                .position(|known| *known == name);
+            assert!(index.is_some(), "{name} is missing from NAMES");
+            decoded[index.unwrap()] = true;
+        }
+        // a name no opcode decodes to is misspelled or listed twice
+        for (name, decoded) in ProcessorInstruction::NAMES.iter().zip(decoded) {
+            assert!(decoded, "no opcode decodes to {name}");
+        }
+    }
 }
@@ -1,9 +1,38 @@
 #![cfg_attr(not(feature = "std"), no_std)]
 
-extern crate alloc;
-
+pub mod audio;
+pub mod bus;
+#[cfg(feature = "cartridge")]
+pub mod cartridge;
+pub mod command;
+#[cfg(feature = "database")]
+pub mod database;
 pub mod display;
 pub mod emulator;
+pub mod error;
+pub mod filter;
+pub mod font;
 pub mod instruction;
+#[cfg(feature = "std")]
+pub mod movie;
+#[cfg(feature = "cartridge")]
+pub mod octo;
+#[cfg(feature = "cli")]
+pub mod options;
+#[cfg(feature = "std")]
+pub mod overlay;
+pub mod palette;
+#[cfg(feature = "std")]
+pub mod profile;
+pub mod quirks;
+pub mod random;
 pub mod read;
-pub mod stack;
\ No newline at end of file
+pub mod rom;
+#[cfg(feature = "std")]
+pub mod recording;
+pub mod run;
+#[cfg(feature = "std")]
+pub mod screenshot;
+pub mod stack;
+#[cfg(feature = "std")]
+pub mod trace;
\ No newline at end of file
@@ -0,0 +1,634 @@
+use crate::bus::MemoryProtection;
+use crate::emulator::Emulator;
+use crate::error::Error;
+use crate::quirks::Quirks;
+use crate::random::RandomMode;
+use crate::stack::{StackMode, DEFAULT_STACK_CAPACITY};
+use anyhow::anyhow;
+use std::fs::File;
+use std::io::{BufRead, BufReader, BufWriter, Write};
+use std::path::Path;
+
+/// The first line of every movie file.
+const MOVIE_MAGIC: &str = "CHIP8-MOVIE 1";
+
+/// The line separating the header from the key events.
+const EVENTS_MARKER: &str = "events";
+
+/// Returns the 64-bit FNV-1a hash of the ROM, used to check a movie is played on the ROM it
+/// was recorded with.
+pub fn rom_hash(rom: impl IntoIterator<Item = u8>) -> u64 {
+    rom.into_iter().fold(0xCBF2_9CE4_8422_2325, |hash, byte| {
+        (hash ^ byte as u64).wrapping_mul(0x0100_0000_01B3)
+    })
+}
+
+/// The settings an emulator must be started with to replay a movie.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct MovieHeader {
+    /// The hash of the ROM, see [`rom_hash`].
+    pub rom_hash: u64,
+    /// The mode of the emulator's random number generator.
+    pub random_mode: RandomMode,
+    /// The seed of the emulator's random number generator.
+    pub seed: u64,
+    /// The amount of instructions executed per frame.
+    pub instructions_per_frame: u32,
+    /// The quirks of the emulator, the defaults for movies recorded before they were stored.
+    pub quirks: Quirks,
+    /// Where the call stack is kept, internal for movies recorded before it was stored.
+    pub stack_mode: StackMode,
+    /// The maximum call depth, 16 for movies recorded before it was stored.
+    pub stack_depth: usize,
+    /// How accesses outside of the ROM's memory are checked, off for movies recorded before
+    /// it was stored.
+    pub memory_protection: MemoryProtection,
+}
+
+impl MovieHeader {
+    /// Returns the header matching the emulator's loaded ROM and settings.
+    pub fn for_emulator(emulator: &Emulator) -> Self {
+        MovieHeader {
+            rom_hash: rom_hash(emulator.rom()),
+            random_mode: emulator.rng().mode(),
+            seed: emulator.rng().seed(),
+            instructions_per_frame: emulator.instructions_per_frame(),
+            quirks: emulator.quirks(),
+            stack_mode: emulator.stack_mode(),
+            stack_depth: emulator.stack_capacity(),
+            memory_protection: emulator.memory_protection(),
+        }
+    }
+
+    /// Prepares the emulator to replay the movie with the recorded settings, failing if it
+    /// runs another ROM.
+    pub fn apply(&self, emulator: &mut Emulator) -> Result<(), anyhow::Error> {
+        let hash = rom_hash(emulator.rom());
+        if hash != self.rom_hash {
+            return Err(anyhow!(
+                "the movie was recorded with the ROM {:016x}, but the loaded ROM is {hash:016x}",
+                self.rom_hash
+            ));
+        }
+        emulator.set_random_mode(self.random_mode, self.seed);
+        emulator.set_instructions_per_frame(self.instructions_per_frame);
+        emulator.set_quirks(self.quirks);
+        emulator.configure_stack(self.stack_mode, self.stack_depth)?;
+        emulator.set_memory_protection(self.memory_protection);
+        Ok(())
+    }
+
+    fn write<W: Write>(&self, writer: &mut W) -> Result<(), anyhow::Error> {
+        writeln!(writer, "{MOVIE_MAGIC}")?;
+        writeln!(writer, "rom_hash {:016x}", self.rom_hash)?;
+        writeln!(writer, "random {}", self.random_mode.name())?;
+        writeln!(writer, "seed {}", self.seed)?;
+        writeln!(
+            writer,
+            "instructions_per_frame {}",
+            self.instructions_per_frame
+        )?;
+        writeln!(writer, "quirks {}", self.quirks)?;
+        writeln!(
+            writer,
+            "stack {} {}",
+            self.stack_mode.name(),
+            self.stack_depth
+        )?;
+        writeln!(
+            writer,
+            "memory_protection {}",
+            self.memory_protection.name()
+        )?;
+        writeln!(writer, "{EVENTS_MARKER}")?;
+        Ok(())
+    }
+}
+
+/// A change of the pressed key at the start of a frame.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct KeyEvent {
+    /// The index of the frame, starting at 0.
+    pub frame: u64,
+    /// The key pressed from this frame on, or `None` once it is released.
+    pub key: Option<u8>,
+}
+
+/// How the emulator is reset.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Reset {
+    /// See [`Emulator::soft_reset`].
+    Soft,
+    /// See [`Emulator::reset`].
+    Hard,
+}
+
+impl Reset {
+    /// Returns the name of the reset in movie files.
+    pub fn name(&self) -> &'static str {
+        match self {
+            Reset::Soft => "soft-reset",
+            Reset::Hard => "hard-reset",
+        }
+    }
+
+    /// Resets the emulator.
+    pub fn apply(self, emulator: &mut Emulator) -> Result<(), Error> {
+        match self {
+            Reset::Soft => {
+                emulator.soft_reset();
+                Ok(())
+            }
+            Reset::Hard => emulator.reset(),
+        }
+    }
+}
+
+/// A reset of the emulator before a frame.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ResetEvent {
+    /// The index of the frame, starting at 0.
+    pub frame: u64,
+    pub reset: Reset,
+}
+
+/// An input movie: the settings of a run and the key presses of each frame.
+///
+/// Movies are stored as text, a header followed by one `FRAME KEY` line per key change, with
+/// the key in hex or `-` for a release, and one `FRAME soft-reset` or `FRAME hard-reset` line
+/// per reset before a frame. An `end FRAMES` line closes movies which were finished properly;
+/// without it the movie ends after its last event.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Movie {
+    pub header: MovieHeader,
+    pub events: Vec<KeyEvent>,
+    pub resets: Vec<ResetEvent>,
+    /// The amount of frames recorded, if the recording was finished.
+    pub frame_count: Option<u64>,
+}
+
+impl Movie {
+    /// Returns the amount of frames the movie lasts.
+    pub fn length(&self) -> u64 {
+        self.frame_count.unwrap_or_else(|| {
+            let last_key = self.events.last().map(|event| event.frame + 1);
+            let last_reset = self.resets.last().map(|event| event.frame + 1);
+            last_key.max(last_reset).unwrap_or_default()
+        })
+    }
+
+    /// Parses a movie.
+    pub fn read<R: BufRead>(reader: R) -> Result<Movie, anyhow::Error> {
+        let mut lines = reader
+            .lines()
+            .enumerate()
+            .map(|(index, line)| line.map(|line| (index + 1, line)));
+        match lines.next().transpose()? {
+            Some((_, line)) if line.trim() == MOVIE_MAGIC => {}
+            _ => return Err(anyhow!("not a movie file, expected {MOVIE_MAGIC}")),
+        }
+
+        let mut rom_hash = None;
+        let mut random_mode = RandomMode::default();
+        let mut seed = None;
+        let mut instructions_per_frame = None;
+        let mut quirks = Quirks::default();
+        let mut stack = (StackMode::default(), DEFAULT_STACK_CAPACITY);
+        let mut memory_protection = MemoryProtection::default();
+        let mut events: Vec<KeyEvent> = Vec::new();
+        let mut resets: Vec<ResetEvent> = Vec::new();
+        let mut frame_count = None;
+        let mut in_events = false;
+        for line in lines {
+            let (number, line) = line?;
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+            let invalid = || anyhow!("invalid movie line {number}: {line}");
+            if !in_events {
+                if line == EVENTS_MARKER {
+                    in_events = true;
+                    continue;
+                }
+                let (name, value) = line.split_once(' ').ok_or_else(invalid)?;
+                let value = value.trim();
+                match name {
+                    "rom_hash" => {
+                        rom_hash = Some(u64::from_str_radix(value, 16).map_err(|_| invalid())?)
+                    }
+                    "random" => random_mode = value.parse().map_err(|_| invalid())?,
+                    "seed" => seed = Some(value.parse().map_err(|_| invalid())?),
+                    "instructions_per_frame" => {
+                        instructions_per_frame = Some(value.parse().map_err(|_| invalid())?)
+                    }
+                    "quirks" => quirks = value.parse().map_err(|_| invalid())?,
+                    "stack" => {
+                        let (mode, depth) = value.split_once(' ').ok_or_else(invalid)?;
+                        stack = (
+                            mode.parse().map_err(|_| invalid())?,
+                            depth.trim().parse().map_err(|_| invalid())?,
+                        );
+                    }
+                    "memory_protection" => {
+                        memory_protection = value.parse().map_err(|_| invalid())?
+                    }
+                    _ => return Err(invalid()),
+                }
+                continue;
+            }
+            if frame_count.is_some() {
+                return Err(anyhow!("movie line {number} comes after the end"));
+            }
+            let (first, second) = line.split_once(' ').ok_or_else(invalid)?;
+            let second = second.trim();
+            if first == "end" {
+                frame_count = Some(second.parse().map_err(|_| invalid())?);
+                continue;
+            }
+            let frame: u64 = first.parse().map_err(|_| invalid())?;
+            let reset = [Reset::Soft, Reset::Hard]
+                .into_iter()
+                .find(|reset| reset.name() == second);
+            if let Some(reset) = reset {
+                if resets.last().is_some_and(|last| last.frame >= frame) {
+                    return Err(anyhow!("movie line {number} is out of order"));
+                }
+                resets.push(ResetEvent { frame, reset });
+                continue;
+            }
+            let key = match second {
+                "-" => None,
+                key => match u8::from_str_radix(key, 16) {
+                    Ok(key) if key <= 0xF => Some(key),
+                    _ => return Err(invalid()),
+                },
+            };
+            if events.last().is_some_and(|last| last.frame >= frame) {
+                return Err(anyhow!("movie line {number} is out of order"));
+            }
+            events.push(KeyEvent { frame, key });
+        }
+
+        let missing = |name: &str| anyhow!("the movie header has no {name}");
+        Ok(Movie {
+            header: MovieHeader {
+                rom_hash: rom_hash.ok_or_else(|| missing("rom_hash"))?,
+                random_mode,
+                seed: seed.ok_or_else(|| missing("seed"))?,
+                instructions_per_frame: instructions_per_frame
+                    .ok_or_else(|| missing("instructions_per_frame"))?,
+                quirks,
+                stack_mode: stack.0,
+                stack_depth: stack.1,
+                memory_protection,
+            },
+            events,
+            resets,
+            frame_count,
+        })
+    }
+
+    /// Loads a movie from a file.
+    pub fn load(path: &Path) -> Result<Movie, anyhow::Error> {
+        Movie::read(BufReader::new(File::open(path)?))
+    }
+}
+
+/// Records the key pressed in each frame into a movie.
+///
+/// The movie is written as it is recorded, so it stays readable even when the frontend is
+/// killed before [`MovieRecorder::finish`] is called.
+pub struct MovieRecorder<W: Write> {
+    writer: W,
+    header: MovieHeader,
+    frame: u64,
+    key: Option<u8>,
+    /// The reset before the next frame, a hard one if both kinds happened.
+    reset: Option<Reset>,
+}
+
+impl<W: Write> MovieRecorder<W> {
+    /// Creates a new recorder and writes the movie's header.
+    pub fn new(mut writer: W, header: MovieHeader) -> Result<Self, anyhow::Error> {
+        header.write(&mut writer)?;
+        writer.flush()?;
+        Ok(MovieRecorder {
+            writer,
+            header,
+            frame: 0,
+            key: None,
+            reset: None,
+        })
+    }
+
+    /// Returns the header of the movie.
+    pub fn header(&self) -> &MovieHeader {
+        &self.header
+    }
+
+    /// Records a reset of the emulator before the next frame.
+    pub fn record_reset(&mut self, reset: Reset) {
+        self.reset = self.reset.max(Some(reset));
+    }
+
+    /// Records the key pressed in the next frame.
+    pub fn record_frame(&mut self, key: Option<u8>) -> Result<(), anyhow::Error> {
+        if let Some(reset) = self.reset.take() {
+            writeln!(self.writer, "{} {}", self.frame, reset.name())?;
+            self.writer.flush()?;
+        }
+        if key != self.key {
+            match key {
+                Some(key) => writeln!(self.writer, "{} {key:X}", self.frame)?,
+                None => writeln!(self.writer, "{} -", self.frame)?,
+            }
+            self.writer.flush()?;
+            self.key = key;
+        }
+        self.frame += 1;
+        Ok(())
+    }
+
+    /// Returns the amount of frames recorded.
+    pub fn frames_recorded(&self) -> u64 {
+        self.frame
+    }
+
+    /// Writes the end of the movie and returns the writer.
+    pub fn finish(mut self) -> Result<W, anyhow::Error> {
+        writeln!(self.writer, "end {}", self.frame)?;
+        self.writer.flush()?;
+        Ok(self.writer)
+    }
+}
+
+impl MovieRecorder<BufWriter<File>> {
+    /// Creates a recorder writing the movie to a file.
+    pub fn create(path: &Path, header: MovieHeader) -> Result<Self, anyhow::Error> {
+        MovieRecorder::new(BufWriter::new(File::create(path)?), header)
+    }
+}
+
+/// The input of one frame of a movie.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct MovieFrame {
+    /// The reset before the frame.
+    pub reset: Option<Reset>,
+    /// The key pressed in the frame.
+    pub key: Option<u8>,
+}
+
+impl MovieFrame {
+    /// Resets the emulator if the frame starts with a reset, then presses the frame's key.
+    pub fn apply(&self, emulator: &mut Emulator) -> Result<(), Error> {
+        if let Some(reset) = self.reset {
+            reset.apply(emulator)?;
+        }
+        emulator.handle_input(self.key.map(u16::from));
+        Ok(())
+    }
+}
+
+/// Plays a movie back, one frame at a time.
+pub struct MoviePlayer {
+    movie: Movie,
+    frame: u64,
+    next_event: usize,
+    next_reset: usize,
+    key: Option<u8>,
+}
+
+impl MoviePlayer {
+    /// Creates a new player starting at the first frame.
+    pub fn new(movie: Movie) -> Self {
+        MoviePlayer {
+            movie,
+            frame: 0,
+            next_event: 0,
+            next_reset: 0,
+            key: None,
+        }
+    }
+
+    /// Returns the movie's header.
+    pub fn header(&self) -> &MovieHeader {
+        &self.movie.header
+    }
+
+    /// Returns the input of the next frame, or `None` once the movie is over.
+    pub fn next_frame(&mut self) -> Option<MovieFrame> {
+        if self.is_finished() {
+            return None;
+        }
+        while let Some(event) = self.movie.events.get(self.next_event) {
+            if event.frame > self.frame {
+                break;
+            }
+            self.key = event.key;
+            self.next_event += 1;
+        }
+        let reset = match self.movie.resets.get(self.next_reset) {
+            Some(event) if event.frame <= self.frame => {
+                self.next_reset += 1;
+                Some(event.reset)
+            }
+            _ => None,
+        };
+        self.frame += 1;
+        Some(MovieFrame {
+            reset,
+            key: self.key,
+        })
+    }
+
+    /// Returns true once every frame of the movie was played.
+    pub fn is_finished(&self) -> bool {
+        self.frame >= self.movie.length()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const HEADER: MovieHeader = MovieHeader {
+        rom_hash: 0x1234,
+        random_mode: RandomMode::Vip,
+        seed: 42,
+        instructions_per_frame: 15,
+        quirks: Quirks {
+            vblank: true,
+            ..Quirks::none()
+        },
+        stack_mode: StackMode::Memory,
+        stack_depth: 12,
+        memory_protection: MemoryProtection::Wrap,
+    };
+
+    fn record(keys: &[Option<u8>], finish: bool) -> Vec<u8> {
+        let mut recorder = MovieRecorder::new(Vec::new(), HEADER).unwrap();
+        for key in keys {
+            recorder.record_frame(*key).unwrap();
+        }
+        if finish {
+            recorder.finish().unwrap()
+        } else {
+            recorder.writer
+        }
+    }
+
+    fn play(data: &[u8]) -> Vec<Option<u8>> {
+        let mut player = MoviePlayer::new(Movie::read(data).unwrap());
+        core::iter::from_fn(|| player.next_frame())
+            .map(|frame| frame.key)
+            .collect()
+    }
+
+    #[test]
+    fn test_rom_hash() {
+        assert_eq!(rom_hash(*b""), 0xCBF2_9CE4_8422_2325);
+        assert_eq!(rom_hash(*b"a"), 0xAF63_DC4C_8601_EC8C);
+    }
+
+    #[test]
+    fn test_movie_round_trip() {
+        let keys = [None, Some(0x5), Some(0x5), None, Some(0xA), None, None];
+
+        let data = record(&keys, true);
+
+        let text = String::from_utf8(data.clone()).unwrap();
+        assert!(text.ends_with("events\n1 5\n3 -\n4 A\n5 -\nend 7\n"));
+        assert_eq!(Movie::read(data.as_slice()).unwrap().header, HEADER);
+        assert_eq!(play(&data), keys);
+    }
+
+    #[test]
+    fn test_movie_records_resets() {
+        let mut recorder = MovieRecorder::new(Vec::new(), HEADER).unwrap();
+        recorder.record_frame(Some(0x1)).unwrap();
+        recorder.record_reset(Reset::Hard);
+        recorder.record_reset(Reset::Soft);
+        recorder.record_frame(None).unwrap();
+        recorder.record_reset(Reset::Soft);
+        recorder.record_frame(None).unwrap();
+        let data = recorder.finish().unwrap();
+
+        let text = String::from_utf8(data.clone()).unwrap();
+        assert!(text.ends_with("events\n0 1\n1 hard-reset\n1 -\n2 soft-reset\nend 3\n"));
+        let mut player = MoviePlayer::new(Movie::read(data.as_slice()).unwrap());
+        let frames: Vec<MovieFrame> = core::iter::from_fn(|| player.next_frame()).collect();
+        assert_eq!(
+            frames,
+            [
+                MovieFrame {
+                    reset: None,
+                    key: Some(0x1)
+                },
+                MovieFrame {
+                    reset: Some(Reset::Hard),
+                    key: None
+                },
+                MovieFrame {
+                    reset: Some(Reset::Soft),
+                    key: None
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn test_movie_frame_apply_resets() {
+        let mut emulator = Emulator::new();
+        emulator.load_rom(&[0x12, 0x02, 0x12, 0x02][..]).unwrap();
+        emulator.run_frame().unwrap();
+        assert_eq!(emulator.program_counter(), 0x202);
+
+        MovieFrame {
+            reset: Some(Reset::Soft),
+            key: Some(0x3),
+        }
+        .apply(&mut emulator)
+        .unwrap();
+
+        assert_eq!(emulator.program_counter(), 0x200);
+        assert_eq!(emulator.key_pressed(), Some(0x3));
+    }
+
+    #[test]
+    fn test_unfinished_movie_ends_after_last_reset() {
+        let header = String::from_utf8(record(&[], false)).unwrap();
+
+        let movie = Movie::read(format!("{header}1 5\n3 hard-reset\n").as_bytes()).unwrap();
+
+        assert_eq!(movie.length(), 4);
+        assert!(Movie::read(format!("{header}3 hard-reset\n1 soft-reset\n").as_bytes()).is_err());
+    }
+
+    #[test]
+    fn test_read_movie_without_stack_and_protection() {
+        let text =
+            format!("{MOVIE_MAGIC}\nrom_hash 1234\nseed 42\ninstructions_per_frame 15\nevents\n");
+
+        let header = Movie::read(text.as_bytes()).unwrap().header;
+
+        assert_eq!(header.stack_mode, StackMode::Internal);
+        assert_eq!(header.stack_depth, DEFAULT_STACK_CAPACITY);
+        assert_eq!(header.memory_protection, MemoryProtection::Off);
+    }
+
+    #[test]
+    fn test_unfinished_movie_ends_after_last_event() {
+        let data = record(&[Some(0x1), None, None], false);
+
+        assert_eq!(play(&data), [Some(0x1), None]);
+    }
+
+    #[test]
+    fn test_read_invalid_movie() {
+        assert!(Movie::read(b"nope\n".as_slice()).is_err());
+        assert!(Movie::read(format!("{MOVIE_MAGIC}\nseed 1\nevents\n").as_bytes()).is_err());
+        let header = String::from_utf8(record(&[], false)).unwrap();
+        assert!(Movie::read(format!("{header}3 1\n2 -\n").as_bytes()).is_err());
+        assert!(Movie::read(format!("{header}3 10\n").as_bytes()).is_err());
+    }
+
+    #[test]
+    fn test_header_apply_checks_rom() {
+        let mut emulator = Emulator::with_seed(RandomMode::XorShift, 1);
+        let header = MovieHeader {
+            random_mode: RandomMode::Vip,
+            seed: 7,
+            instructions_per_frame: 15,
+            quirks: Quirks::none(),
+            stack_mode: StackMode::Memory,
+            stack_depth: 12,
+            memory_protection: MemoryProtection::Error,
+            ..MovieHeader::for_emulator(&emulator)
+        };
+
+        header.apply(&mut emulator).unwrap();
+
+        assert_eq!(emulator.rng().mode(), RandomMode::Vip);
+        assert_eq!(emulator.rng().seed(), 7);
+        assert_eq!(emulator.instructions_per_frame(), 15);
+        assert_eq!(emulator.quirks(), Quirks::none());
+        assert_eq!(emulator.stack_mode(), StackMode::Memory);
+        assert_eq!(emulator.stack_capacity(), 12);
+        assert_eq!(emulator.memory_protection(), MemoryProtection::Error);
+        assert!(HEADER.apply(&mut emulator).is_err());
+    }
+
+    #[test]
+    fn test_header_hashes_rom_as_loaded() {
+        let mut emulator = Emulator::with_seed(RandomMode::XorShift, 1);
+        // overwrites its first instruction
+        emulator.load_rom(&[0xA2, 0x00, 0xF0, 0x55][..]).unwrap();
+        let header = MovieHeader::for_emulator(&emulator);
+
+        emulator.run_frame().unwrap();
+
+        assert_eq!(MovieHeader::for_emulator(&emulator), header);
+        assert!(header.apply(&mut emulator).is_ok());
+    }
+}
@@ -0,0 +1,927 @@
+use anyhow::anyhow;
+use std::collections::HashMap;
+
+/// The address where Octo programs start.
+const PROGRAM_START: u16 = 0x200;
+/// The last address of the memory.
+const MEMORY_END: u16 = 0xFFF;
+/// The most macro expansions a program may make, which stops macros which expand themselves.
+const MAX_MACRO_EXPANSIONS: usize = 100_000;
+
+/// The SUPER-CHIP and XO-CHIP statements, which the emulator does not run.
+const EXTENDED_STATEMENTS: [&str; 12] = [
+    "hires",
+    "lores",
+    "scroll-down",
+    "scroll-up",
+    "scroll-left",
+    "scroll-right",
+    "exit",
+    "saveflags",
+    "loadflags",
+    "plane",
+    "audio",
+    "pitch",
+];
+
+/// Compiles a program written in Octo, the assembly language of the Octo IDE, into ROM bytes.
+///
+/// The CHIP-8 subset of the language is supported: the statements, labels, the `if`, `loop` and
+/// `while` control flow, `:const`, `:alias`, `:unpack`, `:next`, `:org`, `:byte`, `:call`,
+/// `:macro` and `:calc`. The SUPER-CHIP and XO-CHIP statements are rejected.
+pub fn assemble(source: &str) -> Result<Vec<u8>, anyhow::Error> {
+    Assembler::new(source).assemble()
+}
+
+/// A word of the source and the line it is on.
+#[derive(Debug, Clone, PartialEq)]
+struct Token {
+    text: String,
+    line: usize,
+}
+
+/// Splits the source into words, dropping the `#` comments.
+fn tokenize(source: &str) -> Vec<Token> {
+    let mut tokens = Vec::new();
+    for (index, line) in source.lines().enumerate() {
+        let mut rest = line;
+        loop {
+            rest = rest.trim_start();
+            if rest.is_empty() || rest.starts_with('#') {
+                break;
+            }
+            // strings are single tokens, even with spaces
+            let end = match rest.strip_prefix('"') {
+                Some(string) => string.find('"').map_or(rest.len(), |end| end + 2),
+                None => rest.find(char::is_whitespace).unwrap_or(rest.len()),
+            };
+            tokens.push(Token {
+                text: rest[..end].to_string(),
+                line: index + 1,
+            });
+            rest = &rest[end..];
+        }
+    }
+    tokens
+}
+
+/// Where a label's address is written once it is defined.
+#[derive(Debug, Clone, Copy)]
+enum Patch {
+    /// The `NNN` of the instruction at the address.
+    Address,
+    /// The two `:unpack` instructions at the address.
+    Unpack,
+}
+
+/// An open control flow block.
+#[derive(Debug)]
+enum Block {
+    /// An `if ... begin`, with the address of the jump past its body.
+    If { jump: u16 },
+    /// An `else`, with the address of the jump past it.
+    Else { jump: u16 },
+    /// A `loop`, with its first address and the `while` jumps out of it.
+    Loop { start: u16, exits: Vec<u16> },
+}
+
+/// The right hand side of a comparison.
+#[derive(Debug, Clone, Copy)]
+enum Operand {
+    Register(u8),
+    Byte(u8),
+}
+
+/// The condition of an `if` or a `while`.
+#[derive(Debug, Clone, Copy)]
+enum Condition {
+    Equal(u8, Operand),
+    NotEqual(u8, Operand),
+    Less(u8, Operand),
+    Greater(u8, Operand),
+    LessOrEqual(u8, Operand),
+    GreaterOrEqual(u8, Operand),
+    Key(u8),
+    NotKey(u8),
+}
+
+impl Condition {
+    fn negate(self) -> Condition {
+        match self {
+            Condition::Equal(a, b) => Condition::NotEqual(a, b),
+            Condition::NotEqual(a, b) => Condition::Equal(a, b),
+            Condition::Less(a, b) => Condition::GreaterOrEqual(a, b),
+            Condition::GreaterOrEqual(a, b) => Condition::Less(a, b),
+            Condition::Greater(a, b) => Condition::LessOrEqual(a, b),
+            Condition::LessOrEqual(a, b) => Condition::Greater(a, b),
+            Condition::Key(a) => Condition::NotKey(a),
+            Condition::NotKey(a) => Condition::Key(a),
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+struct Macro {
+    parameters: Vec<String>,
+    body: Vec<Token>,
+}
+
+struct Assembler {
+    /// The tokens left to assemble, in reverse order so macros can push their expansion.
+    tokens: Vec<Token>,
+    /// The line of the last token taken, for the error messages.
+    line: usize,
+    /// The assembled bytes, starting at 0x200.
+    rom: Vec<u8>,
+    /// The address of the next byte.
+    here: u16,
+    labels: HashMap<String, u16>,
+    constants: HashMap<String, f64>,
+    aliases: HashMap<String, u8>,
+    macros: HashMap<String, Macro>,
+    /// The amount of macro expansions so far.
+    macro_expansions: usize,
+    /// The uses of labels which were not defined yet.
+    pending: Vec<(String, u16, Patch, usize)>,
+    blocks: Vec<Block>,
+    /// True while the program starts with a `jump main`.
+    jumps_to_main: bool,
+}
+
+impl Assembler {
+    fn new(source: &str) -> Self {
+        let mut tokens = tokenize(source);
+        tokens.reverse();
+        Assembler {
+            tokens,
+            line: 0,
+            // the program starts with a jump to main, dropped if main comes first
+            rom: vec![0x10, 0x00],
+            here: PROGRAM_START + 2,
+            labels: HashMap::new(),
+            constants: HashMap::new(),
+            aliases: HashMap::new(),
+            macros: HashMap::new(),
+            macro_expansions: 0,
+            pending: Vec::new(),
+            blocks: Vec::new(),
+            jumps_to_main: true,
+        }
+    }
+
+    fn error(&self, message: impl std::fmt::Display) -> anyhow::Error {
+        anyhow!("line {}: {message}", self.line)
+    }
+
+    fn assemble(mut self) -> Result<Vec<u8>, anyhow::Error> {
+        while !self.tokens.is_empty() {
+            self.statement()?;
+        }
+        if let Some(block) = self.blocks.last() {
+            return Err(anyhow!("a {block:?} block is not closed"));
+        }
+        let main = *self
+            .labels
+            .get("main")
+            .ok_or_else(|| anyhow!("the program has no main label"))?;
+        if self.jumps_to_main {
+            self.rom[..2].copy_from_slice(&(0x1000 | main).to_be_bytes());
+        }
+        for (name, address, patch, line) in std::mem::take(&mut self.pending) {
+            let value = *self
+                .labels
+                .get(&name)
+                .ok_or_else(|| anyhow!("line {line}: undefined name {name}"))?;
+            self.patch(address, patch, value);
+        }
+        Ok(self.rom)
+    }
+
+    fn next_token(&mut self) -> Result<String, anyhow::Error> {
+        let token = self
+            .tokens
+            .pop()
+            .ok_or_else(|| self.error("unexpected end of the program"))?;
+        self.line = token.line;
+        Ok(token.text)
+    }
+
+    fn peek_token(&self) -> Option<&str> {
+        self.tokens.last().map(|token| token.text.as_str())
+    }
+
+    fn expect(&mut self, expected: &str) -> Result<(), anyhow::Error> {
+        let token = self.next_token()?;
+        if token != expected {
+            return Err(self.error(format!("expected {expected}, found {token}")));
+        }
+        Ok(())
+    }
+
+    fn emit(&mut self, byte: u8) -> Result<(), anyhow::Error> {
+        if self.here > MEMORY_END {
+            return Err(self.error("the program does not fit in the memory"));
+        }
+        let index = (self.here - PROGRAM_START) as usize;
+        if self.rom.len() <= index {
+            self.rom.resize(index + 1, 0);
+        }
+        self.rom[index] = byte;
+        self.here += 1;
+        Ok(())
+    }
+
+    fn emit_instruction(&mut self, instruction: u16) -> Result<(), anyhow::Error> {
+        let [high, low] = instruction.to_be_bytes();
+        self.emit(high)?;
+        self.emit(low)
+    }
+
+    fn patch(&mut self, address: u16, patch: Patch, value: u16) {
+        let index = (address - PROGRAM_START) as usize;
+        match patch {
+            Patch::Address => {
+                self.rom[index] = (self.rom[index] & 0xF0) | (value >> 8) as u8;
+                self.rom[index + 1] = value as u8;
+            }
+            Patch::Unpack => {
+                self.rom[index + 1] |= (value >> 8) as u8;
+                self.rom[index + 3] = value as u8;
+            }
+        }
+    }
+
+    fn define_label(&mut self, name: String, address: u16) -> Result<(), anyhow::Error> {
+        if self.labels.contains_key(&name) {
+            return Err(self.error(format!("the label {name} is defined twice")));
+        }
+        self.labels.insert(name, address);
+        Ok(())
+    }
+
+    fn parse_number(token: &str) -> Option<f64> {
+        let (negative, digits) = match token.strip_prefix('-') {
+            Some(digits) => (true, digits),
+            None => (false, token),
+        };
+        let value = if let Some(hex) = digits.strip_prefix("0x") {
+            i64::from_str_radix(hex, 16).ok()? as f64
+        } else if let Some(binary) = digits.strip_prefix("0b") {
+            i64::from_str_radix(binary, 2).ok()? as f64
+        } else if digits.starts_with(|c: char| c.is_ascii_digit()) {
+            digits.parse::<i64>().ok()? as f64
+        } else {
+            return None;
+        };
+        Some(if negative { -value } else { value })
+    }
+
+    fn register(&self, token: &str) -> Option<u8> {
+        if let Some(register) = self.aliases.get(token) {
+            return Some(*register);
+        }
+        let digit = token.strip_prefix(['v', 'V'])?;
+        if digit.len() != 1 {
+            return None;
+        }
+        u8::from_str_radix(digit, 16).ok()
+    }
+
+    fn expect_register(&mut self) -> Result<u8, anyhow::Error> {
+        let token = self.next_token()?;
+        self.register(&token)
+            .ok_or_else(|| self.error(format!("expected a register, found {token}")))
+    }
+
+    /// Returns the value of a number or constant token.
+    fn constant(&self, token: &str) -> Option<f64> {
+        Self::parse_number(token).or_else(|| self.constants.get(token).copied())
+    }
+
+    fn expect_byte(&mut self) -> Result<u8, anyhow::Error> {
+        let token = self.next_token()?;
+        let value = self
+            .constant(&token)
+            .ok_or_else(|| self.error(format!("expected a number, found {token}")))?;
+        if !(-128.0..256.0).contains(&value) {
+            return Err(self.error(format!("{token} does not fit in a byte")));
+        }
+        Ok(value as i64 as u8)
+    }
+
+    fn expect_nibble(&mut self) -> Result<u8, anyhow::Error> {
+        let value = self.expect_byte()?;
+        if value > 0xF {
+            return Err(self.error(format!("{value} does not fit in 4 bits")));
+        }
+        Ok(value)
+    }
+
+    /// Returns the 12-bit address of a token, or 0 and a pending patch for a label which is
+    /// not defined yet. The instruction must be emitted at the current address.
+    fn expect_address(&mut self, patch: Patch) -> Result<u16, anyhow::Error> {
+        let token = self.next_token()?;
+        if let Some(address) = self.labels.get(&token) {
+            return Ok(*address);
+        }
+        match self.constant(&token) {
+            Some(value) if (0.0..4096.0).contains(&value) => Ok(value as u16),
+            Some(_) => Err(self.error(format!("{token} does not fit in 12 bits"))),
+            None => {
+                self.pending.push((token, self.here, patch, self.line));
+                Ok(0)
+            }
+        }
+    }
+
+    fn statement(&mut self) -> Result<(), anyhow::Error> {
+        let token = self.next_token()?;
+        if let Some(register) = self.register(&token) {
+            return self.register_statement(register);
+        }
+        match token.as_str() {
+            ":" => {
+                let name = self.next_token()?;
+                if name == "main" && self.jumps_to_main && self.here == PROGRAM_START + 2 {
+                    self.jumps_to_main = false;
+                    self.rom.clear();
+                    self.here = PROGRAM_START;
+                }
+                self.define_label(name, self.here)?;
+            }
+            ":const" => {
+                let name = self.next_token()?;
+                let value_token = self.next_token()?;
+                let value = self
+                    .constant(&value_token)
+                    .or_else(|| self.labels.get(&value_token).map(|address| *address as f64))
+                    .ok_or_else(|| self.error(format!("unknown value {value_token}")))?;
+                self.constants.insert(name, value);
+            }
+            ":alias" => {
+                let name = self.next_token()?;
+                let register = self.expect_register()?;
+                self.aliases.insert(name, register);
+            }
+            ":unpack" => {
+                let nibble = self.expect_nibble()?;
+                let address = self.expect_address(Patch::Unpack)?;
+                self.emit_instruction(0x6000 | (nibble as u16) << 4 | address >> 8)?;
+                self.emit_instruction(0x6100 | (address & 0xFF))?;
+            }
+            ":next" => {
+                let name = self.next_token()?;
+                self.define_label(name, self.here + 1)?;
+            }
+            ":org" => {
+                let address = self.expect_address(Patch::Address)?;
+                if address < PROGRAM_START {
+                    return Err(self.error(format!("cannot assemble at {address:04x}")));
+                }
+                self.here = address;
+            }
+            ":byte" => {
+                let byte = if self.peek_token() == Some("{") {
+                    self.next_token()?;
+                    self.calc_block()? as i64 as u8
+                } else {
+                    self.expect_byte()?
+                };
+                self.emit(byte)?;
+            }
+            ":call" => {
+                let address = self.expect_address(Patch::Address)?;
+                self.emit_instruction(0x2000 | address)?;
+            }
+            ":macro" => self.define_macro()?,
+            ":calc" => {
+                let name = self.next_token()?;
+                self.expect("{")?;
+                let value = self.calc_block()?;
+                self.constants.insert(name, value);
+            }
+            ":breakpoint" => {
+                self.next_token()?;
+            }
+            ":monitor" => {
+                self.next_token()?;
+                self.next_token()?;
+            }
+            ":assert" => {
+                if self
+                    .peek_token()
+                    .is_some_and(|token| token.starts_with('"'))
+                {
+                    self.next_token()?;
+                }
+                self.expect("{")?;
+                self.calc_block()?;
+            }
+            ";" | "return" => self.emit_instruction(0x00EE)?,
+            "clear" => self.emit_instruction(0x00E0)?,
+            "bcd" => {
+                let register = self.expect_register()?;
+                self.emit_instruction(0xF033 | (register as u16) << 8)?;
+            }
+            "save" | "load" => {
+                let register = self.expect_register()?;
+                if self.peek_token() == Some("-") {
+                    return Err(self.error("register ranges are an XO-CHIP instruction"));
+                }
+                let opcode = if token == "save" { 0xF055 } else { 0xF065 };
+                self.emit_instruction(opcode | (register as u16) << 8)?;
+            }
+            "sprite" => {
+                let x = self.expect_register()?;
+                let y = self.expect_register()?;
+                let rows = self.expect_nibble()?;
+                self.emit_instruction(0xD000 | (x as u16) << 8 | (y as u16) << 4 | rows as u16)?;
+            }
+            "jump" | "jump0" | "native" => {
+                let address = self.expect_address(Patch::Address)?;
+                let opcode = match token.as_str() {
+                    "jump" => 0x1000,
+                    "jump0" => 0xB000,
+                    _ => 0x0000,
+                };
+                self.emit_instruction(opcode | address)?;
+            }
+            "i" => {
+                let operator = self.next_token()?;
+                match operator.as_str() {
+                    ":=" => match self.peek_token() {
+                        Some("hex") => {
+                            self.next_token()?;
+                            let register = self.expect_register()?;
+                            self.emit_instruction(0xF029 | (register as u16) << 8)?;
+                        }
+                        Some("bighex") | Some("long") => {
+                            return Err(self.error("i := bighex and i := long are not CHIP-8"))
+                        }
+                        _ => {
+                            let address = self.expect_address(Patch::Address)?;
+                            self.emit_instruction(0xA000 | address)?;
+                        }
+                    },
+                    "+=" => {
+                        let register = self.expect_register()?;
+                        self.emit_instruction(0xF01E | (register as u16) << 8)?;
+                    }
+                    _ => return Err(self.error(format!("unknown operator i {operator}"))),
+                }
+            }
+            "delay" | "buzzer" => {
+                self.expect(":=")?;
+                let register = self.expect_register()?;
+                let opcode = if token == "delay" { 0xF015 } else { 0xF018 };
+                self.emit_instruction(opcode | (register as u16) << 8)?;
+            }
+            "if" => {
+                let condition = self.condition()?;
+                match self.next_token()?.as_str() {
+                    "then" => self.skip_unless(condition)?,
+                    "begin" => {
+                        self.skip_unless(condition.negate())?;
+                        let jump = self.here;
+                        self.emit_instruction(0x1000)?;
+                        self.blocks.push(Block::If { jump });
+                    }
+                    other => {
+                        return Err(self.error(format!("expected then or begin, found {other}")))
+                    }
+                }
+            }
+            "else" => match self.blocks.pop() {
+                Some(Block::If { jump }) => {
+                    let end_jump = self.here;
+                    self.emit_instruction(0x1000)?;
+                    self.patch(jump, Patch::Address, self.here);
+                    self.blocks.push(Block::Else { jump: end_jump });
+                }
+                _ => return Err(self.error("else without if ... begin")),
+            },
+            "end" => match self.blocks.pop() {
+                Some(Block::If { jump }) | Some(Block::Else { jump }) => {
+                    self.patch(jump, Patch::Address, self.here);
+                }
+                _ => return Err(self.error("end without if ... begin")),
+            },
+            "loop" => self.blocks.push(Block::Loop {
+                start: self.here,
+                exits: Vec::new(),
+            }),
+            "while" => {
+                let condition = self.condition()?;
+                self.skip_unless(condition.negate())?;
+                let exit = self.here;
+                self.emit_instruction(0x1000)?;
+                match self
+                    .blocks
+                    .iter_mut()
+                    .rev()
+                    .find(|block| matches!(block, Block::Loop { .. }))
+                {
+                    Some(Block::Loop { exits, .. }) => exits.push(exit),
+                    _ => return Err(self.error("while outside of a loop")),
+                }
+            }
+            "again" => match self.blocks.pop() {
+                Some(Block::Loop { start, exits }) => {
+                    self.emit_instruction(0x1000 | start)?;
+                    for exit in exits {
+                        self.patch(exit, Patch::Address, self.here);
+                    }
+                }
+                _ => return Err(self.error("again without loop")),
+            },
+            _ if EXTENDED_STATEMENTS.contains(&token.as_str()) => {
+                return Err(self.error(format!("{token} is a SUPER-CHIP or XO-CHIP instruction")))
+            }
+            _ if self.macros.contains_key(&token) => self.expand_macro(&token)?,
+            _ => {
+                if let Some(value) = self.constant(&token) {
+                    if !(-128.0..256.0).contains(&value) {
+                        return Err(self.error(format!("{token} does not fit in a byte")));
+                    }
+                    self.emit(value as i64 as u8)?;
+                } else if token.starts_with(|c: char| c.is_alphabetic() || c == '_') {
+                    // a label on its own calls the subroutine
+                    self.tokens.push(Token {
+                        text: token,
+                        line: self.line,
+                    });
+                    let address = self.expect_address(Patch::Address)?;
+                    self.emit_instruction(0x2000 | address)?;
+                } else {
+                    return Err(self.error(format!("unexpected {token}")));
+                }
+            }
+        }
+        Ok(())
+    }
+
+    fn register_statement(&mut self, x: u8) -> Result<(), anyhow::Error> {
+        let operator = self.next_token()?;
+        let x = (x as u16) << 8;
+        let operand = self.next_token()?;
+        let y = self.register(&operand).map(|y| (y as u16) << 4);
+        let instruction = match (operator.as_str(), y) {
+            (":=", Some(y)) => 0x8000 | x | y,
+            ("|=", Some(y)) => 0x8001 | x | y,
+            ("&=", Some(y)) => 0x8002 | x | y,
+            ("^=", Some(y)) => 0x8003 | x | y,
+            ("+=", Some(y)) => 0x8004 | x | y,
+            ("-=", Some(y)) => 0x8005 | x | y,
+            (">>=", Some(y)) => 0x8006 | x | y,
+            ("=-", Some(y)) => 0x8007 | x | y,
+            ("<<=", Some(y)) => 0x800E | x | y,
+            (":=", None) => match operand.as_str() {
+                "random" => 0xC000 | x | self.expect_byte()? as u16,
+                "key" => 0xF00A | x,
+                "delay" => 0xF007 | x,
+                _ => {
+                    self.tokens.push(Token {
+                        text: operand,
+                        line: self.line,
+                    });
+                    0x6000 | x | self.expect_byte()? as u16
+                }
+            },
+            ("+=", None) | ("-=", None) => {
+                self.tokens.push(Token {
+                    text: operand,
+                    line: self.line,
+                });
+                let byte = self.expect_byte()?;
+                let byte = if operator == "-=" {
+                    byte.wrapping_neg()
+                } else {
+                    byte
+                };
+                0x7000 | x | byte as u16
+            }
+            _ => return Err(self.error(format!("unknown operation {operator} {operand}"))),
+        };
+        self.emit_instruction(instruction)
+    }
+
+    fn condition(&mut self) -> Result<Condition, anyhow::Error> {
+        let register = self.expect_register()?;
+        let operator = self.next_token()?;
+        match operator.as_str() {
+            "key" => return Ok(Condition::Key(register)),
+            "-key" => return Ok(Condition::NotKey(register)),
+            _ => {}
+        }
+        let operand = match self.peek_token().and_then(|token| self.register(token)) {
+            Some(other) => {
+                self.next_token()?;
+                Operand::Register(other)
+            }
+            None => Operand::Byte(self.expect_byte()?),
+        };
+        Ok(match operator.as_str() {
+            "==" => Condition::Equal(register, operand),
+            "!=" => Condition::NotEqual(register, operand),
+            "<" => Condition::Less(register, operand),
+            ">" => Condition::Greater(register, operand),
+            "<=" => Condition::LessOrEqual(register, operand),
+            ">=" => Condition::GreaterOrEqual(register, operand),
+            _ => return Err(self.error(format!("unknown comparison {operator}"))),
+        })
+    }
+
+    /// Emits the instructions which skip the next instruction unless the condition holds.
+    fn skip_unless(&mut self, condition: Condition) -> Result<(), anyhow::Error> {
+        let skip = |opcode: u16, x: u8, operand: Operand, register_opcode: u16| match operand {
+            Operand::Byte(byte) => opcode | (x as u16) << 8 | byte as u16,
+            Operand::Register(y) => register_opcode | (x as u16) << 8 | (y as u16) << 4,
+        };
+        let instruction = match condition {
+            Condition::Equal(x, operand) => skip(0x4000, x, operand, 0x9000),
+            Condition::NotEqual(x, operand) => skip(0x3000, x, operand, 0x5000),
+            Condition::Key(x) => 0xE0A1 | (x as u16) << 8,
+            Condition::NotKey(x) => 0xE09E | (x as u16) << 8,
+            // the comparisons subtract in VF and test the borrow flag
+            Condition::Less(x, operand)
+            | Condition::Greater(x, operand)
+            | Condition::LessOrEqual(x, operand)
+            | Condition::GreaterOrEqual(x, operand) => {
+                match operand {
+                    Operand::Byte(byte) => self.emit_instruction(0x6F00 | byte as u16)?,
+                    Operand::Register(y) => self.emit_instruction(0x8F00 | (y as u16) << 4)?,
+                }
+                let x = (x as u16) << 4;
+                match condition {
+                    // VF = operand - VX, the flag is set when operand >= VX
+                    Condition::Greater(..) => {
+                        self.emit_instruction(0x8F05 | x)?;
+                        0x4F00
+                    }
+                    Condition::LessOrEqual(..) => {
+                        self.emit_instruction(0x8F05 | x)?;
+                        0x3F00
+                    }
+                    // VF = VX - operand, the flag is set when VX >= operand
+                    Condition::Less(..) => {
+                        self.emit_instruction(0x8F07 | x)?;
+                        0x4F00
+                    }
+                    _ => {
+                        self.emit_instruction(0x8F07 | x)?;
+                        0x3F00
+                    }
+                }
+            }
+        };
+        self.emit_instruction(instruction)
+    }
+
+    /// Reads the tokens up to the closing brace, the opening one was already taken.
+    fn block_tokens(&mut self) -> Result<Vec<Token>, anyhow::Error> {
+        let mut depth = 1;
+        let mut body = Vec::new();
+        loop {
+            let token = self
+                .tokens
+                .pop()
+                .ok_or_else(|| self.error("a { is not closed"))?;
+            match token.text.as_str() {
+                "{" => depth += 1,
+                "}" => {
+                    depth -= 1;
+                    if depth == 0 {
+                        return Ok(body);
+                    }
+                }
+                _ => {}
+            }
+            body.push(token);
+        }
+    }
+
+    fn define_macro(&mut self) -> Result<(), anyhow::Error> {
+        let name = self.next_token()?;
+        let mut parameters = Vec::new();
+        loop {
+            let token = self.next_token()?;
+            if token == "{" {
+                break;
+            }
+            parameters.push(token);
+        }
+        let body = self.block_tokens()?;
+        self.macros.insert(name, Macro { parameters, body });
+        Ok(())
+    }
+
+    fn expand_macro(&mut self, name: &str) -> Result<(), anyhow::Error> {
+        self.macro_expansions += 1;
+        if self.macro_expansions > MAX_MACRO_EXPANSIONS {
+            return Err(self.error(format!(
+                "more than {MAX_MACRO_EXPANSIONS} macro expansions, does {name} expand itself?"
+            )));
+        }
+        let definition = self.macros[name].clone();
+        let mut arguments = HashMap::new();
+        for parameter in &definition.parameters {
+            let argument = self.next_token()?;
+            arguments.insert(parameter.as_str(), argument);
+        }
+        let line = self.line;
+        let expansion = definition.body.iter().rev().map(|token| Token {
+            text: arguments
+                .get(token.text.as_str())
+                .cloned()
+                .unwrap_or_else(|| token.text.clone()),
+            line,
+        });
+        self.tokens.extend(expansion);
+        Ok(())
+    }
+
+    /// Evaluates a `:calc` expression up to the closing brace.
+    ///
+    /// Like in Octo the operators have no precedence and are evaluated from right to left.
+    fn calc_block(&mut self) -> Result<f64, anyhow::Error> {
+        let mut tokens = self.block_tokens()?;
+        tokens.reverse();
+        let value = self.calc_expression(&mut tokens)?;
+        match tokens.pop() {
+            Some(token) => Err(self.error(format!("unexpected {} in :calc", token.text))),
+            None => Ok(value),
+        }
+    }
+
+    fn calc_expression(&self, tokens: &mut Vec<Token>) -> Result<f64, anyhow::Error> {
+        let left = self.calc_term(tokens)?;
+        let Some(operator) = tokens.last().map(|token| token.text.clone()) else {
+            return Ok(left);
+        };
+        if operator == ")" {
+            return Ok(left);
+        }
+        tokens.pop();
+        let right = self.calc_expression(tokens)?;
+        let (a, b) = (left as i64, right as i64);
+        Ok(match operator.as_str() {
+            "+" => left + right,
+            "-" => left - right,
+            "*" => left * right,
+            "/" => left / right,
+            "%" => left % right,
+            "&" => (a & b) as f64,
+            "|" => (a | b) as f64,
+            "^" => (a ^ b) as f64,
+            "<<" => (a << b) as f64,
+            ">>" => (a >> b) as f64,
+            "pow" => left.powf(right),
+            "min" => left.min(right),
+            "max" => left.max(right),
+            "<" => (left < right) as u8 as f64,
+            ">" => (left > right) as u8 as f64,
+            "<=" => (left <= right) as u8 as f64,
+            ">=" => (left >= right) as u8 as f64,
+            "==" => (left == right) as u8 as f64,
+            "!=" => (left != right) as u8 as f64,
+            _ => return Err(self.error(format!("unknown operator {operator} in :calc"))),
+        })
+    }
+
+    fn calc_term(&self, tokens: &mut Vec<Token>) -> Result<f64, anyhow::Error> {
+        let token = tokens
+            .pop()
+            .ok_or_else(|| self.error("incomplete :calc expression"))?
+            .text;
+        let unary: fn(f64) -> f64 = match token.as_str() {
+            "(" => {
+                let value = self.calc_expression(tokens)?;
+                return match tokens.pop() {
+                    Some(token) if token.text == ")" => Ok(value),
+                    _ => Err(self.error("a ( is not closed in :calc")),
+                };
+            }
+            "-" => |value| -value,
+            "~" => |value| !(value as i64) as f64,
+            "!" => |value| (value == 0.0) as u8 as f64,
+            "sin" => f64::sin,
+            "cos" => f64::cos,
+            "tan" => f64::tan,
+            "exp" => f64::exp,
+            "log" => f64::ln,
+            "abs" => f64::abs,
+            "sqrt" => f64::sqrt,
+            "sign" => f64::signum,
+            "ceil" => f64::ceil,
+            "floor" => f64::floor,
+            _ => return self.calc_value(&token),
+        };
+        Ok(unary(self.calc_term(tokens)?))
+    }
+
+    /// Returns the value of a name or number in a `:calc` expression.
+    fn calc_value(&self, token: &str) -> Result<f64, anyhow::Error> {
+        match token {
+            "HERE" => Ok(self.here as f64),
+            "PI" => Ok(std::f64::consts::PI),
+            "E" => Ok(std::f64::consts::E),
+            _ => self
+                .constant(token)
+                .or_else(|| self.labels.get(token).map(|address| *address as f64))
+                .or_else(|| self.register(token).map(f64::from))
+                .ok_or_else(|| self.error(format!("unknown name {token} in :calc"))),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn assemble_words(source: &str) -> Vec<u16> {
+        assemble(source)
+            .unwrap()
+            .chunks(2)
+            .map(|word| u16::from_be_bytes([word[0], *word.get(1).unwrap_or(&0)]))
+            .collect()
+    }
+
+    #[test]
+    fn test_assemble_statements() {
+        let source = "
+            # draws a digit and waits for a key
+            : main
+              clear
+              v0 := 5  v1 := 0x0A  v2 := random 0b111
+              i := hex v0
+              sprite v0 v1 5
+              v0 += 1  v0 -= 1  v0 += v1  v0 =- v1  v3 >>= v3
+              v2 := key
+              delay := v2
+              jump main
+        ";
+
+        assert_eq!(
+            assemble_words(source),
+            [
+                0x00E0, 0x6005, 0x610A, 0xC207, 0xF029, 0xD015, 0x7001, 0x70FF, 0x8014, 0x8017,
+                0x8336, 0xF20A, 0xF215, 0x1200
+            ]
+        );
+    }
+
+    #[test]
+    fn test_assemble_labels_and_control_flow() {
+        let source = "
+            :alias counter v4
+            :const LIMIT 3
+            : draw  sprite v0 v0 1 ;
+            : main
+              jump start
+            : start
+              loop
+                counter += 1
+                while counter != LIMIT
+                if counter == 1 then draw
+                if counter > v5 begin
+                  :unpack 0xA data
+                else
+                  i := data
+                end
+              again
+            : data 0xFF
+        ";
+
+        assert_eq!(
+            assemble_words(source),
+            [
+                0x1206, 0xD001, 0x00EE, 0x1208, 0x7401, 0x4403, 0x1224, 0x4401, 0x2202, 0x8F50,
+                0x8F45, 0x3F00, 0x1220, 0x60A2, 0x6124, 0x1222, 0xA224, 0x1208, 0xFF00
+            ]
+        );
+    }
+
+    #[test]
+    fn test_assemble_macros_and_calc() {
+        let source = "
+            :macro set-both A B { v0 := A v1 := B }
+            :calc WIDTH { 8 * ( 2 + 2 ) }
+            : main
+              set-both WIDTH 2
+              :byte { WIDTH - 1 }
+        ";
+
+        assert_eq!(assemble(source).unwrap(), [0x60, 0x20, 0x61, 0x02, 0x1F]);
+    }
+
+    #[test]
+    fn test_assemble_errors() {
+        assert!(assemble(": start clear").is_err());
+        assert!(assemble(": main jump nowhere").is_err());
+        assert!(assemble(": main hires").is_err());
+        assert!(assemble(": main loop clear").is_err());
+        assert!(assemble(": main v0 := 300").is_err());
+        assert!(assemble(":macro m { m } : main m").is_err());
+        assert!(assemble(":macro m { clear m m } : main m").is_err());
+    }
+}
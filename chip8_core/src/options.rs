@@ -0,0 +1,161 @@
+use crate::bus::MemoryProtection;
+use crate::database::{RomDatabase, RomOverrides};
+use crate::emulator::Emulator;
+use crate::error::Error;
+use crate::quirks::Quirks;
+use crate::random::RandomMode;
+use crate::stack::{StackMode, DEFAULT_STACK_CAPACITY};
+use clap::Args;
+use log::info;
+use std::path::PathBuf;
+
+/// The command line options which change how a ROM is emulated, shared by the frontends with
+/// `#[command(flatten)]`.
+#[derive(Args, Debug, Clone)]
+pub struct EmulatorOptions {
+    /// The seed of the random number generator, random by default.
+    #[arg(long)]
+    pub seed: Option<u64>,
+    /// The random number generator: xorshift or vip (the COSMAC VIP interpreter's routine).
+    #[arg(long, default_value = "xorshift")]
+    pub random: RandomMode,
+    /// The maximum depth of nested subroutine calls.
+    #[arg(long, default_value_t = DEFAULT_STACK_CAPACITY)]
+    pub stack_depth: usize,
+    /// Keeps the call stack in the emulated memory below 0xED0, like the COSMAC VIP (at most 24
+    /// calls deep).
+    #[arg(long)]
+    pub vip_stack: bool,
+    /// Checks reads past 4 KB and writes below 0x200: off, wrap (like real hardware), error or
+    /// log.
+    #[arg(long, default_value = "off")]
+    pub memory_protection: MemoryProtection,
+    /// The quirks to emulate, a comma separated list such as shift,jump or none.
+    /// Defaults to the quirks of the ROM's platform in the ROM database.
+    #[arg(long)]
+    pub quirks: Option<Quirks>,
+    /// The amount of instructions executed per frame. Defaults to the ROM database's tick rate,
+    /// or 8.
+    #[arg(long, value_parser = clap::value_parser!(u32).range(1..))]
+    pub tick_rate: Option<u32>,
+    /// A programs.json file of the chip-8-database to look ROMs up in instead of the bundled one.
+    #[arg(long)]
+    pub rom_database: Option<PathBuf>,
+}
+
+impl EmulatorOptions {
+    /// Configures a new emulator with the options, before the ROM is loaded.
+    pub fn apply(&self, emulator: &mut Emulator) -> Result<(), Error> {
+        let seed = self.seed.unwrap_or(emulator.rng().seed());
+        emulator.set_random_mode(self.random, seed);
+        info!(
+            "Random numbers from {} with seed {seed}",
+            self.random.name()
+        );
+        let stack_mode = if self.vip_stack {
+            StackMode::Memory
+        } else {
+            StackMode::Internal
+        };
+        emulator.configure_stack(stack_mode, self.stack_depth)?;
+        emulator.set_memory_protection(self.memory_protection);
+        Ok(())
+    }
+
+    /// Loads the ROM database of the options, the bundled one by default.
+    pub fn load_database(&self) -> Result<RomDatabase, anyhow::Error> {
+        match &self.rom_database {
+            Some(path) => RomDatabase::load(path),
+            None => Ok(RomDatabase::bundled()),
+        }
+    }
+
+    /// Returns the settings of the options which win over the ROM's, see
+    /// [`RomDatabase::configure`].
+    pub fn overrides(&self) -> RomOverrides {
+        RomOverrides {
+            quirks: self.quirks,
+            tick_rate: self.tick_rate,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use clap::Parser;
+
+    #[derive(Parser)]
+    struct CliArgs {
+        #[command(flatten)]
+        emulator: EmulatorOptions,
+    }
+
+    fn parse(args: &[&str]) -> EmulatorOptions {
+        CliArgs::try_parse_from(["chip8"].iter().chain(args))
+            .expect("Failed to parse")
+            .emulator
+    }
+
+    #[test]
+    fn test_apply_options() {
+        let mut emulator = Emulator::new();
+
+        parse(&[
+            "--seed",
+            "42",
+            "--random",
+            "vip",
+            "--vip-stack",
+            "--stack-depth",
+            "12",
+            "--memory-protection",
+            "wrap",
+        ])
+        .apply(&mut emulator)
+        .unwrap();
+
+        assert_eq!(emulator.rng().mode(), RandomMode::Vip);
+        assert_eq!(emulator.rng().seed(), 42);
+        assert_eq!(emulator.stack_mode(), StackMode::Memory);
+        assert_eq!(emulator.stack_capacity(), 12);
+        assert_eq!(emulator.memory_protection(), MemoryProtection::Wrap);
+    }
+
+    #[test]
+    fn test_default_options_keep_the_seed() {
+        let mut emulator = Emulator::new();
+        let seed = emulator.rng().seed();
+
+        parse(&[]).apply(&mut emulator).unwrap();
+
+        assert_eq!(emulator.rng().mode(), RandomMode::XorShift);
+        assert_eq!(emulator.rng().seed(), seed);
+        assert_eq!(emulator.stack_mode(), StackMode::Internal);
+        assert_eq!(emulator.stack_capacity(), DEFAULT_STACK_CAPACITY);
+        assert_eq!(emulator.memory_protection(), MemoryProtection::Off);
+    }
+
+    #[test]
+    fn test_overrides() {
+        let options = parse(&["--quirks", "shift,jump", "--tick-rate", "20"]);
+
+        assert_eq!(
+            options.overrides(),
+            RomOverrides {
+                quirks: Some("shift,jump".parse().unwrap()),
+                tick_rate: Some(20),
+            }
+        );
+        assert!(CliArgs::try_parse_from(["chip8", "--tick-rate", "0"]).is_err());
+    }
+
+    #[test]
+    fn test_options_reject_a_deep_vip_stack() {
+        let mut emulator = Emulator::new();
+
+        let result = parse(&["--vip-stack", "--stack-depth", "25"]).apply(&mut emulator);
+
+        assert!(matches!(result, Err(Error::InvalidStackCapacity { .. })));
+    }
+}
@@ -0,0 +1,177 @@
+use crate::quirks::Quirks;
+use std::time::{Duration, Instant};
+
+/// How long a message stays over the game view.
+pub const MESSAGE_DURATION: Duration = Duration::from_secs(2);
+/// How often the frames and instructions per second are measured.
+const MEASURE_INTERVAL: Duration = Duration::from_secs(1);
+
+/// The text the frontends draw over the game view.
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+pub struct OverlayText {
+    /// The frames and instructions per second and the quirks preset, when they are shown.
+    pub stats: Option<String>,
+    /// The latest message, such as where a screenshot was saved, until it expires.
+    pub message: Option<String>,
+    /// Drawn large across the game view, `PAUSED` while the emulation is paused.
+    pub banner: Option<&'static str>,
+}
+
+impl OverlayText {
+    pub fn is_empty(&self) -> bool {
+        self.stats.is_none() && self.message.is_none() && self.banner.is_none()
+    }
+}
+
+/// Collects what the frontends show over the game view: the measured frames and
+/// instructions per second, the quirks preset, short messages and a banner while paused.
+#[derive(Debug, Clone)]
+pub struct Overlay {
+    show_stats: bool,
+    quirks: Quirks,
+    paused: bool,
+    message: Option<(String, Instant)>,
+    measure_start: Instant,
+    /// The frames and instructions run since the measure started.
+    frames: u64,
+    instructions: u64,
+    /// The emulator's instruction count at the last record, None before the first one.
+    last_instructions_executed: Option<u64>,
+    frames_per_second: u64,
+    instructions_per_second: u64,
+}
+
+impl Overlay {
+    /// Creates an overlay which shows the statistics if `show_stats` is true.
+    pub fn new(show_stats: bool) -> Self {
+        Overlay {
+            show_stats,
+            quirks: Quirks::default(),
+            paused: false,
+            message: None,
+            measure_start: Instant::now(),
+            frames: 0,
+            instructions: 0,
+            last_instructions_executed: None,
+            frames_per_second: 0,
+            instructions_per_second: 0,
+        }
+    }
+
+    /// Shows or hides the frames and instructions per second and the quirks preset.
+    pub fn toggle_stats(&mut self) {
+        self.show_stats = !self.show_stats;
+    }
+
+    pub fn set_quirks(&mut self, quirks: Quirks) {
+        self.quirks = quirks;
+    }
+
+    /// Shows the `PAUSED` banner while `paused` is true.
+    pub fn set_paused(&mut self, paused: bool) {
+        self.paused = paused;
+    }
+
+    /// Shows the message for [`MESSAGE_DURATION`], replacing the previous one.
+    pub fn show_message(&mut self, message: impl Into<String>) {
+        self.message = Some((message.into(), Instant::now()));
+    }
+
+    /// Counts the frames run during a tick, paused ones included, and the emulator's
+    /// [`Emulator::instructions_executed`] after them.
+    ///
+    /// [`Emulator::instructions_executed`]: crate::emulator::Emulator::instructions_executed
+    pub fn record_tick(&mut self, frames: u32, instructions_executed: u64) {
+        self.record_tick_at(Instant::now(), frames, instructions_executed);
+    }
+
+    fn record_tick_at(&mut self, now: Instant, frames: u32, instructions_executed: u64) {
+        // a reloaded emulator counts from 0 again
+        let last = self
+            .last_instructions_executed
+            .unwrap_or(instructions_executed);
+        self.instructions += instructions_executed.saturating_sub(last);
+        self.last_instructions_executed = Some(instructions_executed);
+        self.frames += u64::from(frames);
+
+        let elapsed = now.saturating_duration_since(self.measure_start);
+        if elapsed >= MEASURE_INTERVAL {
+            let per_second = |count: u64| (count as f64 / elapsed.as_secs_f64()).round() as u64;
+            self.frames_per_second = per_second(self.frames);
+            self.instructions_per_second = per_second(self.instructions);
+            self.frames = 0;
+            self.instructions = 0;
+            self.measure_start = now;
+        }
+    }
+
+    /// Returns the text to draw now.
+    pub fn text(&self) -> OverlayText {
+        self.text_at(Instant::now())
+    }
+
+    fn text_at(&self, now: Instant) -> OverlayText {
+        OverlayText {
+            stats: self.show_stats.then(|| {
+                format!(
+                    "{} FPS {} IPS {}",
+                    self.frames_per_second,
+                    self.instructions_per_second,
+                    self.quirks.preset_name()
+                )
+            }),
+            message: self
+                .message
+                .as_ref()
+                .filter(|(_, shown)| now.saturating_duration_since(*shown) < MESSAGE_DURATION)
+                .map(|(message, _)| message.clone()),
+            banner: self.paused.then_some("PAUSED"),
+        }
+    }
+}
+
+impl Default for Overlay {
+    fn default() -> Self {
+        Overlay::new(false)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::quirks::Platform;
+
+    #[test]
+    fn test_overlay_measures_rates() {
+        let mut overlay = Overlay::new(true);
+        overlay.set_quirks(Platform::Chip48.quirks());
+        let start = overlay.measure_start;
+        overlay.record_tick_at(start, 0, 100);
+        for tick in 1..=120 {
+            overlay.record_tick_at(
+                start + Duration::from_millis(tick * 1000 / 60),
+                1,
+                100 + tick * 8,
+            );
+        }
+
+        let text = overlay.text_at(start);
+        assert_eq!(text.stats.as_deref(), Some("60 FPS 480 IPS chip48"));
+        assert_eq!(text.banner, None);
+    }
+
+    #[test]
+    fn test_overlay_messages_expire() {
+        let mut overlay = Overlay::default();
+        assert!(overlay.text().is_empty());
+
+        overlay.show_message("Saved screenshot");
+        overlay.set_paused(true);
+        let shown = overlay.message.as_ref().unwrap().1;
+        let text = overlay.text_at(shown);
+        assert_eq!(text.message.as_deref(), Some("Saved screenshot"));
+        assert_eq!(text.banner, Some("PAUSED"));
+        assert_eq!(text.stats, None);
+        assert_eq!(overlay.text_at(shown + MESSAGE_DURATION).message, None);
+    }
+}
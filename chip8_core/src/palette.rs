@@ -0,0 +1,283 @@
+use crate::error::Error;
+use core::str::FromStr;
+
+#[cfg(feature = "std")]
+use anyhow::anyhow;
+#[cfg(feature = "std")]
+use std::path::Path;
+
+/// An RGB color.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Rgb {
+    pub r: u8,
+    pub g: u8,
+    pub b: u8,
+}
+
+impl Rgb {
+    /// Creates a new color from its components.
+    pub const fn new(r: u8, g: u8, b: u8) -> Self {
+        Rgb { r, g, b }
+    }
+
+    /// Parses a hex color in the `#RRGGBB` or `RRGGBB` form.
+    pub fn from_hex(hex: &str) -> Option<Rgb> {
+        let hex = hex.trim();
+        let hex = hex.strip_prefix('#').unwrap_or(hex);
+        // from_str_radix would take a sign
+        if hex.len() != 6 || !hex.bytes().all(|byte| byte.is_ascii_hexdigit()) {
+            return None;
+        }
+        let component = |index: usize| u8::from_str_radix(&hex[index..index + 2], 16).ok();
+        Some(Rgb::new(component(0)?, component(2)?, component(4)?))
+    }
+}
+
+impl FromStr for Rgb {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Rgb::from_hex(s).ok_or_else(|| Error::parse(s, "a #RRGGBB hex color"))
+    }
+}
+
+/// The colors used to render the display.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Palette {
+    /// The color of the pixels which are off.
+    pub background: Rgb,
+    /// The color of the pixels which are on.
+    pub pixel: Rgb,
+}
+
+impl Palette {
+    /// Green pixels on a black background.
+    pub const CLASSIC_GREEN: Palette = Palette::new(Rgb::new(0, 0, 0), Rgb::new(0, 255, 0));
+    /// Amber monochrome monitor.
+    pub const AMBER: Palette = Palette::new(Rgb::new(0x1A, 0x0F, 0x00), Rgb::new(0xFF, 0xB0, 0x00));
+    /// Greenish LCD screen with dark pixels.
+    pub const LCD: Palette = Palette::new(Rgb::new(0xC7, 0xF0, 0xD8), Rgb::new(0x43, 0x52, 0x3D));
+    /// White pixels on a black background.
+    pub const HIGH_CONTRAST: Palette = Palette::new(Rgb::new(0, 0, 0), Rgb::new(0xFF, 0xFF, 0xFF));
+    /// The default colors of the Octo IDE.
+    pub const OCTO: Palette = Palette::new(Rgb::new(0x99, 0x66, 0x00), Rgb::new(0xFF, 0xCC, 0x00));
+
+    /// The built-in palettes and their names, as accepted by [`Palette::from_name`].
+    pub const BUILT_IN: [(&'static str, Palette); 5] = [
+        ("classic", Palette::CLASSIC_GREEN),
+        ("amber", Palette::AMBER),
+        ("lcd", Palette::LCD),
+        ("high-contrast", Palette::HIGH_CONTRAST),
+        ("octo", Palette::OCTO),
+    ];
+
+    /// Creates a new palette.
+    pub const fn new(background: Rgb, pixel: Rgb) -> Self {
+        Palette { background, pixel }
+    }
+
+    /// Returns the built-in palette with the given name, ignoring the case.
+    pub fn from_name(name: &str) -> Option<Palette> {
+        Palette::BUILT_IN
+            .iter()
+            .find(|(palette_name, _)| palette_name.eq_ignore_ascii_case(name.trim()))
+            .map(|(_, palette)| *palette)
+    }
+
+    /// Parses a palette name or a custom palette in the `BACKGROUND,PIXEL` hex form,
+    /// for example `#000000,#00FF00`.
+    pub fn parse(spec: &str) -> Option<Palette> {
+        if let Some((background, pixel)) = spec.split_once(',') {
+            return Some(Palette::new(
+                Rgb::from_hex(background)?,
+                Rgb::from_hex(pixel)?,
+            ));
+        }
+        Palette::from_name(spec)
+    }
+
+    /// Returns the color of a pixel with the given intensity, blending linearly from the
+    /// background at 0 to the pixel color at 255.
+    pub fn blend(&self, intensity: u8) -> Rgb {
+        let mix = |background: u8, pixel: u8| {
+            let background = background as u32;
+            let pixel = pixel as u32;
+            let intensity = intensity as u32;
+            ((background * (255 - intensity) + pixel * intensity) / 255) as u8
+        };
+        Rgb::new(
+            mix(self.background.r, self.pixel.r),
+            mix(self.background.g, self.pixel.g),
+            mix(self.background.b, self.pixel.b),
+        )
+    }
+
+    /// Returns the palette hint of a ROM, stored next to it in a `.palette` file.
+    ///
+    /// The file contains a palette in the same form accepted by [`Palette::parse`].
+    #[cfg(feature = "std")]
+    pub fn hint_for_rom(rom_path: &Path) -> Result<Option<Palette>, anyhow::Error> {
+        let hint_path = rom_path.with_extension("palette");
+        if !hint_path.exists() {
+            return Ok(None);
+        }
+        let spec = std::fs::read_to_string(&hint_path)?;
+        Palette::parse(&spec)
+            .map(Some)
+            .ok_or_else(|| anyhow!("invalid palette in {}", hint_path.display()))
+    }
+}
+
+/// The palette and colors chosen on the command line.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct PaletteChoice {
+    /// The palette, the ROM's colors are used when None.
+    pub palette: Option<Palette>,
+    /// Overrides the background color of the palette.
+    pub background: Option<Rgb>,
+    /// Overrides the pixel color of the palette.
+    pub pixel: Option<Rgb>,
+}
+
+impl PaletteChoice {
+    /// Resolves the palette of a ROM: the chosen palette, falling back to the ROM's palette
+    /// hint file, then to `rom_colors`, the colors of the ROM's settings,
+    /// and then to the frontend's `default`. The chosen background and pixel colors override it.
+    #[cfg(feature = "std")]
+    pub fn resolve(
+        &self,
+        rom_path: &Path,
+        rom_colors: Option<Palette>,
+        default: Palette,
+    ) -> Result<Palette, anyhow::Error> {
+        let mut palette = match self.palette {
+            Some(palette) => palette,
+            None => Palette::hint_for_rom(rom_path)?
+                .or(rom_colors)
+                .unwrap_or(default),
+        };
+        if let Some(background) = self.background {
+            palette.background = background;
+        }
+        if let Some(pixel) = self.pixel {
+            palette.pixel = pixel;
+        }
+        Ok(palette)
+    }
+}
+
+impl Default for Palette {
+    fn default() -> Self {
+        Palette::CLASSIC_GREEN
+    }
+}
+
+impl FromStr for Palette {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Palette::parse(s).ok_or_else(|| {
+            Error::parse(
+                s,
+                "classic, amber, lcd, high-contrast, octo or BACKGROUND,PIXEL hex colors",
+            )
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_rgb_from_hex() {
+        assert_eq!(Rgb::from_hex("#FFCC00"), Some(Rgb::new(0xFF, 0xCC, 0x00)));
+        assert_eq!(Rgb::from_hex("0a0b0c"), Some(Rgb::new(0x0A, 0x0B, 0x0C)));
+        assert_eq!(Rgb::from_hex("#FFF"), None);
+        assert_eq!(Rgb::from_hex("#GGGGGG"), None);
+        assert_eq!(Rgb::from_hex("+1+2+3"), None);
+    }
+
+    #[test]
+    fn test_palette_from_name() {
+        for (name, palette) in Palette::BUILT_IN {
+            assert_eq!(Palette::from_name(name), Some(palette));
+        }
+        assert_eq!(Palette::from_name("Amber"), Some(Palette::AMBER));
+        assert_eq!(Palette::from_name("unknown"), None);
+    }
+
+    #[test]
+    fn test_palette_parse_custom() {
+        assert_eq!(
+            Palette::parse("#101010, #FAFAFA"),
+            Some(Palette::new(
+                Rgb::new(0x10, 0x10, 0x10),
+                Rgb::new(0xFA, 0xFA, 0xFA)
+            ))
+        );
+        assert_eq!(Palette::parse("#101010,nope"), None);
+        assert!("octo".parse::<Palette>().is_ok());
+        assert!("nope".parse::<Palette>().is_err());
+    }
+
+    #[cfg(feature = "std")]
+    #[test]
+    fn test_palette_choice_resolve() {
+        let directory = std::env::temp_dir().join("chip8_palette_choice");
+        std::fs::create_dir_all(&directory).unwrap();
+        let hinted = directory.join("hinted.ch8");
+        std::fs::write(directory.join("hinted.palette"), "amber").unwrap();
+        let plain = directory.join("plain.ch8");
+        let chosen = PaletteChoice {
+            palette: Some(Palette::LCD),
+            ..PaletteChoice::default()
+        };
+
+        assert_eq!(
+            chosen
+                .resolve(&hinted, Some(Palette::OCTO), Palette::default())
+                .unwrap(),
+            Palette::LCD
+        );
+        let unchosen = PaletteChoice::default();
+        assert_eq!(
+            unchosen
+                .resolve(&hinted, Some(Palette::OCTO), Palette::default())
+                .unwrap(),
+            Palette::AMBER
+        );
+        assert_eq!(
+            unchosen
+                .resolve(&plain, Some(Palette::OCTO), Palette::default())
+                .unwrap(),
+            Palette::OCTO
+        );
+        assert_eq!(
+            unchosen
+                .resolve(&plain, None, Palette::HIGH_CONTRAST)
+                .unwrap(),
+            Palette::HIGH_CONTRAST
+        );
+
+        let overridden = PaletteChoice {
+            pixel: Some(Rgb::new(1, 2, 3)),
+            ..PaletteChoice::default()
+        };
+        assert_eq!(
+            overridden
+                .resolve(&plain, Some(Palette::OCTO), Palette::default())
+                .unwrap(),
+            Palette::new(Palette::OCTO.background, Rgb::new(1, 2, 3))
+        );
+    }
+
+    #[test]
+    fn test_palette_blend() {
+        let palette = Palette::new(Rgb::new(0, 0, 0), Rgb::new(200, 100, 255));
+
+        assert_eq!(palette.blend(0), palette.background);
+        assert_eq!(palette.blend(255), palette.pixel);
+        assert_eq!(palette.blend(51), Rgb::new(40, 20, 51));
+    }
+}
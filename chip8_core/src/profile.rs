@@ -0,0 +1,422 @@
+use crate::instruction::ProcessorInstruction;
+use anyhow::anyhow;
+use std::collections::{BTreeMap, HashMap};
+use std::fs::File;
+use std::io::{self, BufWriter, Write};
+use std::path::Path;
+use std::str::FromStr;
+
+/// How many of the most executed addresses the report lists.
+pub const HOT_SPOTS: usize = 20;
+/// The frames per second the waiting time is measured in.
+const FRAMES_PER_SECOND: f64 = 60.0;
+/// The id of the stack outside of any subroutine.
+const MAIN_STACK: usize = 0;
+
+/// How a profile is written.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ProfileFormat {
+    /// A readable report of the hot spots, instruction types, key waits and draw calls.
+    Report,
+    /// Folded stacks for flame graph tools: the subroutines and the address of every executed
+    /// instruction, separated by `;`, and how often it ran.
+    Folded,
+}
+
+impl FromStr for ProfileFormat {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_ascii_lowercase().as_str() {
+            "report" => Ok(ProfileFormat::Report),
+            "folded" => Ok(ProfileFormat::Folded),
+            _ => Err(anyhow!(
+                "unknown profile format {s}, expected report or folded"
+            )),
+        }
+    }
+}
+
+/// Counts where a program spends its instructions: the executions per address and per
+/// instruction type, the waits for a key and the draw calls per frame.
+#[derive(Debug, Clone, Default)]
+pub struct Profiler {
+    /// The executions at each address and the last instruction executed there.
+    addresses: BTreeMap<u16, (ProcessorInstruction, u64)>,
+    /// The executions of each instruction type, by [`ProcessorInstruction::name`].
+    instructions: BTreeMap<&'static str, u64>,
+    /// The executions per subroutine stack id and address.
+    stacks: HashMap<(usize, u16), u64>,
+    /// The stack each stack id after [`MAIN_STACK`] stands for: the id of the stack the
+    /// subroutine was called from and its entry address, None for those called before
+    /// profiling started.
+    stack_ids: Vec<(usize, Option<u16>)>,
+    /// The id of each stack already seen, by the fields of `stack_ids`.
+    known_stacks: HashMap<(usize, Option<u16>), usize>,
+    /// The return address and stack id of each running subroutine, from the outermost.
+    call_path: Vec<(u16, usize)>,
+    instructions_executed: u64,
+    frames: u64,
+    /// The `FX0A` instructions which found no key pressed and ran again.
+    key_wait_instructions: u64,
+    /// The frames during which the program waited for a key.
+    key_wait_frames: u64,
+    waited_this_frame: bool,
+    draws_this_frame: u32,
+    /// How many frames made each amount of draw calls.
+    draws_per_frame: BTreeMap<u32, u64>,
+}
+
+impl Profiler {
+    pub fn new() -> Self {
+        Profiler::default()
+    }
+
+    /// Counts an executed instruction. `stack_frames` are the return addresses on the call
+    /// stack after it ran, from the outermost, and `waited` is true if it waited for a key.
+    pub fn record(
+        &mut self,
+        address: u16,
+        instruction: &ProcessorInstruction,
+        stack_frames: impl Iterator<Item = u16>,
+        waited: bool,
+    ) {
+        self.instructions_executed += 1;
+        let executions = self.addresses.entry(address).or_insert((*instruction, 0));
+        *executions = (*instruction, executions.1 + 1);
+        *self.instructions.entry(instruction.name()).or_default() += 1;
+        *self
+            .stacks
+            .entry((self.current_stack(), address))
+            .or_default() += 1;
+        self.follow_calls(instruction, stack_frames);
+
+        match instruction {
+            ProcessorInstruction::Draw { .. } => self.draws_this_frame += 1,
+            ProcessorInstruction::GetKeyBlocking { .. } if waited => {
+                self.key_wait_instructions += 1;
+                self.waited_this_frame = true;
+            }
+            _ => {}
+        }
+    }
+
+    /// Keeps the subroutines whose return addresses are still on the call stack, so calls,
+    /// returns, resets and a program rewriting the stack in memory are all followed. A call
+    /// adds its subroutine, the other new frames were called before profiling started.
+    fn follow_calls(
+        &mut self,
+        instruction: &ProcessorInstruction,
+        stack_frames: impl Iterator<Item = u16>,
+    ) {
+        let mut depth = 0;
+        let mut stack_frames = stack_frames.peekable();
+        while let Some(return_address) = stack_frames.next() {
+            if self.call_path.get(depth).map(|(known, _)| *known) != Some(return_address) {
+                self.call_path.truncate(depth);
+                let entry = match instruction {
+                    ProcessorInstruction::Call { address } if stack_frames.peek().is_none() => {
+                        Some(*address)
+                    }
+                    _ => None,
+                };
+                let stack = self.stack_id(self.current_stack(), entry);
+                self.call_path.push((return_address, stack));
+            }
+            depth += 1;
+        }
+        self.call_path.truncate(depth);
+    }
+
+    /// Returns the id of the stack of the running subroutine.
+    fn current_stack(&self) -> usize {
+        self.call_path
+            .last()
+            .map_or(MAIN_STACK, |(_, stack)| *stack)
+    }
+
+    /// Returns the id of the stack of the subroutine called from the caller's stack.
+    fn stack_id(&mut self, caller: usize, entry: Option<u16>) -> usize {
+        let next_id = self.stack_ids.len() + 1;
+        let id = *self.known_stacks.entry((caller, entry)).or_insert(next_id);
+        if id == next_id {
+            self.stack_ids.push((caller, entry));
+        }
+        id
+    }
+
+    /// Returns the entry addresses of the subroutines of the stack, from the outermost.
+    fn subroutines(&self, mut stack: usize) -> Vec<Option<u16>> {
+        let mut subroutines = Vec::new();
+        while stack != MAIN_STACK {
+            let (caller, entry) = self.stack_ids[stack - 1];
+            subroutines.push(entry);
+            stack = caller;
+        }
+        subroutines.reverse();
+        subroutines
+    }
+
+    /// Counts the end of a frame.
+    pub fn end_frame(&mut self) {
+        self.frames += 1;
+        *self
+            .draws_per_frame
+            .entry(self.draws_this_frame)
+            .or_default() += 1;
+        if self.waited_this_frame {
+            self.key_wait_frames += 1;
+        }
+        self.draws_this_frame = 0;
+        self.waited_this_frame = false;
+    }
+
+    pub fn instructions_executed(&self) -> u64 {
+        self.instructions_executed
+    }
+
+    pub fn frames(&self) -> u64 {
+        self.frames
+    }
+
+    /// Returns how often the instruction at the address was executed.
+    pub fn executions(&self, address: u16) -> u64 {
+        self.addresses.get(&address).map_or(0, |(_, count)| *count)
+    }
+
+    /// Returns the addresses executed most often, the most executed first.
+    pub fn hot_spots(&self) -> Vec<(u16, ProcessorInstruction, u64)> {
+        let mut hot_spots: Vec<_> = self
+            .addresses
+            .iter()
+            .map(|(address, (instruction, count))| (*address, *instruction, *count))
+            .collect();
+        hot_spots.sort_by(|a, b| b.2.cmp(&a.2).then(a.0.cmp(&b.0)));
+        hot_spots
+    }
+
+    /// Returns the executions of each instruction type, the most executed first.
+    pub fn instruction_counts(&self) -> Vec<(&'static str, u64)> {
+        let mut counts: Vec<_> = self
+            .instructions
+            .iter()
+            .map(|(name, count)| (*name, *count))
+            .collect();
+        counts.sort_by(|a, b| b.1.cmp(&a.1).then(a.0.cmp(b.0)));
+        counts
+    }
+
+    /// Returns the amount of frames during which the program waited for a key.
+    pub fn key_wait_frames(&self) -> u64 {
+        self.key_wait_frames
+    }
+
+    /// Returns the amount of draw calls in all frames.
+    pub fn draw_calls(&self) -> u64 {
+        self.draws_per_frame
+            .iter()
+            .map(|(draws, frames)| u64::from(*draws) * frames)
+            .sum()
+    }
+
+    fn share(&self, count: u64) -> f64 {
+        100.0 * count as f64 / self.instructions_executed.max(1) as f64
+    }
+
+    /// Writes the report of the [`HOT_SPOTS`] most executed addresses, the instruction
+    /// types, the waits for a key and the draw calls per frame.
+    pub fn write_report<W: Write>(&self, mut writer: W) -> io::Result<()> {
+        writeln!(
+            writer,
+            "{} instructions in {} frames",
+            self.instructions_executed, self.frames
+        )?;
+
+        writeln!(
+            writer,
+            "\nHot spots\n  ADDR  EXECUTIONS   SHARE  INSTRUCTION"
+        )?;
+        for (address, instruction, count) in self.hot_spots().into_iter().take(HOT_SPOTS) {
+            writeln!(
+                writer,
+                "  {address:04X}  {count:>10}  {:>5.1}%  {instruction}",
+                self.share(count)
+            )?;
+        }
+
+        writeln!(writer, "\nInstructions")?;
+        for (name, count) in self.instruction_counts() {
+            writeln!(
+                writer,
+                "  {name:<28}{count:>10}  {:>5.1}%",
+                self.share(count)
+            )?;
+        }
+
+        // fast-forwarding and a slow host make the emulated time differ from the wall clock
+        writeln!(
+            writer,
+            "\nWaiting for a key\n  {} GetKeyBlocking instructions in {} frames, {:.1} s of emulated time",
+            self.key_wait_instructions,
+            self.key_wait_frames,
+            self.key_wait_frames as f64 / FRAMES_PER_SECOND
+        )?;
+
+        let draw_calls = self.draw_calls();
+        writeln!(
+            writer,
+            "\nDraw calls\n  {draw_calls} in {} frames, {:.2} per frame, at most {}",
+            self.frames,
+            draw_calls as f64 / self.frames.max(1) as f64,
+            self.draws_per_frame
+                .keys()
+                .last()
+                .copied()
+                .unwrap_or_default()
+        )?;
+        for (draws, frames) in &self.draws_per_frame {
+            writeln!(writer, "  {draws:>3} per frame  {frames:>10} frames")?;
+        }
+        Ok(())
+    }
+
+    /// Writes a line per executed subroutine stack and address, such as
+    /// `main;sub_2A4;02B0 120`, sorted by the stacks.
+    pub fn write_folded<W: Write>(&self, mut writer: W) -> io::Result<()> {
+        let mut stacks: Vec<_> = self
+            .stacks
+            .iter()
+            .map(|((stack, address), count)| (self.subroutines(*stack), *address, *count))
+            .collect();
+        stacks.sort();
+        for (subroutines, address, count) in stacks {
+            write!(writer, "main")?;
+            for subroutine in subroutines {
+                match subroutine {
+                    Some(entry) => write!(writer, ";sub_{entry:03X}")?,
+                    None => write!(writer, ";sub_unknown")?,
+                }
+            }
+            writeln!(writer, ";{address:04X} {count}")?;
+        }
+        Ok(())
+    }
+
+    /// Writes the profile to a new file in the format.
+    pub fn save(&self, path: &Path, format: ProfileFormat) -> Result<(), anyhow::Error> {
+        let mut writer = BufWriter::new(File::create(path)?);
+        match format {
+            ProfileFormat::Report => self.write_report(&mut writer)?,
+            ProfileFormat::Folded => self.write_folded(&mut writer)?,
+        }
+        writer.flush()?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::emulator::Emulator;
+    use crate::stack::StackMode;
+
+    /// Calls a subroutine drawing a sprite, then waits for a key.
+    const PROGRAM: [u8; 10] = [
+        0x22, 0x06, // 200: CALL 206
+        0xF0, 0x0A, // 202: LD V0, K
+        0x12, 0x04, // 204: JP 204
+        0xD0, 0x15, // 206: DRW V0, V1, 5
+        0x00, 0xEE, // 208: RET
+    ];
+
+    fn profile(frames: usize) -> Profiler {
+        let mut emulator = Emulator::new();
+        emulator.load_rom(&PROGRAM[..]).expect("Failed to load");
+        emulator.set_profiler(Some(Profiler::new()));
+        for _ in 0..frames {
+            emulator.run_frame().expect("Failed to run");
+        }
+        emulator.take_profiler().expect("No profiler")
+    }
+
+    #[test]
+    fn test_profiler_counts() {
+        let profiler = profile(2);
+
+        assert_eq!(profiler.instructions_executed(), 16);
+        assert_eq!(profiler.frames(), 2);
+        assert_eq!(profiler.executions(0x200), 1);
+        assert_eq!(profiler.executions(0x202), 13);
+        assert_eq!(profiler.executions(0x204), 0);
+        assert_eq!(profiler.hot_spots()[0].0, 0x202);
+        assert_eq!(
+            profiler.instruction_counts(),
+            [
+                ("GetKeyBlocking", 13),
+                ("Call", 1),
+                ("Draw", 1),
+                ("Return", 1)
+            ]
+        );
+        assert_eq!(profiler.key_wait_frames(), 2);
+        assert_eq!(profiler.draw_calls(), 1);
+        assert_eq!(profiler.draws_per_frame, BTreeMap::from([(0, 1), (1, 1)]));
+    }
+
+    #[test]
+    fn test_profiler_folded_stacks() {
+        let mut folded = Vec::new();
+        profile(1).write_folded(&mut folded).unwrap();
+
+        assert_eq!(
+            String::from_utf8(folded).unwrap(),
+            "main;0200 1\nmain;0202 5\nmain;sub_206;0206 1\nmain;sub_206;0208 1\n"
+        );
+    }
+
+    #[test]
+    fn test_profiler_follows_a_rewritten_stack() {
+        let mut emulator = Emulator::new();
+        emulator.configure_stack(StackMode::Memory, 12).unwrap();
+        emulator.set_instructions_per_frame(8);
+        let program = [
+            0x22, 0x06, // 200: CALL 206
+            0x12, 0x02, // 202: JP 202
+            0x12, 0x04, // 204: JP 204
+            0x60, 0x02, // 206: LD V0, 02
+            0x61, 0x0E, // 208: LD V1, 0E
+            0xAE, 0xCE, // 20A: LD I, ECE, the return address in memory
+            0xF1, 0x55, // 20C: LD [I], V1 makes it 20E
+            0x12, 0x0E, // 20E: JP 20E
+        ];
+        emulator.load_rom(&program[..]).expect("Failed to load");
+        emulator.set_profiler(Some(Profiler::new()));
+        emulator.run_frame().expect("Failed to run");
+        let mut folded = Vec::new();
+
+        emulator
+            .take_profiler()
+            .unwrap()
+            .write_folded(&mut folded)
+            .unwrap();
+
+        assert_eq!(
+            String::from_utf8(folded).unwrap(),
+            "main;0200 1\nmain;sub_unknown;020E 3\n\
+             main;sub_206;0206 1\nmain;sub_206;0208 1\nmain;sub_206;020A 1\nmain;sub_206;020C 1\n"
+        );
+    }
+
+    #[test]
+    fn test_profile_format() {
+        assert_eq!(
+            "Folded".parse::<ProfileFormat>().unwrap(),
+            ProfileFormat::Folded
+        );
+        assert_eq!(
+            "report".parse::<ProfileFormat>().unwrap(),
+            ProfileFormat::Report
+        );
+        assert!("flame".parse::<ProfileFormat>().is_err());
+    }
+}
@@ -0,0 +1,284 @@
+use crate::error::Error;
+use core::fmt::{Display, Formatter};
+use core::str::FromStr;
+
+/// The behaviours which differ between CHIP-8 interpreters, named like in the community
+/// chip-8-database.
+///
+/// The defaults match this emulator's historic behaviour, which runs most modern ROMs.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Quirks {
+    /// `8XY6` and `8XYE` shift VX in place instead of shifting VY into VX.
+    pub shift: bool,
+    /// `FX55` and `FX65` increment I by X instead of X + 1.
+    pub memory_increment_by_x: bool,
+    /// `FX55` and `FX65` leave I unchanged.
+    pub memory_leave_i_unchanged: bool,
+    /// Sprites wrap around the edges of the display instead of being clipped.
+    pub wrap: bool,
+    /// `BNNN` jumps to NNN + VX instead of NNN + V0.
+    pub jump: bool,
+    /// `DXYN` waits for the vertical blank, so at most one sprite is drawn per frame.
+    pub vblank: bool,
+    /// `8XY1`, `8XY2` and `8XY3` reset VF to 0.
+    pub logic: bool,
+    /// `8XY5`, `8XY6`, `8XY7` and `8XYE` write VF before their result, like Octo's option of
+    /// the same name. The database has no such quirk, all its platforms write VF last.
+    pub vf_order: bool,
+}
+
+impl Quirks {
+    /// The name of every quirk, as accepted by [`Quirks::from_str`].
+    pub const NAMES: [&'static str; 8] = [
+        "shift",
+        "memoryIncrementByX",
+        "memoryLeaveIUnchanged",
+        "wrap",
+        "jump",
+        "vblank",
+        "logic",
+        "vfOrder",
+    ];
+
+    /// Returns the quirks with every quirk turned off.
+    pub const fn none() -> Self {
+        Quirks {
+            shift: false,
+            memory_increment_by_x: false,
+            memory_leave_i_unchanged: false,
+            wrap: false,
+            jump: false,
+            vblank: false,
+            logic: false,
+            vf_order: false,
+        }
+    }
+
+    /// Returns whether the quirk with the given name is on, see [`Quirks::NAMES`].
+    pub fn get(&self, name: &str) -> Option<bool> {
+        let mut quirks = *self;
+        quirks.get_mut(name).map(|quirk| *quirk)
+    }
+
+    /// Returns the quirk with the given name, see [`Quirks::NAMES`].
+    pub fn get_mut(&mut self, name: &str) -> Option<&mut bool> {
+        let quirk = match name {
+            "shift" => &mut self.shift,
+            "memoryIncrementByX" => &mut self.memory_increment_by_x,
+            "memoryLeaveIUnchanged" => &mut self.memory_leave_i_unchanged,
+            "wrap" => &mut self.wrap,
+            "jump" => &mut self.jump,
+            "vblank" => &mut self.vblank,
+            "logic" => &mut self.logic,
+            "vfOrder" => &mut self.vf_order,
+            _ => return None,
+        };
+        Some(quirk)
+    }
+
+    /// Returns the id of the first platform with these quirks, `default` for the emulator's
+    /// defaults, or `custom`.
+    pub fn preset_name(&self) -> &'static str {
+        if *self == Quirks::default() {
+            return "default";
+        }
+        Platform::ALL
+            .into_iter()
+            .find(|platform| platform.quirks() == *self)
+            .map_or("custom", |platform| platform.id())
+    }
+
+    /// Returns the names of the quirks which are turned on.
+    pub fn enabled(&self) -> impl Iterator<Item = &'static str> {
+        let quirks = *self;
+        Quirks::NAMES
+            .into_iter()
+            .filter(move |name| quirks.get(name) == Some(true))
+    }
+}
+
+impl Default for Quirks {
+    fn default() -> Self {
+        Quirks {
+            shift: true,
+            memory_leave_i_unchanged: true,
+            wrap: true,
+            vf_order: true,
+            ..Quirks::none()
+        }
+    }
+}
+
+/// Formats the quirks which are turned on as a comma separated list, or `none`.
+impl Display for Quirks {
+    fn fmt(&self, f: &mut Formatter<'_>) -> core::fmt::Result {
+        let mut enabled = self.enabled().peekable();
+        if enabled.peek().is_none() {
+            return f.write_str("none");
+        }
+        for (index, name) in enabled.enumerate() {
+            if index > 0 {
+                f.write_str(",")?;
+            }
+            f.write_str(name)?;
+        }
+        Ok(())
+    }
+}
+
+/// Parses a comma separated list of the quirks to turn on, or `none`.
+impl FromStr for Quirks {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut quirks = Quirks::none();
+        if s.trim() == "none" {
+            return Ok(quirks);
+        }
+        for name in s.split(',') {
+            *quirks.get_mut(name.trim()).ok_or_else(|| {
+                Error::parse(name.trim(), "none or a comma separated list of quirk names")
+            })? = true;
+        }
+        Ok(quirks)
+    }
+}
+
+/// The interpreters ROMs were written for, with the ids of the chip-8-database.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Platform {
+    /// The original interpreter of the COSMAC VIP.
+    OriginalChip8,
+    /// The COSMAC VIP interpreter extended with machine code routines.
+    HybridVip,
+    /// CHIP-8 as most modern emulators implement it.
+    ModernChip8,
+    /// CHIP-48 on the HP-48 calculators.
+    Chip48,
+    /// SUPER-CHIP 1.0.
+    Superchip1,
+    /// SUPER-CHIP 1.1.
+    Superchip,
+    /// Octo's XO-CHIP.
+    XoChip,
+}
+
+impl Platform {
+    const ALL: [Platform; 7] = [
+        Platform::OriginalChip8,
+        Platform::HybridVip,
+        Platform::ModernChip8,
+        Platform::Chip48,
+        Platform::Superchip1,
+        Platform::Superchip,
+        Platform::XoChip,
+    ];
+
+    /// Returns the id of the platform, as accepted by [`Platform::from_str`].
+    pub fn id(&self) -> &'static str {
+        match self {
+            Platform::OriginalChip8 => "originalChip8",
+            Platform::HybridVip => "hybridVIP",
+            Platform::ModernChip8 => "modernChip8",
+            Platform::Chip48 => "chip48",
+            Platform::Superchip1 => "superchip1",
+            Platform::Superchip => "superchip",
+            Platform::XoChip => "xochip",
+        }
+    }
+
+    /// Returns the quirks of the platform's interpreter.
+    pub fn quirks(&self) -> Quirks {
+        match self {
+            Platform::OriginalChip8 | Platform::HybridVip => Quirks {
+                vblank: true,
+                logic: true,
+                ..Quirks::none()
+            },
+            Platform::ModernChip8 => Quirks::none(),
+            Platform::Chip48 => Quirks {
+                shift: true,
+                memory_increment_by_x: true,
+                jump: true,
+                ..Quirks::none()
+            },
+            Platform::Superchip1 | Platform::Superchip => Quirks {
+                shift: true,
+                memory_leave_i_unchanged: true,
+                jump: true,
+                ..Quirks::none()
+            },
+            Platform::XoChip => Quirks {
+                wrap: true,
+                ..Quirks::none()
+            },
+        }
+    }
+
+    /// Returns the amount of instructions per frame ROMs for the platform usually expect.
+    pub fn tick_rate(&self) -> u32 {
+        match self {
+            Platform::OriginalChip8 | Platform::HybridVip => 15,
+            Platform::ModernChip8 => 12,
+            Platform::Chip48 | Platform::Superchip1 | Platform::Superchip => 30,
+            Platform::XoChip => 100,
+        }
+    }
+
+    /// Returns true if the emulator runs the platform's instruction set. The others extend
+    /// CHIP-8 with instructions which are skipped.
+    pub fn is_supported(&self) -> bool {
+        matches!(
+            self,
+            Platform::OriginalChip8 | Platform::HybridVip | Platform::ModernChip8
+        )
+    }
+}
+
+impl FromStr for Platform {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Platform::ALL
+            .into_iter()
+            .find(|platform| platform.id().eq_ignore_ascii_case(s))
+            .ok_or_else(|| Error::parse(s, "a chip-8-database platform id such as originalChip8"))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    extern crate std;
+    use std::string::ToString;
+
+    #[test]
+    fn test_quirks_round_trip() {
+        let quirks = Platform::Chip48.quirks();
+
+        assert_eq!(quirks.to_string(), "shift,memoryIncrementByX,jump");
+        assert_eq!(quirks.to_string().parse(), Ok(quirks));
+        assert_eq!("none".parse(), Ok(Quirks::none()));
+        assert!("shift,turbo".parse::<Quirks>().is_err());
+    }
+
+    #[test]
+    fn test_quirks_preset_name() {
+        assert_eq!(Quirks::default().preset_name(), "default");
+        assert_eq!(Platform::HybridVip.quirks().preset_name(), "originalChip8");
+        assert_eq!(Quirks::none().preset_name(), "modernChip8");
+        let quirks = Quirks {
+            jump: true,
+            ..Quirks::none()
+        };
+        assert_eq!(quirks.preset_name(), "custom");
+    }
+
+    #[test]
+    fn test_platform_from_str() {
+        assert_eq!("hybridvip".parse(), Ok(Platform::HybridVip));
+        assert!(Platform::ModernChip8.is_supported());
+        assert!(!"xochip".parse::<Platform>().unwrap().is_supported());
+        assert!("megachip8".parse::<Platform>().is_err());
+    }
+}
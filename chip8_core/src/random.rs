@@ -0,0 +1,240 @@
+use crate::error::Error;
+use core::str::FromStr;
+
+/// A source of random numbers for the `CXNN` instruction.
+pub trait Rng {
+    /// Returns the next random byte, any value from 0x00 to 0xFF.
+    fn next_byte(&mut self) -> u8;
+
+    /// Starts the numbers over from the seed, so a hard reset replays the same numbers.
+    /// Generators without a seed keep going.
+    fn restart(&mut self) {}
+}
+
+/// The xorshift64* generator, fast and small enough for microcontrollers.
+#[derive(Debug, Clone)]
+pub struct XorShift {
+    state: u64,
+}
+
+impl XorShift {
+    /// Creates a new generator. Xorshift gets stuck on a zero state, so the seed is mixed first.
+    pub fn new(seed: u64) -> Self {
+        // the splitmix64 finalizer maps 0 to 0, so offset the seed first
+        let mut state = seed.wrapping_add(0x9E37_79B9_7F4A_7C15);
+        state = (state ^ (state >> 30)).wrapping_mul(0xBF58_476D_1CE4_E5B9);
+        state = (state ^ (state >> 27)).wrapping_mul(0x94D0_49BB_1331_11EB);
+        state ^= state >> 31;
+        XorShift {
+            state: if state == 0 { 1 } else { state },
+        }
+    }
+
+    /// Returns the next random number.
+    pub fn next_u64(&mut self) -> u64 {
+        self.state ^= self.state >> 12;
+        self.state ^= self.state << 25;
+        self.state ^= self.state >> 27;
+        self.state.wrapping_mul(0x2545_F491_4F6C_DD1D)
+    }
+}
+
+impl Rng for XorShift {
+    fn next_byte(&mut self) -> u8 {
+        (self.next_u64() >> 56) as u8
+    }
+}
+
+/// The random routine of the COSMAC VIP interpreter.
+///
+/// The VIP adds the byte at a walking pointer in a page of its own interpreter code to the
+/// previous random number. The interpreter's code is not bundled, so the page is filled from
+/// the seed instead: the numbers keep the VIP's character, such as the strong correlation of
+/// successive numbers, without being the exact sequence of the original machine.
+#[derive(Debug, Clone)]
+pub struct VipRandom {
+    page: [u8; 256],
+    pointer: u8,
+    number: u8,
+}
+
+impl VipRandom {
+    /// Creates a new VIP random routine with the page filled from the seed.
+    pub fn new(seed: u64) -> Self {
+        let mut xorshift = XorShift::new(seed);
+        let mut page = [0; 256];
+        page.iter_mut()
+            .for_each(|byte| *byte = xorshift.next_byte());
+        VipRandom::with_page(page)
+    }
+
+    /// Creates a new VIP random routine reading the given page, for example a dump of the
+    /// VIP interpreter's code.
+    pub fn with_page(page: [u8; 256]) -> Self {
+        VipRandom {
+            page,
+            pointer: 0,
+            number: 0,
+        }
+    }
+}
+
+impl Rng for VipRandom {
+    fn next_byte(&mut self) -> u8 {
+        self.pointer = self.pointer.wrapping_add(1);
+        self.number = self.number.wrapping_add(self.page[self.pointer as usize]);
+        self.number
+    }
+}
+
+/// Selects the random number generator of the emulator.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum RandomMode {
+    /// A xorshift generator with uniformly distributed numbers.
+    #[default]
+    XorShift,
+    /// The random routine of the COSMAC VIP interpreter, see [`VipRandom`].
+    Vip,
+}
+
+impl RandomMode {
+    /// Returns the name of the mode, as accepted by [`RandomMode::from_str`].
+    pub fn name(&self) -> &'static str {
+        match self {
+            RandomMode::XorShift => "xorshift",
+            RandomMode::Vip => "vip",
+        }
+    }
+}
+
+impl FromStr for RandomMode {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        if s.eq_ignore_ascii_case("xorshift") {
+            Ok(RandomMode::XorShift)
+        } else if s.eq_ignore_ascii_case("vip") {
+            Ok(RandomMode::Vip)
+        } else {
+            Err(Error::parse(s, "xorshift or vip"))
+        }
+    }
+}
+
+/// The default random number generator of the emulator, a seeded generator of the chosen mode.
+#[derive(Debug, Clone)]
+pub struct RandomSource {
+    mode: RandomMode,
+    seed: u64,
+    generator: Generator,
+}
+
+// boxing the VIP page would need an allocator, and it is small next to the emulator's memory
+#[allow(clippy::large_enum_variant)]
+#[derive(Debug, Clone)]
+enum Generator {
+    XorShift(XorShift),
+    Vip(VipRandom),
+}
+
+impl RandomSource {
+    /// Creates a new random number generator.
+    pub fn new(mode: RandomMode, seed: u64) -> Self {
+        let generator = match mode {
+            RandomMode::XorShift => Generator::XorShift(XorShift::new(seed)),
+            RandomMode::Vip => Generator::Vip(VipRandom::new(seed)),
+        };
+        RandomSource {
+            mode,
+            seed,
+            generator,
+        }
+    }
+
+    /// Creates a new xorshift generator seeded from the operating system's randomness.
+    #[cfg(feature = "std")]
+    pub fn from_entropy() -> Self {
+        use std::hash::{BuildHasher, Hasher};
+
+        let seed = std::collections::hash_map::RandomState::new()
+            .build_hasher()
+            .finish();
+        RandomSource::new(RandomMode::XorShift, seed)
+    }
+
+    /// Returns the mode of the generator.
+    pub fn mode(&self) -> RandomMode {
+        self.mode
+    }
+
+    /// Returns the seed the generator was created with.
+    pub fn seed(&self) -> u64 {
+        self.seed
+    }
+}
+
+impl Rng for RandomSource {
+    fn next_byte(&mut self) -> u8 {
+        match &mut self.generator {
+            Generator::XorShift(xorshift) => xorshift.next_byte(),
+            Generator::Vip(vip) => vip.next_byte(),
+        }
+    }
+
+    fn restart(&mut self) {
+        *self = RandomSource::new(self.mode, self.seed);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_xorshift_is_seeded() {
+        let mut first = XorShift::new(7);
+        let mut second = XorShift::new(7);
+        let mut other = XorShift::new(8);
+
+        let numbers: [u64; 8] = core::array::from_fn(|_| first.next_u64());
+
+        assert_eq!(numbers, core::array::from_fn(|_| second.next_u64()));
+        assert_ne!(numbers, core::array::from_fn(|_| other.next_u64()));
+    }
+
+    #[test]
+    fn test_xorshift_covers_every_byte() {
+        let mut xorshift = XorShift::new(0);
+        let mut seen = [false; 256];
+
+        for _ in 0..10_000 {
+            seen[xorshift.next_byte() as usize] = true;
+        }
+
+        assert!(seen.iter().all(|seen| *seen));
+    }
+
+    #[test]
+    fn test_vip_random_adds_page_bytes() {
+        let mut page = [0; 256];
+        page[1] = 0x10;
+        page[2] = 0xF5;
+        let mut vip = VipRandom::with_page(page);
+
+        assert_eq!(vip.next_byte(), 0x10);
+        assert_eq!(vip.next_byte(), 0x05);
+        assert_eq!(vip.next_byte(), 0x05);
+    }
+
+    #[test]
+    fn test_random_source() {
+        let mut source = RandomSource::new(RandomMode::Vip, 3);
+        let mut vip = VipRandom::new(3);
+
+        assert_eq!(source.next_byte(), vip.next_byte());
+        assert_eq!(source.mode(), RandomMode::Vip);
+        assert_eq!(source.seed(), 3);
+        assert_eq!("VIP".parse::<RandomMode>().unwrap(), RandomMode::Vip);
+        assert!("dice".parse::<RandomMode>().is_err());
+    }
+}
@@ -1,12 +1,44 @@
+#[cfg(feature = "archive")]
+use crate::emulator::MAX_ROM_SIZE;
+use crate::error::Error;
+#[cfg(feature = "archive")]
+use crate::error::ErrorText;
+#[cfg(feature = "std")]
+use crate::rom::RomSettings;
+
 #[cfg(feature = "std")]
 use std::fs::File;
 
+#[cfg(feature = "archive")]
+use core::fmt::Write;
+
 #[cfg(feature = "std")]
 use std::io::Read;
 
+#[cfg(feature = "std")]
+use std::path::Path;
+
 pub trait Reader {
-    /// Reads the bytes into the buffer and returns the amount read.
-    fn read(&mut self, buf: &mut [u8]) -> Result<usize, anyhow::Error>;
+    /// Reads the bytes into the buffer and returns the amount read, 0 once the ROM is over.
+    fn read(&mut self, buf: &mut [u8]) -> Result<usize, Error>;
+}
+
+/// Reads a ROM from memory, for example one embedded with `include_bytes!`.
+impl Reader for &[u8] {
+    fn read(&mut self, buf: &mut [u8]) -> Result<usize, Error> {
+        let amount = self.len().min(buf.len());
+        let (read, rest) = self.split_at(amount);
+        buf[..amount].copy_from_slice(read);
+        *self = rest;
+        Ok(amount)
+    }
+}
+
+#[cfg(feature = "std")]
+impl<T: Reader + ?Sized> Reader for Box<T> {
+    fn read(&mut self, buf: &mut [u8]) -> Result<usize, Error> {
+        (**self).read(buf)
+    }
 }
 
 #[cfg(feature = "std")]
@@ -23,8 +55,277 @@ impl StdFileReader {
 
 #[cfg(feature = "std")]
 impl Reader for StdFileReader {
-    fn read(&mut self, buf: &mut [u8]) -> Result<usize, anyhow::Error> {
+    fn read(&mut self, buf: &mut [u8]) -> Result<usize, Error> {
         let amount = self.file.read(buf)?;
         Ok(amount)
     }
 }
+
+/// Reads a ROM from any std reader, such as stdin or a decompressor.
+#[cfg(feature = "std")]
+pub struct StdReader<R: Read> {
+    reader: R,
+}
+
+#[cfg(feature = "std")]
+impl<R: Read> StdReader<R> {
+    pub fn new(reader: R) -> Self {
+        StdReader { reader }
+    }
+}
+
+#[cfg(feature = "std")]
+impl StdReader<std::io::Stdin> {
+    /// Reads the ROM piped into the standard input.
+    pub fn stdin() -> Self {
+        StdReader::new(std::io::stdin())
+    }
+}
+
+#[cfg(feature = "std")]
+impl<R: Read> Reader for StdReader<R> {
+    fn read(&mut self, buf: &mut [u8]) -> Result<usize, Error> {
+        let amount = self.reader.read(buf)?;
+        Ok(amount)
+    }
+}
+
+/// The extensions of raw ROMs.
+#[cfg(feature = "std")]
+const ROM_EXTENSIONS: [&str; 3] = ["ch8", "sc8", "xo8"];
+
+/// Returns the lowercase extension of the file name.
+#[cfg(feature = "std")]
+fn extension(name: &Path) -> Option<String> {
+    name.extension()
+        .and_then(|extension| extension.to_str())
+        .map(|extension| extension.to_ascii_lowercase())
+}
+
+/// Returns true if the file name has one of the ROM extensions.
+#[cfg(feature = "archive")]
+fn is_rom_file_name(name: &str) -> bool {
+    extension(Path::new(name)).is_some_and(|extension| ROM_EXTENSIONS.contains(&extension.as_str()))
+}
+
+/// Returns true if the file has the extension of a raw ROM or of a file [`open_rom`] extracts
+/// a ROM from.
+#[cfg(feature = "std")]
+pub fn is_rom_path(path: &Path) -> bool {
+    extension(path).is_some_and(|extension| {
+        ROM_EXTENSIONS.contains(&extension.as_str())
+            || (cfg!(feature = "archive") && ["zip", "gz"].contains(&extension.as_str()))
+            || (cfg!(feature = "cartridge") && extension == "gif")
+    })
+}
+
+/// Reads a ROM extracted into memory.
+#[cfg(feature = "std")]
+pub type ExtractedReader = StdReader<std::io::Cursor<Vec<u8>>>;
+
+/// Opens the single `.ch8`, `.sc8` or `.xo8` ROM inside a zip archive and returns it with the
+/// settings of its extension.
+#[cfg(feature = "archive")]
+pub fn open_zip<R: Read + std::io::Seek>(
+    archive: R,
+) -> Result<(ExtractedReader, RomSettings), Error> {
+    let invalid = |error: zip::result::ZipError| {
+        let mut source = ErrorText::default();
+        let _ = write!(source, "{error}");
+        Error::Archive {
+            message: "invalid zip archive",
+            source: Some(source),
+        }
+    };
+    let mut archive = zip::ZipArchive::new(archive).map_err(invalid)?;
+    let name = {
+        let mut roms = archive.file_names().filter(|name| is_rom_file_name(name));
+        (roms.next().map(str::to_owned), roms.next().is_some())
+    };
+    let name = match name {
+        (Some(name), false) => name,
+        (None, _) => return Err(Error::archive("the zip archive contains no ROM")),
+        (Some(_), true) => {
+            return Err(Error::archive("the zip archive contains more than one ROM"))
+        }
+    };
+    // one byte more than fits, so loading reports the ROM as too large
+    let mut data = Vec::new();
+    archive
+        .by_name(&name)
+        .map_err(invalid)?
+        .take(MAX_ROM_SIZE as u64 + 1)
+        .read_to_end(&mut data)?;
+    let settings = RomSettings::for_extension(&extension(Path::new(&name)).unwrap_or_default());
+    Ok((StdReader::new(std::io::Cursor::new(data)), settings))
+}
+
+/// Opens a gzip compressed ROM.
+#[cfg(feature = "archive")]
+pub fn open_gzip<R: Read>(compressed: R) -> StdReader<flate2::read::GzDecoder<R>> {
+    StdReader::new(flate2::read::GzDecoder::new(compressed))
+}
+
+/// A ROM file opened by [`open_rom`].
+#[cfg(feature = "std")]
+pub struct RomFile {
+    pub reader: Box<dyn Reader>,
+    pub settings: RomSettings,
+}
+
+#[cfg(feature = "std")]
+impl RomFile {
+    fn new(reader: impl Reader + 'static, settings: RomSettings) -> Self {
+        RomFile {
+            reader: Box::new(reader),
+            settings,
+        }
+    }
+}
+
+/// Opens the ROM at the path: `-` reads stdin, `.zip` and `.gz` files are extracted, `.gif`
+/// files are assembled from Octo cartridges and anything else is read as a raw ROM.
+///
+/// The `.sc8` and `.xo8` extensions name the platform of raw ROMs, inside archives too.
+#[cfg(feature = "std")]
+pub fn open_rom(path: &Path) -> Result<RomFile, anyhow::Error> {
+    if path.as_os_str() == "-" {
+        return Ok(RomFile::new(StdReader::stdin(), RomSettings::default()));
+    }
+    let file = File::open(path)?;
+    match extension(path).as_deref() {
+        #[cfg(feature = "archive")]
+        Some("zip") => {
+            let (reader, settings) = open_zip(file)?;
+            Ok(RomFile::new(reader, settings))
+        }
+        #[cfg(feature = "archive")]
+        Some("gz") => {
+            // the extension of the compressed ROM, as in pong.sc8.gz
+            let stem = extension(Path::new(path.file_stem().unwrap_or_default()));
+            let settings = RomSettings::for_extension(&stem.unwrap_or_default());
+            Ok(RomFile::new(open_gzip(file), settings))
+        }
+        #[cfg(feature = "cartridge")]
+        Some("gif") => {
+            let cartridge = crate::cartridge::Cartridge::read(std::io::BufReader::new(file))?;
+            let reader = StdReader::new(std::io::Cursor::new(cartridge.rom));
+            Ok(RomFile::new(reader, cartridge.settings))
+        }
+        extension => Ok(RomFile::new(
+            StdFileReader::new(file),
+            RomSettings::for_extension(extension.unwrap_or_default()),
+        )),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    #[cfg(feature = "archive")]
+    use crate::quirks::Platform;
+
+    #[test]
+    fn test_slice_reader() {
+        let mut rom: &[u8] = &[1, 2, 3];
+        let mut buf = [0; 2];
+
+        assert_eq!(Reader::read(&mut rom, &mut buf), Ok(2));
+        assert_eq!(buf, [1, 2]);
+        assert_eq!(Reader::read(&mut rom, &mut buf), Ok(1));
+        assert_eq!(buf[0], 3);
+        assert_eq!(Reader::read(&mut rom, &mut buf), Ok(0));
+    }
+
+    #[cfg(feature = "std")]
+    #[test]
+    fn test_is_rom_path() {
+        assert!(is_rom_path(Path::new("roms/PONG.CH8")));
+        assert!(is_rom_path(Path::new("pong.sc8.gz")));
+        assert!(!is_rom_path(Path::new("roms/pong.palette")));
+        assert!(!is_rom_path(Path::new("README")));
+    }
+
+    #[cfg(feature = "archive")]
+    fn zip_archive(files: &[&str]) -> std::io::Cursor<Vec<u8>> {
+        use std::io::Write;
+
+        let mut writer = zip::ZipWriter::new(std::io::Cursor::new(Vec::new()));
+        for name in files {
+            writer
+                .start_file(*name, zip::write::SimpleFileOptions::default())
+                .unwrap();
+            writer.write_all(name.as_bytes()).unwrap();
+        }
+        let mut archive = writer.finish().unwrap();
+        archive.set_position(0);
+        archive
+    }
+
+    #[cfg(feature = "archive")]
+    #[test]
+    fn test_open_zip() {
+        let (mut reader, settings) =
+            open_zip(zip_archive(&["README.txt", "games/PONG.SC8"])).unwrap();
+        let mut buf = [0; 32];
+
+        let amount = reader.read(&mut buf).unwrap();
+
+        assert_eq!(&buf[..amount], b"games/PONG.SC8");
+        assert_eq!(settings.platform, Some(Platform::Superchip));
+        assert!(open_zip(zip_archive(&["README.txt"])).is_err());
+        assert!(open_zip(zip_archive(&["a.ch8", "b.xo8"])).is_err());
+    }
+
+    #[cfg(feature = "archive")]
+    #[test]
+    fn test_open_zip_caps_the_rom() {
+        use std::io::Write;
+
+        let mut writer = zip::ZipWriter::new(std::io::Cursor::new(Vec::new()));
+        writer
+            .start_file("huge.ch8", zip::write::SimpleFileOptions::default())
+            .unwrap();
+        writer.write_all(&vec![0; 1 << 20]).unwrap();
+        let mut archive = writer.finish().unwrap();
+        archive.set_position(0);
+        let (mut reader, _) = open_zip(archive).unwrap();
+        let mut data = Vec::new();
+        let mut buf = [0; 4096];
+
+        loop {
+            match reader.read(&mut buf).unwrap() {
+                0 => break,
+                amount => data.extend_from_slice(&buf[..amount]),
+            }
+        }
+
+        assert_eq!(data.len(), MAX_ROM_SIZE + 1);
+        let error = open_zip(std::io::Cursor::new(b"not a zip".to_vec()))
+            .err()
+            .unwrap();
+        assert!(matches!(
+            error,
+            Error::Archive {
+                source: Some(_),
+                ..
+            }
+        ));
+    }
+
+    #[cfg(feature = "archive")]
+    #[test]
+    fn test_open_gzip() {
+        use flate2::write::GzEncoder;
+        use std::io::Write;
+
+        let mut encoder = GzEncoder::new(Vec::new(), flate2::Compression::default());
+        encoder.write_all(&[0x00, 0xE0]).unwrap();
+        let compressed = encoder.finish().unwrap();
+        let mut buf = [0; 4];
+
+        let amount = open_gzip(compressed.as_slice()).read(&mut buf).unwrap();
+
+        assert_eq!(&buf[..amount], [0x00, 0xE0]);
+    }
+}
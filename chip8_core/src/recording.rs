@@ -0,0 +1,335 @@
+use crate::audio::FRAMES_PER_SECOND;
+use crate::display::{Framebuffer, DISPLAY_HEIGHT, DISPLAY_WIDTH};
+use crate::palette::Palette;
+use crate::screenshot::{scaled_rgb, timestamped_path};
+use anyhow::anyhow;
+use log::info;
+use std::fs::File;
+use std::io::{BufWriter, Write};
+use std::path::Path;
+use std::str::FromStr;
+
+/// The animated image formats a recording can be saved in.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum AnimationFormat {
+    /// Animated GIF, frame delays are rounded to hundredths of a second.
+    Gif,
+    /// Animated PNG, frame delays are exact multiples of 1/60 of a second.
+    Apng,
+}
+
+impl AnimationFormat {
+    /// Returns the file extension of the format.
+    pub fn extension(&self) -> &'static str {
+        match self {
+            AnimationFormat::Gif => "gif",
+            AnimationFormat::Apng => "png",
+        }
+    }
+
+    /// Returns the format matching the extension of the path, `.png` and `.apng` being APNG.
+    pub fn from_path(path: &Path) -> Option<AnimationFormat> {
+        path.extension()?.to_str()?.parse().ok()
+    }
+}
+
+impl FromStr for AnimationFormat {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_ascii_lowercase().as_str() {
+            "gif" => Ok(AnimationFormat::Gif),
+            "apng" | "png" => Ok(AnimationFormat::Apng),
+            _ => Err(anyhow!(
+                "unknown animation format {s}, expected gif or apng"
+            )),
+        }
+    }
+}
+
+/// A distinct frame of the recording.
+struct RecordedFrame {
+    framebuffer: Framebuffer,
+    /// How many emulated 60 Hz frames this frame stays on screen.
+    duration: u32,
+}
+
+/// Records the emulated frames into an animated GIF or APNG.
+///
+/// Frames are captured once per emulated 60 Hz frame, so the timing of the animation follows
+/// the emulation rather than the wall-clock. Consecutive identical frames are merged into one
+/// longer frame. The frames are kept packed in memory and encoded when the recording is saved.
+pub struct AnimationRecorder {
+    format: AnimationFormat,
+    scale: usize,
+    palette: Palette,
+    frames: Vec<RecordedFrame>,
+}
+
+impl AnimationRecorder {
+    /// Creates a new recorder, each Chip8 pixel becoming a `scale` x `scale` square.
+    pub fn new(format: AnimationFormat, scale: usize, palette: Palette) -> Self {
+        AnimationRecorder {
+            format,
+            scale: scale.max(1),
+            palette,
+            frames: Vec::new(),
+        }
+    }
+
+    /// Returns the format of the recording.
+    pub fn format(&self) -> AnimationFormat {
+        self.format
+    }
+
+    /// Records one emulated frame.
+    pub fn record_frame(&mut self, framebuffer: &Framebuffer) {
+        match self.frames.last_mut() {
+            Some(last) if last.framebuffer == *framebuffer => last.duration += 1,
+            _ => self.frames.push(RecordedFrame {
+                framebuffer: *framebuffer,
+                duration: 1,
+            }),
+        }
+    }
+
+    /// Returns the amount of distinct frames recorded.
+    pub fn frame_count(&self) -> usize {
+        self.frames.len()
+    }
+
+    /// Returns the amount of emulated frames recorded.
+    pub fn emulated_frame_count(&self) -> u64 {
+        self.frames.iter().map(|frame| frame.duration as u64).sum()
+    }
+
+    /// Encodes the recording into the writer.
+    pub fn write<W: Write>(&self, writer: W) -> Result<(), anyhow::Error> {
+        if self.frames.is_empty() {
+            return Err(anyhow!("the recording has no frames"));
+        }
+        match self.format {
+            AnimationFormat::Gif => self.write_gif(writer),
+            AnimationFormat::Apng => self.write_apng(writer),
+        }
+    }
+
+    /// Saves the recording to a file.
+    pub fn save(&self, path: &Path) -> Result<(), anyhow::Error> {
+        let mut writer = BufWriter::new(File::create(path)?);
+        self.write(&mut writer)?;
+        writer.flush()?;
+        Ok(())
+    }
+
+    fn width(&self) -> usize {
+        DISPLAY_WIDTH * self.scale
+    }
+
+    fn height(&self) -> usize {
+        DISPLAY_HEIGHT * self.scale
+    }
+
+    fn write_gif<W: Write>(&self, writer: W) -> Result<(), anyhow::Error> {
+        let background = self.palette.background;
+        let pixel = self.palette.pixel;
+        let global_palette = [
+            background.r,
+            background.g,
+            background.b,
+            pixel.r,
+            pixel.g,
+            pixel.b,
+        ];
+        let mut encoder = gif::Encoder::new(
+            writer,
+            self.width() as u16,
+            self.height() as u16,
+            &global_palette,
+        )?;
+        encoder.set_repeat(gif::Repeat::Infinite)?;
+
+        // GIF delays are in hundredths of a second, so round the end time of each frame
+        // instead of each delay to keep the animation from drifting.
+        let mut start = 0u64;
+        for recorded in &self.frames {
+            let end = start + recorded.duration as u64;
+            let delay = Self::centiseconds(end) - Self::centiseconds(start);
+            start = end;
+
+            let indices = self.scaled_indices(&recorded.framebuffer);
+            let mut frame = gif::Frame::from_indexed_pixels(
+                self.width() as u16,
+                self.height() as u16,
+                indices,
+                None,
+            );
+            frame.delay = delay.min(u16::MAX as u64) as u16;
+            encoder.write_frame(&frame)?;
+        }
+        Ok(())
+    }
+
+    fn write_apng<W: Write>(&self, writer: W) -> Result<(), anyhow::Error> {
+        let mut encoder = png::Encoder::new(writer, self.width() as u32, self.height() as u32);
+        encoder.set_color(png::ColorType::Rgb);
+        encoder.set_depth(png::BitDepth::Eight);
+        encoder.set_animated(self.frames.len() as u32, 0)?;
+        let mut png_writer = encoder.write_header()?;
+        for recorded in &self.frames {
+            let duration = recorded.duration.min(u16::MAX as u32) as u16;
+            png_writer.set_frame_delay(duration, FRAMES_PER_SECOND as u16)?;
+            png_writer.write_image_data(&scaled_rgb(
+                &recorded.framebuffer,
+                self.scale,
+                &self.palette,
+            ))?;
+        }
+        png_writer.finish()?;
+        Ok(())
+    }
+
+    /// Returns the palette indices of the scaled frame, 1 for lit pixels and 0 otherwise.
+    fn scaled_indices(&self, framebuffer: &Framebuffer) -> Vec<u8> {
+        let mut indices = Vec::with_capacity(self.width() * self.height());
+        for y in 0..self.height() {
+            for x in 0..self.width() {
+                indices.push(framebuffer.get(x / self.scale, y / self.scale) as u8);
+            }
+        }
+        indices
+    }
+
+    /// Converts a number of emulated frames to hundredths of a second, rounded.
+    fn centiseconds(frames: u64) -> u64 {
+        let fps = FRAMES_PER_SECOND as u64;
+        (frames * 100 + fps / 2) / fps
+    }
+}
+
+/// Starts a recording, or stops the running one and saves it to a file named after the current
+/// time in the directory, and returns what happened. A recording which cannot be saved is
+/// dropped.
+pub fn toggle_recording(
+    recorder: &mut Option<AnimationRecorder>,
+    directory: &Path,
+    format: AnimationFormat,
+    scale: usize,
+    palette: Palette,
+) -> Result<String, anyhow::Error> {
+    let message = match recorder.take() {
+        Some(recorder) => {
+            let path = timestamped_path(directory, recorder.format().extension());
+            recorder.save(&path)?;
+            format!("Saved recording to {}", path.display())
+        }
+        None => {
+            *recorder = Some(AnimationRecorder::new(format, scale, palette));
+            "Started recording".to_string()
+        }
+    };
+    info!("{message}");
+    Ok(message)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn recorder(format: AnimationFormat) -> AnimationRecorder {
+        let mut recorder = AnimationRecorder::new(format, 2, Palette::default());
+        let mut framebuffer = Framebuffer::new();
+        recorder.record_frame(&framebuffer);
+        recorder.record_frame(&framebuffer);
+        framebuffer.set(1, 1, true);
+        recorder.record_frame(&framebuffer);
+        recorder
+    }
+
+    #[test]
+    fn test_recorder_merges_identical_frames() {
+        let recorder = recorder(AnimationFormat::Gif);
+
+        assert_eq!(recorder.frame_count(), 2);
+        assert_eq!(recorder.emulated_frame_count(), 3);
+    }
+
+    #[test]
+    fn test_centiseconds_do_not_drift() {
+        let total: u64 = (0..60)
+            .map(|frame| {
+                AnimationRecorder::centiseconds(frame + 1) - AnimationRecorder::centiseconds(frame)
+            })
+            .sum();
+
+        assert_eq!(AnimationRecorder::centiseconds(1), 2);
+        assert_eq!(total, 100);
+    }
+
+    #[test]
+    fn test_write_gif() {
+        let mut data = Vec::new();
+
+        recorder(AnimationFormat::Gif).write(&mut data).unwrap();
+
+        assert_eq!(&data[0..6], b"GIF89a");
+    }
+
+    #[test]
+    fn test_write_apng() {
+        let mut data = Vec::new();
+
+        recorder(AnimationFormat::Apng).write(&mut data).unwrap();
+
+        assert_eq!(&data[1..4], b"PNG");
+        assert!(data.windows(4).any(|chunk| chunk == b"acTL"));
+    }
+
+    #[test]
+    fn test_write_empty_recording_fails() {
+        let recorder = AnimationRecorder::new(AnimationFormat::Gif, 1, Palette::default());
+
+        assert!(recorder.write(Vec::new()).is_err());
+    }
+
+    #[test]
+    fn test_animation_format_from_path() {
+        assert_eq!(
+            AnimationFormat::from_path(Path::new("run.gif")),
+            Some(AnimationFormat::Gif)
+        );
+        assert_eq!(
+            AnimationFormat::from_path(Path::new("run.apng")),
+            Some(AnimationFormat::Apng)
+        );
+        assert_eq!(AnimationFormat::from_path(Path::new("run.mp4")), None);
+    }
+
+    #[test]
+    fn test_toggle_recording() {
+        let directory = std::env::temp_dir().join("chip8_toggle_recording");
+        std::fs::create_dir_all(&directory).unwrap();
+        let mut recorder = None;
+        let toggle = |recorder: &mut Option<AnimationRecorder>| {
+            toggle_recording(
+                recorder,
+                &directory,
+                AnimationFormat::Gif,
+                1,
+                Palette::default(),
+            )
+        };
+
+        assert_eq!(toggle(&mut recorder).unwrap(), "Started recording");
+        recorder.as_mut().unwrap().record_frame(&Framebuffer::new());
+        let message = toggle(&mut recorder).unwrap();
+        assert!(recorder.is_none());
+        let path = message.strip_prefix("Saved recording to ").unwrap();
+        assert!(Path::new(path).is_file());
+
+        // an empty recording cannot be saved
+        toggle(&mut recorder).unwrap();
+        assert!(toggle(&mut recorder).is_err());
+        assert!(recorder.is_none());
+    }
+}
@@ -0,0 +1,126 @@
+use crate::bus::Bus;
+use crate::emulator::Emulator;
+use crate::palette::Palette;
+use crate::quirks::{Platform, Quirks};
+use crate::random::Rng;
+use log::warn;
+
+/// The keypad keys a ROM uses for its directions and buttons, for frontends to map the
+/// arrow keys and the action keys to.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct KeyHints {
+    pub up: Option<u8>,
+    pub down: Option<u8>,
+    pub left: Option<u8>,
+    pub right: Option<u8>,
+    pub a: Option<u8>,
+    pub b: Option<u8>,
+}
+
+/// The settings a ROM file carries besides the program: the platform its extension names, or
+/// the options embedded in an Octo cartridge.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct RomSettings {
+    pub platform: Option<Platform>,
+    pub quirks: Option<Quirks>,
+    /// The amount of instructions per frame.
+    pub tick_rate: Option<u32>,
+    pub palette: Option<Palette>,
+    pub keys: Option<KeyHints>,
+}
+
+impl RomSettings {
+    /// Returns the settings of a raw ROM with the extension: `.sc8` names SUPER-CHIP and
+    /// `.xo8` XO-CHIP, anything else nothing.
+    pub fn for_extension(extension: &str) -> Self {
+        let platform = if extension.eq_ignore_ascii_case("sc8") {
+            Some(Platform::Superchip)
+        } else if extension.eq_ignore_ascii_case("xo8") {
+            Some(Platform::XoChip)
+        } else {
+            None
+        };
+        RomSettings {
+            platform,
+            ..RomSettings::default()
+        }
+    }
+
+    /// Returns these settings, with those of `fallback` filling the ones they do not have.
+    pub fn or(self, fallback: RomSettings) -> RomSettings {
+        RomSettings {
+            platform: self.platform.or(fallback.platform),
+            quirks: self.quirks.or(fallback.quirks),
+            tick_rate: self.tick_rate.or(fallback.tick_rate),
+            palette: self.palette.or(fallback.palette),
+            keys: self.keys.or(fallback.keys),
+        }
+    }
+
+    /// Configures the emulator with the quirks and tick rate, falling back to the platform's.
+    pub fn apply<R: Rng, B: Bus>(&self, emulator: &mut Emulator<R, B>) {
+        if let Some(platform) = self.platform.filter(|platform| !platform.is_supported()) {
+            warn!(
+                "The ROM is written for {}, its extended instructions are not supported",
+                platform.id()
+            );
+        }
+        if let Some(quirks) = self.quirks.or(self.platform.map(|platform| platform.quirks())) {
+            emulator.set_quirks(quirks);
+        }
+        let platform_tick_rate = self.platform.map(|platform| platform.tick_rate());
+        if let Some(tick_rate) = self.tick_rate.or(platform_tick_rate) {
+            emulator.set_instructions_per_frame(tick_rate);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_rom_settings_for_extension() {
+        let mut emulator = Emulator::new();
+
+        RomSettings::for_extension("SC8").apply(&mut emulator);
+
+        assert_eq!(emulator.quirks(), Platform::Superchip.quirks());
+        assert_eq!(emulator.instructions_per_frame(), 30);
+        assert_eq!(
+            RomSettings::for_extension("xo8").platform,
+            Some(Platform::XoChip)
+        );
+        assert_eq!(RomSettings::for_extension("ch8"), RomSettings::default());
+    }
+
+    #[test]
+    fn test_rom_settings_or() {
+        let cartridge = RomSettings {
+            quirks: Some(Quirks::none()),
+            palette: Some(Palette::LCD),
+            keys: Some(KeyHints {
+                up: Some(0x5),
+                ..KeyHints::default()
+            }),
+            ..RomSettings::default()
+        };
+        let database = RomSettings {
+            platform: Some(Platform::OriginalChip8),
+            quirks: Some(Platform::OriginalChip8.quirks()),
+            tick_rate: Some(15),
+            palette: Some(Palette::OCTO),
+            keys: Some(KeyHints::default()),
+        };
+
+        assert_eq!(
+            cartridge.or(database),
+            RomSettings {
+                platform: Some(Platform::OriginalChip8),
+                tick_rate: Some(15),
+                ..cartridge
+            }
+        );
+        assert_eq!(RomSettings::default().or(database), database);
+    }
+}
@@ -0,0 +1,280 @@
+use crate::bus::Bus;
+use crate::emulator::Emulator;
+use crate::error::Error;
+use crate::random::Rng;
+use log::info;
+
+/// The default amount of frames a fast-forwarding emulator runs per 60 Hz tick.
+pub const DEFAULT_FAST_FORWARD_SPEED: u32 = 4;
+
+/// What the emulator runs during a 60 Hz tick of a frontend.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct Tick {
+    /// The amount of whole frames to run with [`Emulator::run_frame`].
+    pub frames: u32,
+    /// The amount of single instructions to run with [`Emulator::step`].
+    pub steps: u32,
+}
+
+/// Decides how far the emulator runs during each 60 Hz tick: normally one frame, nothing while
+/// paused except for frame advances and instruction steps, several frames while fast-forwarding.
+#[derive(Debug, Clone)]
+pub struct RunLoop {
+    paused: bool,
+    fast_forwarding: bool,
+    fast_forward_speed: u32,
+    pending_frames: u32,
+    pending_steps: u32,
+}
+
+impl RunLoop {
+    /// Creates a running loop which fast-forwards at the given amount of frames per tick.
+    pub fn new(fast_forward_speed: u32) -> Self {
+        RunLoop {
+            paused: false,
+            fast_forwarding: false,
+            fast_forward_speed: fast_forward_speed.max(1),
+            pending_frames: 0,
+            pending_steps: 0,
+        }
+    }
+
+    pub fn is_paused(&self) -> bool {
+        self.paused
+    }
+
+    /// Pauses or resumes the emulation, dropping queued frame advances and steps.
+    pub fn toggle_pause(&mut self) {
+        self.paused = !self.paused;
+        self.pending_frames = 0;
+        self.pending_steps = 0;
+    }
+
+    /// Pauses and runs one more frame during the next tick.
+    pub fn advance_frame(&mut self) {
+        self.paused = true;
+        self.pending_frames += 1;
+    }
+
+    /// Pauses and runs one more instruction during the next tick.
+    pub fn step_instruction(&mut self) {
+        self.paused = true;
+        self.pending_steps += 1;
+    }
+
+    /// Starts or stops fast-forwarding, which a paused loop ignores.
+    pub fn set_fast_forward(&mut self, fast_forwarding: bool) {
+        self.fast_forwarding = fast_forwarding;
+    }
+
+    /// Returns true if the next ticks run several frames.
+    pub fn is_fast_forwarding(&self) -> bool {
+        self.fast_forwarding && !self.paused
+    }
+
+    pub fn fast_forward_speed(&self) -> u32 {
+        self.fast_forward_speed
+    }
+
+    /// Returns what to run during this tick and takes the queued frame advances and steps.
+    pub fn tick(&mut self) -> Tick {
+        if self.paused {
+            let tick = Tick {
+                frames: self.pending_frames,
+                steps: self.pending_steps,
+            };
+            self.pending_frames = 0;
+            self.pending_steps = 0;
+            tick
+        } else if self.fast_forwarding {
+            Tick {
+                frames: self.fast_forward_speed,
+                steps: 0,
+            }
+        } else {
+            Tick {
+                frames: 1,
+                steps: 0,
+            }
+        }
+    }
+
+    /// Runs the emulator for one tick.
+    ///
+    /// `before_frame` is called whenever a frame starts, to feed and record its input, and
+    /// `after_frame` whenever a frame ends, to record its output. Frames stepped through one
+    /// instruction at a time call them too, so recordings do not depend on how a frame ran.
+    pub fn run<R: Rng, B: Bus, E: From<Error>>(
+        &mut self,
+        emulator: &mut Emulator<R, B>,
+        mut before_frame: impl FnMut(&mut Emulator<R, B>) -> Result<(), E>,
+        mut after_frame: impl FnMut(&mut Emulator<R, B>) -> Result<(), E>,
+    ) -> Result<Tick, E> {
+        let tick = self.tick();
+        for _ in 0..tick.frames {
+            if emulator.frame_position() == 0 {
+                before_frame(emulator)?;
+            }
+            emulator.run_frame()?;
+            after_frame(emulator)?;
+        }
+        for _ in 0..tick.steps {
+            if emulator.frame_position() == 0 {
+                before_frame(emulator)?;
+            }
+            emulator.step()?;
+            info!(
+                "Stepped to {:#05X}, instruction {} of the frame",
+                emulator.program_counter(),
+                emulator.frame_position()
+            );
+            if emulator.frame_position() == 0 {
+                after_frame(emulator)?;
+            }
+        }
+        Ok(tick)
+    }
+}
+
+impl Default for RunLoop {
+    fn default() -> Self {
+        RunLoop::new(DEFAULT_FAST_FORWARD_SPEED)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_tick_runs_one_frame() {
+        let mut run_loop = RunLoop::default();
+        assert_eq!(
+            run_loop.tick(),
+            Tick {
+                frames: 1,
+                steps: 0
+            }
+        );
+    }
+
+    #[test]
+    fn test_paused_tick_runs_queued_work_once() {
+        let mut run_loop = RunLoop::default();
+        run_loop.toggle_pause();
+        assert_eq!(run_loop.tick(), Tick::default());
+
+        run_loop.advance_frame();
+        run_loop.step_instruction();
+        run_loop.step_instruction();
+        assert_eq!(
+            run_loop.tick(),
+            Tick {
+                frames: 1,
+                steps: 2
+            }
+        );
+        assert_eq!(run_loop.tick(), Tick::default());
+    }
+
+    #[test]
+    fn test_advance_frame_pauses() {
+        let mut run_loop = RunLoop::default();
+        run_loop.advance_frame();
+        assert!(run_loop.is_paused());
+        assert_eq!(
+            run_loop.tick(),
+            Tick {
+                frames: 1,
+                steps: 0
+            }
+        );
+    }
+
+    #[test]
+    fn test_fast_forward() {
+        let mut run_loop = RunLoop::new(3);
+        run_loop.set_fast_forward(true);
+        assert!(run_loop.is_fast_forwarding());
+        assert_eq!(
+            run_loop.tick(),
+            Tick {
+                frames: 3,
+                steps: 0
+            }
+        );
+
+        run_loop.toggle_pause();
+        assert!(!run_loop.is_fast_forwarding());
+        assert_eq!(run_loop.tick(), Tick::default());
+
+        run_loop.toggle_pause();
+        run_loop.set_fast_forward(false);
+        assert_eq!(
+            run_loop.tick(),
+            Tick {
+                frames: 1,
+                steps: 0
+            }
+        );
+    }
+
+    /// Runs a tick and returns the frame starts and ends it reported, `<` and `>`.
+    #[cfg(feature = "std")]
+    fn run_tick(run_loop: &mut RunLoop, emulator: &mut Emulator) -> String {
+        let events = core::cell::RefCell::new(String::new());
+        run_loop
+            .run(
+                emulator,
+                |_| {
+                    events.borrow_mut().push('<');
+                    Ok::<(), Error>(())
+                },
+                |_| {
+                    events.borrow_mut().push('>');
+                    Ok(())
+                },
+            )
+            .expect("Failed to run");
+        events.into_inner()
+    }
+
+    #[cfg(feature = "std")]
+    #[test]
+    fn test_run_steps_through_frame() {
+        let mut emulator = Emulator::new();
+        emulator.set_instructions_per_frame(4);
+        emulator
+            .load_rom(&[0x12, 0x00][..])
+            .expect("Failed to load");
+        let mut run_loop = RunLoop::default();
+
+        run_loop.step_instruction();
+        assert_eq!(run_tick(&mut run_loop, &mut emulator), "<");
+        assert_eq!(emulator.frame_position(), 1);
+
+        run_loop.advance_frame();
+        assert_eq!(run_tick(&mut run_loop, &mut emulator), ">");
+        assert_eq!(emulator.frame_position(), 0);
+    }
+
+    #[cfg(feature = "std")]
+    #[test]
+    fn test_run_reports_stepped_frames() {
+        let mut emulator = Emulator::new();
+        emulator.set_instructions_per_frame(2);
+        emulator
+            .load_rom(&[0x12, 0x00][..])
+            .expect("Failed to load");
+        let mut run_loop = RunLoop::default();
+
+        assert_eq!(run_tick(&mut run_loop, &mut emulator), "<>");
+        for _ in 0..3 {
+            run_loop.step_instruction();
+        }
+        assert_eq!(run_tick(&mut run_loop, &mut emulator), "<><");
+        run_loop.advance_frame();
+        run_loop.advance_frame();
+        assert_eq!(run_tick(&mut run_loop, &mut emulator), "><>");
+    }
+}
@@ -0,0 +1,221 @@
+use crate::display::{Framebuffer, DISPLAY_HEIGHT, DISPLAY_WIDTH};
+use crate::palette::Palette;
+use anyhow::anyhow;
+use std::fs::File;
+use std::io::{BufWriter, Write};
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+/// The image formats a screenshot can be saved in.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ImageFormat {
+    /// Portable Network Graphics, in the palette's colors.
+    Png,
+    /// Binary portable pixmap, in the palette's colors.
+    Ppm,
+    /// Binary portable bitmap, lit pixels are black and the palette is ignored.
+    Pbm,
+}
+
+impl ImageFormat {
+    /// Returns the file extension of the format.
+    pub fn extension(&self) -> &'static str {
+        match self {
+            ImageFormat::Png => "png",
+            ImageFormat::Ppm => "ppm",
+            ImageFormat::Pbm => "pbm",
+        }
+    }
+
+    /// Returns the format matching the extension of the path.
+    pub fn from_path(path: &Path) -> Option<ImageFormat> {
+        path.extension()?.to_str()?.parse().ok()
+    }
+}
+
+impl FromStr for ImageFormat {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_ascii_lowercase().as_str() {
+            "png" => Ok(ImageFormat::Png),
+            "ppm" => Ok(ImageFormat::Ppm),
+            "pbm" => Ok(ImageFormat::Pbm),
+            _ => Err(anyhow!("unknown image format {s}, expected png, ppm or pbm")),
+        }
+    }
+}
+
+/// Renders the framebuffer into RGB bytes, each Chip8 pixel becoming a `scale` x `scale` square.
+pub fn scaled_rgb(framebuffer: &Framebuffer, scale: usize, palette: &Palette) -> Vec<u8> {
+    let width = DISPLAY_WIDTH * scale;
+    let height = DISPLAY_HEIGHT * scale;
+    let mut data = Vec::with_capacity(width * height * 3);
+    for y in 0..height {
+        for x in 0..width {
+            let color = if framebuffer.get(x / scale, y / scale) {
+                palette.pixel
+            } else {
+                palette.background
+            };
+            data.extend_from_slice(&[color.r, color.g, color.b]);
+        }
+    }
+    data
+}
+
+/// Encodes the framebuffer as an image in the given format.
+pub fn write_image<W: Write>(
+    mut writer: W,
+    framebuffer: &Framebuffer,
+    format: ImageFormat,
+    scale: usize,
+    palette: &Palette,
+) -> Result<(), anyhow::Error> {
+    if scale == 0 {
+        return Err(anyhow!("the image scale must be at least 1"));
+    }
+    let width = DISPLAY_WIDTH * scale;
+    let height = DISPLAY_HEIGHT * scale;
+    match format {
+        ImageFormat::Png => {
+            let mut encoder = png::Encoder::new(writer, width as u32, height as u32);
+            encoder.set_color(png::ColorType::Rgb);
+            encoder.set_depth(png::BitDepth::Eight);
+            let mut png_writer = encoder.write_header()?;
+            png_writer.write_image_data(&scaled_rgb(framebuffer, scale, palette))?;
+            png_writer.finish()?;
+        }
+        ImageFormat::Ppm => {
+            write!(writer, "P6\n{width} {height}\n255\n")?;
+            writer.write_all(&scaled_rgb(framebuffer, scale, palette))?;
+        }
+        ImageFormat::Pbm => {
+            write!(writer, "P4\n{width} {height}\n")?;
+            // Each row is packed 8 pixels per byte, padded to a whole byte.
+            let mut row = vec![0u8; width.div_ceil(8)];
+            for y in 0..height {
+                row.fill(0);
+                for x in 0..width {
+                    if framebuffer.get(x / scale, y / scale) {
+                        row[x / 8] |= 0x80 >> (x % 8);
+                    }
+                }
+                writer.write_all(&row)?;
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Saves the framebuffer as an image file.
+pub fn save_screenshot(
+    path: &Path,
+    framebuffer: &Framebuffer,
+    format: ImageFormat,
+    scale: usize,
+    palette: &Palette,
+) -> Result<(), anyhow::Error> {
+    let mut writer = BufWriter::new(File::create(path)?);
+    write_image(&mut writer, framebuffer, format, scale, palette)?;
+    writer.flush()?;
+    Ok(())
+}
+
+/// Returns a path in the directory named after the current time, e.g. `chip8-1700000000123.png`.
+pub fn timestamped_path(directory: &Path, extension: &str) -> PathBuf {
+    let millis = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|duration| duration.as_millis())
+        .unwrap_or_default();
+    directory.join(format!("chip8-{millis}.{extension}"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn framebuffer() -> Framebuffer {
+        let mut framebuffer = Framebuffer::new();
+        framebuffer.set(0, 0, true);
+        framebuffer
+    }
+
+    #[test]
+    fn test_image_format_from_path() {
+        assert_eq!(
+            ImageFormat::from_path(Path::new("shot.PNG")),
+            Some(ImageFormat::Png)
+        );
+        assert_eq!(
+            ImageFormat::from_path(Path::new("shot.pbm")),
+            Some(ImageFormat::Pbm)
+        );
+        assert_eq!(ImageFormat::from_path(Path::new("shot.jpg")), None);
+    }
+
+    #[test]
+    fn test_write_ppm() {
+        let mut data = Vec::new();
+
+        write_image(
+            &mut data,
+            &framebuffer(),
+            ImageFormat::Ppm,
+            2,
+            &Palette::CLASSIC_GREEN,
+        )
+        .unwrap();
+
+        let header = b"P6\n128 64\n255\n";
+        assert_eq!(&data[..header.len()], header);
+        assert_eq!(data.len(), header.len() + 128 * 64 * 3);
+        let pixels = &data[header.len()..];
+        assert_eq!(pixels[0..9], [0, 255, 0, 0, 255, 0, 0, 0, 0]);
+        assert_eq!(pixels[128 * 3..128 * 3 + 3], [0, 255, 0]);
+    }
+
+    #[test]
+    fn test_write_pbm() {
+        let mut data = Vec::new();
+
+        write_image(
+            &mut data,
+            &framebuffer(),
+            ImageFormat::Pbm,
+            1,
+            &Palette::default(),
+        )
+        .unwrap();
+
+        let header = b"P4\n64 32\n";
+        assert_eq!(&data[..header.len()], header);
+        assert_eq!(data.len(), header.len() + 8 * 32);
+        assert_eq!(data[header.len()], 0x80);
+    }
+
+    #[test]
+    fn test_write_png() {
+        let mut data = Vec::new();
+
+        write_image(
+            &mut data,
+            &framebuffer(),
+            ImageFormat::Png,
+            3,
+            &Palette::default(),
+        )
+        .unwrap();
+
+        assert_eq!(&data[1..4], b"PNG");
+        assert!(write_image(
+            &mut Vec::new(),
+            &framebuffer(),
+            ImageFormat::Png,
+            0,
+            &Palette::default()
+        )
+        .is_err());
+    }
+}
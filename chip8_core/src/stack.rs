@@ -1,52 +1,144 @@
-use alloc::vec::Vec;
-use core::fmt::Debug;
+use crate::error::Error;
+use core::fmt::{Debug, Display, Formatter};
+use core::str::FromStr;
 
-/// Implements a simple stack based on a vector.
-#[derive(Debug)]
+/// The largest capacity a stack can be configured with.
+pub const MAX_STACK_CAPACITY: usize = 64;
+
+/// The capacity of the CHIP-8 call stack.
+pub const DEFAULT_STACK_CAPACITY: usize = 16;
+
+/// The errors of the stack operations.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum StackError {
+    /// An element was pushed on a full stack.
+    Overflow,
+    /// An element was popped from an empty stack.
+    Underflow,
+}
+
+impl Display for StackError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> core::fmt::Result {
+        match self {
+            StackError::Overflow => write!(f, "stack overflow"),
+            StackError::Underflow => write!(f, "stack underflow"),
+        }
+    }
+}
+
+/// Selects where the emulator keeps its call stack.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum StackMode {
+    /// In a [`Stack`] outside of the emulated memory.
+    #[default]
+    Internal,
+    /// In the emulated memory, below 0xED0, like the COSMAC VIP interpreter did.
+    Memory,
+}
+
+impl StackMode {
+    /// Returns the name of the mode, as accepted by [`StackMode::from_str`].
+    pub fn name(&self) -> &'static str {
+        match self {
+            StackMode::Internal => "internal",
+            StackMode::Memory => "memory",
+        }
+    }
+}
+
+impl FromStr for StackMode {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        [StackMode::Internal, StackMode::Memory]
+            .into_iter()
+            .find(|mode| mode.name().eq_ignore_ascii_case(s))
+            .ok_or_else(|| Error::parse(s, "internal or memory"))
+    }
+}
+
+/// Implements a fixed-capacity stack which does not allocate.
+#[derive(Debug, Clone)]
 pub struct Stack<T> {
-    storage: Vec<T>,
+    storage: [T; MAX_STACK_CAPACITY],
+    len: usize,
+    capacity: usize,
 }
 
-impl<T: Debug> Stack<T> {
+impl<T: Debug + Copy + Default> Stack<T> {
+    /// Creates a new stack holding up to [`DEFAULT_STACK_CAPACITY`] elements.
     pub fn new() -> Self {
+        Stack::with_capacity(DEFAULT_STACK_CAPACITY)
+    }
+
+    /// Creates a new stack holding up to `capacity` elements, at most [`MAX_STACK_CAPACITY`].
+    pub fn with_capacity(capacity: usize) -> Self {
         Stack {
-            storage: Vec::new(),
+            storage: [T::default(); MAX_STACK_CAPACITY],
+            len: 0,
+            capacity: capacity.min(MAX_STACK_CAPACITY),
         }
     }
 
     /// Pushes an element on the top of the stack
-    pub fn push(&mut self, element: T) {
-        self.storage.push(element)
+    pub fn push(&mut self, element: T) -> Result<(), StackError> {
+        if self.len >= self.capacity {
+            return Err(StackError::Overflow);
+        }
+        self.storage[self.len] = element;
+        self.len += 1;
+        Ok(())
     }
 
     /// Returns the top element from the stack.
-    pub fn pop(&mut self) -> Option<T> {
-        self.storage.pop()
+    pub fn pop(&mut self) -> Result<T, StackError> {
+        if self.len == 0 {
+            return Err(StackError::Underflow);
+        }
+        self.len -= 1;
+        Ok(self.storage[self.len])
     }
 
     /// Returns the top element from the stack without removing it.
     pub fn peek(&self) -> Option<&T> {
-        self.storage.last()
+        self.elements().last()
+    }
+
+    /// Returns the elements of the stack, from the bottom to the top.
+    pub fn elements(&self) -> &[T] {
+        &self.storage[..self.len]
+    }
+
+    /// Removes all the elements.
+    pub fn clear(&mut self) {
+        self.len = 0;
     }
 
     /// Size returns the size of the stack.
     pub fn size(&self) -> usize {
-        self.storage.len()
+        self.len
+    }
+
+    /// Returns the amount of elements the stack can hold.
+    pub fn capacity(&self) -> usize {
+        self.capacity
     }
 
     /// Is Empty returns true if the stack is empty.
     pub fn is_empty(&self) -> bool {
-        self.storage.len() == 0
+        self.len == 0
     }
 }
 
-impl<T: Debug> Default for Stack<T> {
+impl<T: Debug + Copy + Default> Default for Stack<T> {
     fn default() -> Self {
         Self::new()
     }
 }
 
 #[cfg(test)]
+// the tests predate the lints
+#[allow(clippy::bool_assert_comparison)]
 mod tests {
     use super::*;
 
@@ -56,7 +148,7 @@ mod tests {
         let mut stack = Stack::<u16>::new();
 
         // Then
-        stack.push(15);
+        stack.push(15).unwrap();
 
         assert_eq!(1, stack.size());
         assert_eq!(15u16, *stack.peek().unwrap());
@@ -67,6 +159,20 @@ mod tests {
         assert_eq!(true, stack.is_empty());
 
         let element = stack.pop();
-        assert_eq!(true, element.is_none())
+        assert_eq!(element, Err(StackError::Underflow))
+    }
+
+    #[test]
+    fn overflow() {
+        // Given
+        let mut stack = Stack::<u16>::with_capacity(2);
+
+        // Then
+        stack.push(1).unwrap();
+        stack.push(2).unwrap();
+
+        assert_eq!(stack.push(3), Err(StackError::Overflow));
+        assert_eq!(stack.elements(), &[1, 2]);
+        assert_eq!(Stack::<u16>::with_capacity(1000).capacity(), MAX_STACK_CAPACITY);
     }
 }
@@ -0,0 +1,750 @@
+use crate::bus::Bus;
+use crate::emulator::Emulator;
+use crate::error::Error;
+use crate::instruction::{Instruction, ProcessorInstruction};
+use crate::random::Rng;
+use anyhow::anyhow;
+use std::fmt::{self, Display, Formatter};
+use std::fs::File;
+use std::io::{self, BufRead, BufReader, BufWriter, Read, Write};
+use std::path::Path;
+use std::str::FromStr;
+
+/// The first bytes of every binary trace file.
+pub const BINARY_TRACE_MAGIC: &[u8; 5] = b"C8TR\x01";
+
+/// How the instructions of a trace are written.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TraceFormat {
+    /// One line per instruction: its number, address, opcode, mnemonic and the changes.
+    Text,
+    /// Big-endian records of the same fields without the mnemonic, after [`BINARY_TRACE_MAGIC`].
+    Binary,
+}
+
+impl FromStr for TraceFormat {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_ascii_lowercase().as_str() {
+            "text" => Ok(TraceFormat::Text),
+            "binary" => Ok(TraceFormat::Binary),
+            _ => Err(Error::parse(s, "text or binary")),
+        }
+    }
+}
+
+/// An inclusive range of memory addresses, such as `200-2FF`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct AddressRange {
+    pub start: u16,
+    pub end: u16,
+}
+
+impl AddressRange {
+    pub fn contains(&self, address: u16) -> bool {
+        (self.start..=self.end).contains(&address)
+    }
+}
+
+/// Parses `START-END` or a single address, in hexadecimal with an optional `0x` prefix.
+impl FromStr for AddressRange {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let parse = |address: &str| {
+            let address = address.trim();
+            let digits = address
+                .strip_prefix("0x")
+                .or_else(|| address.strip_prefix("0X"))
+                .unwrap_or(address);
+            u16::from_str_radix(digits, 16)
+                .map_err(|_| Error::parse(address, "an address in hexadecimal digits"))
+        };
+        let (start, end) = match s.split_once('-') {
+            Some((start, end)) => (parse(start)?, parse(end)?),
+            None => (parse(s)?, parse(s)?),
+        };
+        if start > end {
+            return Err(Error::parse(s, "a range which ends after it starts"));
+        }
+        Ok(AddressRange { start, end })
+    }
+}
+
+/// Chooses the instructions a trace records.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct TraceFilter {
+    /// Only instructions at these addresses are recorded, all of them if None.
+    pub addresses: Option<AddressRange>,
+    /// Only instructions of these types are recorded, see [`ProcessorInstruction::NAMES`]. All
+    /// of them if empty.
+    pub instructions: Vec<&'static str>,
+}
+
+impl TraceFilter {
+    /// Creates a filter of the address range and of the instruction types, whose names are
+    /// matched case-insensitively.
+    pub fn new(addresses: Option<AddressRange>, names: &[String]) -> Result<Self, Error> {
+        let instructions = names
+            .iter()
+            .map(|name| {
+                ProcessorInstruction::NAMES
+                    .into_iter()
+                    .find(|known| known.eq_ignore_ascii_case(name.trim()))
+                    .ok_or_else(|| Error::parse(name, "an instruction type such as Draw or Call"))
+            })
+            .collect::<Result<_, _>>()?;
+        Ok(TraceFilter {
+            addresses,
+            instructions,
+        })
+    }
+
+    /// Returns true if the instruction at the address is recorded.
+    pub fn matches(&self, address: u16, instruction: &ProcessorInstruction) -> bool {
+        self.addresses.is_none_or(|range| range.contains(address))
+            && (self.instructions.is_empty() || self.instructions.contains(&instruction.name()))
+    }
+}
+
+/// An executed instruction and the registers it changed.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TraceRecord {
+    /// The number of the instruction since the emulator was created, from 0.
+    pub number: u64,
+    /// The address the instruction was fetched from.
+    pub address: u16,
+    pub opcode: u16,
+    /// The general purpose registers the instruction changed and their new values.
+    pub registers: Vec<(u8, u8)>,
+    /// The new value of the index register if the instruction changed it.
+    pub index_register: Option<u16>,
+}
+
+impl TraceRecord {
+    /// Returns the record of an instruction from the registers and index register before and
+    /// after it ran.
+    pub fn new(
+        number: u64,
+        address: u16,
+        opcode: u16,
+        before: (&[u8; 16], u16),
+        after: (&[u8; 16], u16),
+    ) -> Self {
+        TraceRecord {
+            number,
+            address,
+            opcode,
+            registers: (0..16u8)
+                .filter(|&register| before.0[register as usize] != after.0[register as usize])
+                .map(|register| (register, after.0[register as usize]))
+                .collect(),
+            index_register: (before.1 != after.1).then_some(after.1),
+        }
+    }
+
+    /// Returns the decoded instruction.
+    pub fn instruction(&self) -> ProcessorInstruction {
+        Instruction::new(self.opcode.to_be_bytes()).processor_instruction()
+    }
+
+    /// Writes the record as a line such as `42 0208 7A01 ADD VA, 01 VA=03`.
+    pub fn write_text<W: Write>(&self, writer: &mut W) -> io::Result<()> {
+        writeln!(writer, "{self}")
+    }
+
+    /// Parses a line written by [`TraceRecord::write_text`]. The mnemonic is skipped, the
+    /// opcode tells the instruction.
+    pub fn parse_text(line: &str) -> Result<Self, Error> {
+        let invalid = || Error::parse(line, "a trace line such as 42 0208 7A01 ADD VA, 01 VA=03");
+        let mut fields = line.split_whitespace();
+        let number = fields
+            .next()
+            .and_then(|field| field.parse().ok())
+            .ok_or_else(invalid)?;
+        let mut hex_field = || {
+            fields
+                .next()
+                .and_then(|field| u16::from_str_radix(field, 16).ok())
+                .ok_or_else(invalid)
+        };
+        let address = hex_field()?;
+        let opcode = hex_field()?;
+        let mut record = TraceRecord {
+            number,
+            address,
+            opcode,
+            registers: Vec::new(),
+            index_register: None,
+        };
+        for change in line.split_whitespace().skip(3) {
+            let Some((target, value)) = change.split_once('=') else {
+                continue;
+            };
+            let value = u16::from_str_radix(value, 16).map_err(|_| invalid())?;
+            match target.strip_prefix('V') {
+                _ if target == "I" => record.index_register = Some(value),
+                Some(register) => {
+                    let register = u8::from_str_radix(register, 16).map_err(|_| invalid())?;
+                    if register > 0xF || value > 0xFF {
+                        return Err(invalid());
+                    }
+                    record.registers.push((register, value as u8));
+                }
+                None => return Err(invalid()),
+            }
+        }
+        Ok(record)
+    }
+
+    /// Writes the number, address and opcode, a bit mask of the changed registers, a byte
+    /// telling if I changed, then the new values of the changed registers and of I.
+    pub fn write_binary<W: Write>(&self, writer: &mut W) -> io::Result<()> {
+        let mask = self
+            .registers
+            .iter()
+            .fold(0u16, |mask, (register, _)| mask | 1 << register);
+        writer.write_all(&self.number.to_be_bytes())?;
+        writer.write_all(&self.address.to_be_bytes())?;
+        writer.write_all(&self.opcode.to_be_bytes())?;
+        writer.write_all(&mask.to_be_bytes())?;
+        writer.write_all(&[self.index_register.is_some() as u8])?;
+        for (_, value) in &self.registers {
+            writer.write_all(&[*value])?;
+        }
+        if let Some(index_register) = self.index_register {
+            writer.write_all(&index_register.to_be_bytes())?;
+        }
+        Ok(())
+    }
+
+    /// Reads a record written by [`TraceRecord::write_binary`], None at the end of the trace.
+    /// A trace which ends inside a record is an error.
+    pub fn read_binary<R: Read>(reader: &mut R) -> Result<Option<Self>, anyhow::Error> {
+        let mut fixed = [0; 15];
+        let mut filled = 0;
+        while filled < fixed.len() {
+            match reader.read(&mut fixed[filled..]) {
+                Ok(0) if filled == 0 => return Ok(None),
+                Ok(0) => return Err(truncated_record()),
+                Ok(amount) => filled += amount,
+                Err(error) if error.kind() == io::ErrorKind::Interrupted => {}
+                Err(error) => return Err(error.into()),
+            }
+        }
+        let mut read_exact = |buf: &mut [u8]| {
+            reader.read_exact(buf).map_err(|error| match error.kind() {
+                io::ErrorKind::UnexpectedEof => truncated_record(),
+                _ => error.into(),
+            })
+        };
+        let mut number = [0; 8];
+        number.copy_from_slice(&fixed[..8]);
+        let mask = u16::from_be_bytes([fixed[12], fixed[13]]);
+        let mut registers = Vec::new();
+        for register in (0..16u8).filter(|register| mask & 1 << register != 0) {
+            let mut value = [0];
+            read_exact(&mut value)?;
+            registers.push((register, value[0]));
+        }
+        let index_register = match fixed[14] {
+            0 => None,
+            _ => {
+                let mut value = [0; 2];
+                read_exact(&mut value)?;
+                Some(u16::from_be_bytes(value))
+            }
+        };
+        Ok(Some(TraceRecord {
+            number: u64::from_be_bytes(number),
+            address: u16::from_be_bytes([fixed[8], fixed[9]]),
+            opcode: u16::from_be_bytes([fixed[10], fixed[11]]),
+            registers,
+            index_register,
+        }))
+    }
+}
+
+/// Returns the error of a binary trace which ends inside a record.
+fn truncated_record() -> anyhow::Error {
+    anyhow!("the binary trace ends inside a record")
+}
+
+/// Formats the record as a line of a text trace, without the line break.
+impl Display for TraceRecord {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{} {:04X} {:04X} {}",
+            self.number,
+            self.address,
+            self.opcode,
+            self.instruction()
+        )?;
+        for (register, value) in &self.registers {
+            write!(f, " V{register:X}={value:02X}")?;
+        }
+        if let Some(index_register) = self.index_register {
+            write!(f, " I={index_register:04X}")?;
+        }
+        Ok(())
+    }
+}
+
+/// Reads the records of a text or binary trace, telling them apart by [`BINARY_TRACE_MAGIC`].
+/// Empty lines and lines starting with `#` are skipped in text traces.
+pub fn read_trace<R: BufRead>(mut reader: R) -> Result<Vec<TraceRecord>, anyhow::Error> {
+    let mut records = Vec::new();
+    if reader.fill_buf()?.starts_with(BINARY_TRACE_MAGIC) {
+        reader.consume(BINARY_TRACE_MAGIC.len());
+        while let Some(record) = TraceRecord::read_binary(&mut reader)? {
+            records.push(record);
+        }
+        return Ok(records);
+    }
+    for line in reader.lines() {
+        let line = line?;
+        let line = line.trim();
+        if !line.is_empty() && !line.starts_with('#') {
+            records.push(TraceRecord::parse_text(line)?);
+        }
+    }
+    Ok(records)
+}
+
+/// Reads the trace file at the path, see [`read_trace`].
+pub fn load_trace(path: &Path) -> Result<Vec<TraceRecord>, anyhow::Error> {
+    read_trace(BufReader::new(File::open(path)?))
+}
+
+/// Writes the records of the instructions an emulator executes, see
+/// [`Emulator::set_trace`].
+///
+/// [`Emulator::set_trace`]: crate::emulator::Emulator::set_trace
+pub struct TraceWriter {
+    writer: Box<dyn Write>,
+    format: TraceFormat,
+    filter: TraceFilter,
+    records: u64,
+    /// The first write which failed, the emulation goes on and [`TraceWriter::finish`]
+    /// reports it.
+    error: Option<io::Error>,
+}
+
+impl TraceWriter {
+    pub fn new(
+        writer: impl Write + 'static,
+        format: TraceFormat,
+        filter: TraceFilter,
+    ) -> Result<Self, anyhow::Error> {
+        let mut writer: Box<dyn Write> = Box::new(writer);
+        if format == TraceFormat::Binary {
+            writer.write_all(BINARY_TRACE_MAGIC)?;
+        }
+        Ok(TraceWriter {
+            writer,
+            format,
+            filter,
+            records: 0,
+            error: None,
+        })
+    }
+
+    /// Creates the trace file at the path.
+    pub fn create(
+        path: &Path,
+        format: TraceFormat,
+        filter: TraceFilter,
+    ) -> Result<Self, anyhow::Error> {
+        TraceWriter::new(BufWriter::new(File::create(path)?), format, filter)
+    }
+
+    /// Returns true if the instruction at the address passes the filter.
+    pub fn wants(&self, address: u16, instruction: &ProcessorInstruction) -> bool {
+        self.error.is_none() && self.filter.matches(address, instruction)
+    }
+
+    pub fn record(&mut self, record: &TraceRecord) {
+        let result = match self.format {
+            TraceFormat::Text => record.write_text(&mut self.writer),
+            TraceFormat::Binary => record.write_binary(&mut self.writer),
+        };
+        match result {
+            Ok(()) => self.records += 1,
+            Err(error) => self.error = Some(error),
+        }
+    }
+
+    /// Writes a `#` comment line telling why the instruction of the last record failed, in
+    /// text traces. Binary traces end with the failed instruction's record.
+    pub fn record_failure(&mut self, error: &Error) {
+        if self.format != TraceFormat::Text || self.error.is_some() {
+            return;
+        }
+        if let Err(error) = writeln!(self.writer, "# failed: {error}") {
+            self.error = Some(error);
+        }
+    }
+
+    /// Returns the amount of records written.
+    pub fn records(&self) -> u64 {
+        self.records
+    }
+
+    /// Flushes the trace and returns the amount of records written, or the first error.
+    pub fn finish(mut self) -> Result<u64, anyhow::Error> {
+        if let Some(error) = self.error.take() {
+            return Err(error.into());
+        }
+        self.writer.flush()?;
+        Ok(self.records)
+    }
+}
+
+/// Where an emulator's run stopped following a reference trace, see [`compare`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Divergence {
+    /// The instruction differs from the reference's.
+    Differs {
+        expected: TraceRecord,
+        actual: TraceRecord,
+        /// The last reference instruction which matched, None if the first one differs.
+        last_match: Option<TraceRecord>,
+    },
+    /// The emulator failed to run an instruction before or at the expected one.
+    Failed { expected: TraceRecord, error: Error },
+    /// The reference goes on after the maximum amount of frames ran.
+    GaveUp { expected: TraceRecord },
+}
+
+/// The result of comparing an emulator's run with a reference trace.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Comparison {
+    /// The amount of frames the emulator started.
+    pub frames: u64,
+    /// The first difference, None if every reference instruction matched.
+    pub divergence: Option<Divergence>,
+}
+
+/// Runs the emulator instruction by instruction and compares it with the reference trace, so
+/// it stops right at the first difference. Instructions missing from a filtered reference run
+/// without being compared.
+///
+/// `before_frame` is called whenever a frame starts, for example to play a movie's input.
+/// Reference instruction numbers which go back are an error.
+pub fn compare<R: Rng, B: Bus>(
+    emulator: &mut Emulator<R, B>,
+    reference: &[TraceRecord],
+    max_frames: u64,
+    mut before_frame: impl FnMut(&mut Emulator<R, B>) -> Result<(), anyhow::Error>,
+) -> Result<Comparison, anyhow::Error> {
+    let mut frames = 0;
+    let mut last_match: Option<&TraceRecord> = None;
+    for expected in reference {
+        let actual = loop {
+            let number = emulator.instructions_executed();
+            if number > expected.number {
+                return Err(anyhow!(
+                    "the reference instruction numbers go back at instruction {}",
+                    expected.number
+                ));
+            }
+            if emulator.frame_position() == 0 {
+                if frames == max_frames {
+                    return Ok(Comparison {
+                        frames,
+                        divergence: Some(Divergence::GaveUp {
+                            expected: expected.clone(),
+                        }),
+                    });
+                }
+                frames += 1;
+                before_frame(emulator)?;
+            }
+
+            let address = emulator.program_counter();
+            let bus = emulator.bus();
+            let opcode = u16::from_be_bytes([
+                bus.peek(address).unwrap_or_default(),
+                bus.peek(address.wrapping_add(1)).unwrap_or_default(),
+            ]);
+            let before = (*emulator.registers(), emulator.index_register());
+            if let Err(error) = emulator.step() {
+                return Ok(Comparison {
+                    frames,
+                    divergence: Some(Divergence::Failed {
+                        expected: expected.clone(),
+                        error,
+                    }),
+                });
+            }
+            if number == expected.number {
+                break TraceRecord::new(
+                    number,
+                    address,
+                    opcode,
+                    (&before.0, before.1),
+                    (emulator.registers(), emulator.index_register()),
+                );
+            }
+        };
+
+        if actual != *expected {
+            return Ok(Comparison {
+                frames,
+                divergence: Some(Divergence::Differs {
+                    expected: expected.clone(),
+                    actual,
+                    last_match: last_match.cloned(),
+                }),
+            });
+        }
+        last_match = Some(expected);
+    }
+    Ok(Comparison {
+        frames,
+        divergence: None,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::cell::RefCell;
+    use std::rc::Rc;
+
+    /// A writer whose bytes the test keeps after the trace took it.
+    #[derive(Clone, Default)]
+    struct SharedBuffer(Rc<RefCell<Vec<u8>>>);
+
+    impl Write for SharedBuffer {
+        fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+            self.0.borrow_mut().extend_from_slice(buf);
+            Ok(buf.len())
+        }
+
+        fn flush(&mut self) -> io::Result<()> {
+            Ok(())
+        }
+    }
+
+    /// Returns an emulator running 4 instructions per frame of a ROM which loops at 206.
+    fn traced_emulator() -> Emulator {
+        let mut emulator = Emulator::new();
+        emulator.set_instructions_per_frame(4);
+        // LD VA, 02; ADD VA, 01; LD I, 22A; JP 206
+        emulator
+            .load_rom(&[0x6A, 0x02, 0x7A, 0x01, 0xA2, 0x2A, 0x12, 0x06][..])
+            .expect("Failed to load");
+        emulator
+    }
+
+    fn run_traced(format: TraceFormat, filter: TraceFilter) -> Vec<u8> {
+        let buffer = SharedBuffer::default();
+        let mut emulator = traced_emulator();
+        let trace = TraceWriter::new(buffer.clone(), format, filter).expect("Failed to create");
+        emulator.set_trace(Some(trace));
+        emulator.run_frame().expect("Failed to run");
+        let records = emulator
+            .take_trace()
+            .unwrap()
+            .finish()
+            .expect("Failed to finish");
+        assert!(records > 0);
+        let bytes = buffer.0.borrow().clone();
+        bytes
+    }
+
+    #[test]
+    fn test_text_trace() {
+        let trace = run_traced(TraceFormat::Text, TraceFilter::default());
+        assert_eq!(
+            String::from_utf8(trace).unwrap(),
+            "0 0200 6A02 LD VA, 02 VA=02\n\
+             1 0202 7A01 ADD VA, 01 VA=03\n\
+             2 0204 A22A LD I, 22A I=022A\n\
+             3 0206 1206 JP 206\n"
+        );
+    }
+
+    #[test]
+    fn test_binary_trace() {
+        let filter = TraceFilter::new(None, &["addvaluetoregister".to_string()]).unwrap();
+        let trace = run_traced(TraceFormat::Binary, filter);
+        let mut expected = BINARY_TRACE_MAGIC.to_vec();
+        expected.extend_from_slice(&[0, 0, 0, 0, 0, 0, 0, 1, 0x02, 0x02, 0x7A, 0x01]);
+        expected.extend_from_slice(&[0x04, 0x00, 0, 0x03]);
+        assert_eq!(trace, expected);
+    }
+
+    #[test]
+    fn test_read_trace() {
+        let text = run_traced(TraceFormat::Text, TraceFilter::default());
+        let binary = run_traced(TraceFormat::Binary, TraceFilter::default());
+        let records = read_trace(&text[..]).expect("Failed to read");
+        assert_eq!(read_trace(&binary[..]).expect("Failed to read"), records);
+        assert_eq!(records.len(), 4);
+        assert_eq!(records[1].registers, vec![(0xA, 0x03)]);
+        assert_eq!(records[2].index_register, Some(0x22A));
+
+        let records =
+            read_trace(&b"# from another emulator\n\n7 0200 8124 ADD V1, V2 V1=FF VF=00\n"[..])
+                .expect("Failed to read");
+        assert_eq!(records[0].number, 7);
+        assert_eq!(records[0].registers, vec![(0x1, 0xFF), (0xF, 0x00)]);
+        assert!(read_trace(&b"0 0200 8124 V1=100\n"[..]).is_err());
+    }
+
+    #[test]
+    fn test_trace_records_failed_instruction() {
+        let buffer = SharedBuffer::default();
+        let mut emulator = Emulator::new();
+        // LD I, FFF; LD V1, [I] reads past the end of memory
+        emulator
+            .load_rom(&[0xAF, 0xFF, 0xF1, 0x65][..])
+            .expect("Failed to load");
+        let trace = TraceWriter::new(buffer.clone(), TraceFormat::Text, TraceFilter::default())
+            .expect("Failed to create");
+        emulator.set_trace(Some(trace));
+
+        let error = emulator
+            .run_frame()
+            .expect_err("Read past the end of memory");
+
+        emulator
+            .take_trace()
+            .unwrap()
+            .finish()
+            .expect("Failed to finish");
+        let trace = String::from_utf8(buffer.0.borrow().clone()).unwrap();
+        let lines: Vec<&str> = trace.lines().collect();
+        assert_eq!(lines.len(), 3);
+        assert!(lines[1].starts_with("1 0202 F165"));
+        assert_eq!(lines[2], format!("# failed: {error}"));
+        assert_eq!(
+            read_trace(trace.as_bytes()).expect("Failed to read").len(),
+            2
+        );
+    }
+
+    #[test]
+    fn test_read_truncated_binary_trace() {
+        let binary = run_traced(TraceFormat::Binary, TraceFilter::default());
+
+        for length in [
+            binary.len() - 1,
+            binary.len() - 3,
+            BINARY_TRACE_MAGIC.len() + 4,
+        ] {
+            assert!(read_trace(&binary[..length]).is_err(), "cut at {length}");
+        }
+        assert!(read_trace(&binary[..BINARY_TRACE_MAGIC.len()])
+            .expect("Failed to read")
+            .is_empty());
+    }
+
+    #[test]
+    fn test_parse_trace_format() {
+        assert_eq!("Binary".parse::<TraceFormat>(), Ok(TraceFormat::Binary));
+        assert!(matches!(
+            "json".parse::<TraceFormat>(),
+            Err(Error::Parse { .. })
+        ));
+    }
+
+    /// Compares a new emulator with the reference, counting the frames it starts.
+    fn compare_traced(reference: &[TraceRecord], max_frames: u64) -> (Comparison, u64) {
+        let mut frames_started = 0;
+        let comparison = compare(&mut traced_emulator(), reference, max_frames, |_| {
+            frames_started += 1;
+            Ok(())
+        })
+        .expect("Failed to compare");
+        (comparison, frames_started)
+    }
+
+    #[test]
+    fn test_compare_matching_trace() {
+        let text = run_traced(TraceFormat::Text, TraceFilter::default());
+        let reference = read_trace(&text[..]).expect("Failed to read");
+
+        let (comparison, frames_started) = compare_traced(&reference, 10);
+
+        assert_eq!(
+            comparison,
+            Comparison {
+                frames: 1,
+                divergence: None
+            }
+        );
+        assert_eq!(frames_started, 1);
+    }
+
+    #[test]
+    fn test_compare_divergent_trace() {
+        let text = run_traced(TraceFormat::Text, TraceFilter::default());
+        let mut reference = read_trace(&text[..]).expect("Failed to read");
+        reference[1].registers = vec![(0xA, 0x04)];
+
+        let (comparison, _) = compare_traced(&reference, 10);
+
+        let Some(Divergence::Differs {
+            expected,
+            actual,
+            last_match,
+        }) = comparison.divergence
+        else {
+            panic!("Unexpected comparison {comparison:?}");
+        };
+        assert_eq!(expected, reference[1]);
+        assert_eq!(actual.registers, vec![(0xA, 0x03)]);
+        assert_eq!(last_match, Some(reference[0].clone()));
+    }
+
+    #[test]
+    fn test_compare_filtered_trace() {
+        let range: AddressRange = "206".parse().unwrap();
+        let filter = TraceFilter::new(Some(range), &[]).unwrap();
+        let text = run_traced(TraceFormat::Text, filter);
+        let mut reference = read_trace(&text[..]).expect("Failed to read");
+        assert_eq!(reference.len(), 1);
+        // the loop runs 4 instructions per frame
+        reference.push(TraceRecord {
+            number: 11,
+            ..reference[0].clone()
+        });
+
+        let (comparison, _) = compare_traced(&reference, 10);
+        assert_eq!(comparison.frames, 3);
+        assert_eq!(comparison.divergence, None);
+
+        let (comparison, _) = compare_traced(&reference, 2);
+        assert_eq!(
+            comparison.divergence,
+            Some(Divergence::GaveUp {
+                expected: reference[1].clone()
+            })
+        );
+        reference.swap(0, 1);
+        assert!(compare(&mut traced_emulator(), &reference, 10, |_| Ok(())).is_err());
+    }
+
+    #[test]
+    fn test_trace_filter() {
+        let range: AddressRange = "0x202-204".parse().unwrap();
+        let filter = TraceFilter::new(Some(range), &[]).unwrap();
+        let trace = String::from_utf8(run_traced(TraceFormat::Text, filter)).unwrap();
+        assert_eq!(trace.lines().count(), 2);
+
+        assert!("300-200".parse::<AddressRange>().is_err());
+        assert_eq!(
+            "2A0".parse::<AddressRange>().unwrap(),
+            AddressRange {
+                start: 0x2A0,
+                end: 0x2A0
+            }
+        );
+        assert!(TraceFilter::new(None, &["Teleport".to_string()]).is_err());
+    }
+}
@@ -0,0 +1,315 @@
+use anyhow::anyhow;
+use chip8_core::audio::{WavRecorder, DEFAULT_SAMPLE_RATE};
+use chip8_core::emulator::Emulator;
+use chip8_core::error::Error;
+use chip8_core::movie::{Movie, MovieHeader, MoviePlayer, MovieRecorder};
+use chip8_core::options::EmulatorOptions;
+use chip8_core::palette::Palette;
+use chip8_core::profile::{ProfileFormat, Profiler};
+use chip8_core::read::open_rom;
+use chip8_core::recording::{AnimationFormat, AnimationRecorder};
+use chip8_core::run::RunLoop;
+use chip8_core::screenshot::{save_screenshot, ImageFormat};
+use chip8_core::trace::{AddressRange, TraceFilter, TraceFormat, TraceWriter};
+use clap::Parser;
+use log::info;
+use std::fs::File;
+use std::io::{BufWriter, Seek, Write};
+use std::path::{Path, PathBuf};
+
+#[derive(Parser, Debug)]
+#[command(
+    version = "1.0",
+    about = "A headless Chip8 runner.",
+    long_about = "A program which runs a Chip8 ROM without a display for a fixed amount of frames."
+)]
+struct CliArgs {
+    /// The path to the ROM file to emulate, a zip or gzip archive of it, an Octo cartridge
+    /// .gif, or - for stdin.
+    rom_path: String,
+    #[command(flatten)]
+    emulator: EmulatorOptions,
+    /// The amount of 60 Hz frames to emulate, defaults to 600 or to the length of the movie.
+    #[arg(long)]
+    frames: Option<u64>,
+    /// Records the emulator's sound output to the given WAV file.
+    #[arg(long)]
+    record_audio: Option<String>,
+    /// Saves a screenshot after the given frame was emulated, can be repeated.
+    #[arg(long)]
+    screenshot_at_frame: Vec<u64>,
+    /// The directory where screenshots are saved as frame-<N>.<format>.
+    #[arg(long, default_value = ".")]
+    output_dir: PathBuf,
+    /// The screenshot image format: png, ppm or pbm.
+    #[arg(long, default_value = "png")]
+    screenshot_format: ImageFormat,
+    /// How many times larger than the Chip8 display screenshots are.
+    #[arg(long, default_value_t = 8, value_parser = clap::value_parser!(u32).range(1..))]
+    screenshot_scale: u32,
+    /// The color palette of screenshots: classic, amber, lcd, high-contrast, octo or BACKGROUND,PIXEL hex colors.
+    #[arg(long, default_value = "classic")]
+    palette: Palette,
+    /// Records all emulated frames into an animated .gif or .png (APNG) file.
+    #[arg(long)]
+    record_animation: Option<PathBuf>,
+    /// How many times larger than the Chip8 display recordings are.
+    #[arg(long, default_value_t = 4, value_parser = clap::value_parser!(u32).range(1..))]
+    animation_scale: u32,
+    /// Records the settings of the run into an input movie file, which replays it exactly.
+    #[arg(long, conflicts_with = "play_movie")]
+    record_movie: Option<PathBuf>,
+    /// Plays the key presses of an input movie back.
+    #[arg(long)]
+    play_movie: Option<PathBuf>,
+    /// Writes a trace of the executed instructions, with the registers they changed, to the file.
+    #[arg(long)]
+    trace: Option<PathBuf>,
+    /// The trace format: text (one line per instruction) or binary.
+    #[arg(long, default_value = "text")]
+    trace_format: TraceFormat,
+    /// Traces only the instructions at these addresses, such as 200-2FF.
+    #[arg(long)]
+    trace_addresses: Option<AddressRange>,
+    /// Traces only these instruction types, a comma separated list such as Draw,Call.
+    #[arg(long, value_delimiter = ',')]
+    trace_only: Vec<String>,
+    /// Profiles the executed instructions and writes the hot spots to the file when the
+    /// emulation ends.
+    #[arg(long)]
+    profile: Option<PathBuf>,
+    /// The profile format: report, or folded stacks for flame graph tools.
+    #[arg(long, default_value = "report")]
+    profile_format: ProfileFormat,
+}
+
+fn main() -> Result<(), anyhow::Error> {
+    env_logger::init();
+    let args = CliArgs::parse();
+
+    let rom_file = open_rom(Path::new(&args.rom_path))?;
+
+    let mut emulator = Emulator::new();
+    args.emulator.apply(&mut emulator)?;
+    emulator.load_rom(rom_file.reader)?;
+    args.emulator.load_database()?.configure(
+        &mut emulator,
+        &rom_file.settings,
+        args.emulator.overrides(),
+    );
+
+    let movie = match &args.play_movie {
+        Some(path) => {
+            let movie = Movie::load(path)?;
+            movie.header.apply(&mut emulator)?;
+            Some(movie)
+        }
+        None => None,
+    };
+    let frames = args
+        .frames
+        .unwrap_or_else(|| movie.as_ref().map_or(600, Movie::length));
+    let mut movie_player = movie.map(MoviePlayer::new);
+    let mut movie_recorder = match &args.record_movie {
+        Some(path) => Some(MovieRecorder::create(
+            path,
+            MovieHeader::for_emulator(&emulator),
+        )?),
+        None => None,
+    };
+
+    let mut audio_recorder = match &args.record_audio {
+        Some(path) => Some(WavRecorder::new(
+            BufWriter::new(File::create(path)?),
+            DEFAULT_SAMPLE_RATE,
+        )?),
+        None => None,
+    };
+
+    let mut animation_recorder = match &args.record_animation {
+        Some(path) => {
+            let format = AnimationFormat::from_path(path).ok_or_else(|| {
+                anyhow!(
+                    "unknown animation format of {}, expected .gif or .png",
+                    path.display()
+                )
+            })?;
+            Some(AnimationRecorder::new(
+                format,
+                args.animation_scale as usize,
+                args.palette,
+            ))
+        }
+        None => None,
+    };
+
+    if let Some(path) = &args.trace {
+        let filter = TraceFilter::new(args.trace_addresses, &args.trace_only)?;
+        emulator.set_trace(Some(TraceWriter::create(path, args.trace_format, filter)?));
+    }
+
+    if args.profile.is_some() {
+        emulator.set_profiler(Some(Profiler::new()));
+    }
+
+    let result = run_frames(
+        &mut emulator,
+        frames,
+        |emulator| {
+            before_frame(
+                emulator,
+                movie_player.as_mut(),
+                movie_recorder.as_mut(),
+                audio_recorder.as_mut(),
+            )
+        },
+        |emulator, frame| {
+            if let Some(recorder) = animation_recorder.as_mut() {
+                recorder.record_frame(emulator.get_display_buffer());
+            }
+            if args.screenshot_at_frame.contains(&frame) {
+                let path = args.output_dir.join(format!(
+                    "frame-{frame}.{}",
+                    args.screenshot_format.extension()
+                ));
+                save_screenshot(
+                    &path,
+                    emulator.get_display_buffer(),
+                    args.screenshot_format,
+                    args.screenshot_scale as usize,
+                    &args.palette,
+                )?;
+                info!("Saved screenshot of frame {frame} to {}", path.display());
+            }
+            Ok(())
+        },
+    );
+
+    // the recordings, the trace and the profile are saved even when the ROM failed
+    if let Some(recorder) = audio_recorder {
+        recorder.finish()?;
+    }
+    if let Some(recorder) = movie_recorder {
+        let frames = recorder.frames_recorded();
+        recorder.finish()?;
+        info!("Recorded {frames} frames of input");
+    }
+    if let (Some(recorder), Some(path)) = (animation_recorder, &args.record_animation) {
+        recorder.save(path)?;
+        info!(
+            "Saved {} distinct frames to {}",
+            recorder.frame_count(),
+            path.display()
+        );
+    }
+
+    if let (Some(trace), Some(path)) = (emulator.take_trace(), &args.trace) {
+        let records = trace.finish()?;
+        info!("Traced {records} instructions to {}", path.display());
+    }
+    if let (Some(profiler), Some(path)) = (emulator.take_profiler(), &args.profile) {
+        profiler.save(path, args.profile_format)?;
+        info!("Saved the profile to {}", path.display());
+    }
+
+    result?;
+    info!("Emulated {frames} frames.");
+    Ok(())
+}
+
+/// Runs the frames one 60 Hz tick at a time through the core's run loop, like the other
+/// frontends, and passes the number of each finished frame to `after_frame`.
+fn run_frames<E: From<Error>>(
+    emulator: &mut Emulator,
+    frames: u64,
+    mut before_frame: impl FnMut(&mut Emulator) -> Result<(), E>,
+    mut after_frame: impl FnMut(&mut Emulator, u64) -> Result<(), E>,
+) -> Result<(), E> {
+    let mut run_loop = RunLoop::default();
+    let mut frame = 0;
+    while frame < frames {
+        run_loop.run(emulator, &mut before_frame, |emulator| {
+            frame += 1;
+            after_frame(emulator, frame)
+        })?;
+    }
+    Ok(())
+}
+
+/// Feeds the movie's input to a frame and records its input and sound before it runs, while
+/// the sound timer still holds the frame's value.
+fn before_frame<W: Write + Seek>(
+    emulator: &mut Emulator,
+    movie_player: Option<&mut MoviePlayer>,
+    movie_recorder: Option<&mut MovieRecorder<BufWriter<File>>>,
+    audio_recorder: Option<&mut WavRecorder<W>>,
+) -> Result<(), anyhow::Error> {
+    if let Some(movie_frame) = movie_player.and_then(MoviePlayer::next_frame) {
+        movie_frame.apply(emulator)?;
+    }
+    if let Some(recorder) = movie_recorder {
+        recorder.record_frame(emulator.key_pressed())?;
+    }
+    if let Some(recorder) = audio_recorder {
+        recorder.record_frame(emulator.should_beep())?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chip8_core::audio::samples_for_frame;
+    use std::io::Cursor;
+
+    /// A ROM which beeps for a single frame: V0 = 1, ST = V0, then loops.
+    const SHORT_BEEP: [u8; 6] = [0x60, 0x01, 0xF0, 0x18, 0x12, 0x04];
+
+    fn beep_samples(recorder: WavRecorder<Cursor<Vec<u8>>>) -> usize {
+        let wav = recorder.finish().expect("Failed to finish").into_inner();
+        wav[44..]
+            .chunks(2)
+            .filter(|sample| sample != &[0, 0])
+            .count()
+    }
+
+    fn beeping_emulator() -> Emulator {
+        let mut emulator = Emulator::new();
+        emulator.load_rom(&SHORT_BEEP[..]).expect("Failed to load");
+        emulator
+    }
+
+    #[test]
+    fn test_short_beep_is_recorded_like_the_run_loop() {
+        let mut emulator = beeping_emulator();
+        let mut recorder = WavRecorder::new(Cursor::new(Vec::new()), DEFAULT_SAMPLE_RATE)
+            .expect("Failed to create");
+        run_frames(
+            &mut emulator,
+            3,
+            |emulator| before_frame(emulator, None, None, Some(&mut recorder)),
+            |_, _| Ok(()),
+        )
+        .expect("Failed to run");
+        let headless = beep_samples(recorder);
+
+        // the graphical frontends record the sound in the run loop's before_frame
+        let mut emulator = beeping_emulator();
+        let mut recorder = WavRecorder::new(Cursor::new(Vec::new()), DEFAULT_SAMPLE_RATE)
+            .expect("Failed to create");
+        let mut run_loop = RunLoop::default();
+        for _ in 0..3 {
+            run_loop
+                .run(
+                    &mut emulator,
+                    |emulator| recorder.record_frame(emulator.should_beep()),
+                    |_| Ok(()),
+                )
+                .expect("Failed to run");
+        }
+        let frontend = beep_samples(recorder);
+
+        assert_eq!(headless, frontend);
+        assert_eq!(headless as u64, samples_for_frame(1, DEFAULT_SAMPLE_RATE));
+    }
+}
@@ -0,0 +1,28 @@
+#![no_std]
+
+use chip8_core::emulator::Emulator;
+use chip8_core::random::RandomMode;
+use core::panic::PanicInfo;
+
+/// Clears the screen, draws the font's 0 in the top left corner and loops forever.
+const ROM: [u8; 8] = [0x00, 0xE0, 0xA0, 0xF0, 0xD0, 0x05, 0x12, 0x06];
+
+/// Emulates the frames and returns the amount of lit pixels, or -1 if the emulation failed.
+#[no_mangle]
+pub extern "C" fn chip8_run_frames(frames: u32) -> i32 {
+    let mut emulator = Emulator::with_seed(RandomMode::XorShift, 1);
+    if emulator.load_rom(&ROM[..]).is_err() {
+        return -1;
+    }
+    for _ in 0..frames {
+        if emulator.run_frame().is_err() {
+            return -1;
+        }
+    }
+    emulator.get_display_buffer().lit_pixels().count() as i32
+}
+
+#[panic_handler]
+fn panic(_info: &PanicInfo) -> ! {
+    loop {}
+}
@@ -1,23 +1,15 @@
+use chip8_core::audio::SquareWave;
 use sdl2::audio::AudioCallback;
 
-pub struct SquareWave {
-    pub phase_inc: f32,
-    pub phase: f32,
-    pub volume: f32,
+/// Plays the emulator's square wave through SDL's audio callback.
+pub struct SdlSquareWave {
+    pub wave: SquareWave,
 }
 
-impl AudioCallback for SquareWave {
+impl AudioCallback for SdlSquareWave {
     type Channel = f32;
 
     fn callback(&mut self, out: &mut [f32]) {
-        // Generate a square wave
-        for x in out.iter_mut() {
-            *x = if self.phase <= 0.5 {
-                self.volume
-            } else {
-                -self.volume
-            };
-            self.phase = (self.phase + self.phase_inc) % 1.0;
-        }
+        self.wave.fill(out);
     }
 }
@@ -1,23 +1,63 @@
-use crate::{BACKGROUND_COLOR, PIXEL_COLOR};
 use anyhow::anyhow;
-use chip8_core::display::{DISPLAY_HEIGHT, DISPLAY_WIDTH};
+use chip8_core::display::{DirtyRect, DISPLAY_HEIGHT, DISPLAY_WIDTH};
+use chip8_core::font::{glyph, GLYPH_HEIGHT, GLYPH_WIDTH};
+use chip8_core::overlay::OverlayText;
+use chip8_core::palette::Palette;
 use log::error;
+use sdl2::pixels::{Color, PixelFormatEnum};
 use sdl2::rect::Rect;
-use sdl2::render::WindowCanvas;
+use sdl2::render::{Texture, WindowCanvas};
+use sdl2::video::FullscreenType;
 use sdl2::Sdl;
 
+/// The amount of bytes used by one pixel in the framebuffer texture.
+const BYTES_PER_PIXEL: usize = 3;
+
+/// The title of the window without an indicator.
+const TITLE: &str = "Chip8 Emulator by nuculabs.dev";
+
+/// The state of the emulation shown in the window title. Fast-forwarding also draws arrows in
+/// the top right corner of the display, pausing leaves it to the overlay's banner.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Indicator {
+    Paused,
+    /// Fast-forwarding at the amount of frames per tick.
+    FastForward(u32),
+}
+
 /// SDL2 display module for the Chip8 emulator.
 pub struct SdlDisplay {
     canvas: WindowCanvas,
+    /// Streaming texture holding the Chip8 framebuffer at its native resolution.
+    texture: Texture,
+    /// Pixel data uploaded to the texture once per frame.
+    pixels: [u8; DISPLAY_WIDTH * DISPLAY_HEIGHT * BYTES_PER_PIXEL],
+    /// The colors used to render the display.
+    palette: Palette,
+    indicator: Option<Indicator>,
+    overlay: OverlayText,
 }
 
 impl SdlDisplay {
-    pub fn new(sdl_context: &Sdl) -> Result<Self, anyhow::Error> {
+    /// Creates a resizable window which is `scale` times larger than the Chip8 display.
+    pub fn new(
+        sdl_context: &Sdl,
+        scale: u32,
+        fullscreen: bool,
+        palette: Palette,
+    ) -> Result<Self, anyhow::Error> {
         let video_subsystem = sdl_context.video().map_err(|s| anyhow!(s))?;
 
-        let window = video_subsystem
-            .window("Chip8 Emulator by nuculabs.dev", 816, 648)
-            .vulkan()
+        let mut window_builder = video_subsystem.window(
+            TITLE,
+            DISPLAY_WIDTH as u32 * scale,
+            DISPLAY_HEIGHT as u32 * scale,
+        );
+        window_builder.vulkan().resizable().position_centered();
+        if fullscreen {
+            window_builder.fullscreen_desktop();
+        }
+        let window = window_builder
             .build()
             .map_err(|e| e.to_string())
             .map_err(|s| anyhow!(s))?;
@@ -28,42 +68,215 @@ impl SdlDisplay {
             .map_err(|e| e.to_string())
             .map_err(|s| anyhow!(s))?;
 
-        Ok(SdlDisplay { canvas })
+        let texture = canvas
+            .texture_creator()
+            .create_texture_streaming(
+                PixelFormatEnum::RGB24,
+                DISPLAY_WIDTH as u32,
+                DISPLAY_HEIGHT as u32,
+            )
+            .map_err(|e| e.to_string())
+            .map_err(|s| anyhow!(s))?;
+
+        Ok(SdlDisplay {
+            canvas,
+            texture,
+            pixels: [0; DISPLAY_WIDTH * DISPLAY_HEIGHT * BYTES_PER_PIXEL],
+            palette,
+            indicator: None,
+            overlay: OverlayText::default(),
+        })
+    }
+
+    /// Shows the indicator on the display and in the window title from the next render on.
+    pub fn set_indicator(&mut self, indicator: Option<Indicator>) {
+        self.indicator = indicator;
+        let title = match indicator {
+            Some(Indicator::Paused) => format!("{TITLE} [PAUSED]"),
+            Some(Indicator::FastForward(speed)) => format!("{TITLE} [FAST x{speed}]"),
+            None => TITLE.to_string(),
+        };
+        if let Err(error) = self.canvas.window_mut().set_title(&title) {
+            error!("{}", error)
+        }
+    }
+
+    /// Changes the colors of the display from the next render on.
+    pub fn set_palette(&mut self, palette: Palette) {
+        self.palette = palette;
+    }
+
+    /// Draws the overlay over the display from the next render on.
+    pub fn set_overlay(&mut self, overlay: OverlayText) {
+        self.overlay = overlay;
     }
 
     pub fn clear(&mut self) {
-        self.canvas.set_draw_color(BACKGROUND_COLOR);
+        let background = self.palette.background;
+        self.canvas
+            .set_draw_color(Color::RGB(background.r, background.g, background.b));
         self.canvas.clear();
     }
 
-    pub fn render(&mut self, display_data: &[bool; DISPLAY_WIDTH * DISPLAY_HEIGHT]) {
-        for row in 0..32 {
-            for column in 0..64 {
-                if display_data[row * DISPLAY_WIDTH + column] {
-                    self.canvas.set_draw_color(PIXEL_COLOR);
-                    let result = self.canvas.fill_rect(Rect::new(
-                        column as i32 * 12 + 24,
-                        row as i32 * 18 + 18,
-                        12,
-                        18,
-                    ));
-                    if let Err(error_message) = result {
-                        error!("{}", error_message)
-                    }
-                } else {
-                    self.canvas.set_draw_color(BACKGROUND_COLOR);
-                    let result = self.canvas.fill_rect(Rect::new(
-                        column as i32 * 12 + 24,
-                        row as i32 * 18 + 18,
-                        12,
-                        18,
-                    ));
-                    if let Err(error_message) = result {
-                        error!("{}", error_message)
+    /// Switches the window between fullscreen and windowed mode.
+    pub fn toggle_fullscreen(&mut self) {
+        let window = self.canvas.window_mut();
+        let fullscreen_type = match window.fullscreen_state() {
+            FullscreenType::Off => FullscreenType::Desktop,
+            _ => FullscreenType::Off,
+        };
+        if let Err(error_message) = window.set_fullscreen(fullscreen_type) {
+            error!("{}", error_message)
+        }
+    }
+
+    /// Computes the largest integer scaled area that fits in the window, centered so
+    /// the remaining space is letterboxed.
+    fn viewport(&self) -> Rect {
+        let (width, height) = self
+            .canvas
+            .output_size()
+            .unwrap_or((DISPLAY_WIDTH as u32, DISPLAY_HEIGHT as u32));
+        let scale = (width / DISPLAY_WIDTH as u32)
+            .min(height / DISPLAY_HEIGHT as u32)
+            .max(1);
+        let viewport_width = DISPLAY_WIDTH as u32 * scale;
+        let viewport_height = DISPLAY_HEIGHT as u32 * scale;
+        Rect::new(
+            (width as i32 - viewport_width as i32) / 2,
+            (height as i32 - viewport_height as i32) / 2,
+            viewport_width,
+            viewport_height,
+        )
+    }
+
+    /// Renders the pixel intensities produced by the display filter, refreshing only
+    /// the pixels inside the changed region.
+    pub fn render(
+        &mut self,
+        intensities: &[u8; DISPLAY_WIDTH * DISPLAY_HEIGHT],
+        region: DirtyRect,
+    ) {
+        for y in region.y..region.y + region.height {
+            for x in region.x..region.x + region.width {
+                let index = y * DISPLAY_WIDTH + x;
+                let color = self.palette.blend(intensities[index]);
+                let offset = index * BYTES_PER_PIXEL;
+                self.pixels[offset] = color.r;
+                self.pixels[offset + 1] = color.g;
+                self.pixels[offset + 2] = color.b;
+            }
+        }
+        if let Err(error_message) =
+            self.texture
+                .update(None, &self.pixels, DISPLAY_WIDTH * BYTES_PER_PIXEL)
+        {
+            error!("{}", error_message)
+        }
+
+        let viewport = self.viewport();
+        self.clear();
+        if let Err(error_message) = self.canvas.copy(&self.texture, None, viewport) {
+            error!("{}", error_message)
+        }
+        if let Some(Indicator::FastForward(_)) = self.indicator {
+            self.draw_fast_forward(viewport);
+        }
+        self.draw_overlay(viewport);
+        self.canvas.present()
+    }
+
+    /// Draws the statistics in the top left corner, the message in the bottom left corner and
+    /// the banner in the middle of the display.
+    fn draw_overlay(&mut self, viewport: Rect) {
+        let scale = (viewport.width() / DISPLAY_WIDTH as u32).max(1) as i32;
+        let unit = (scale / 4).max(1);
+        let overlay = self.overlay.clone();
+        if let Some(stats) = &overlay.stats {
+            self.draw_text(stats, viewport.left(), viewport.top(), unit);
+        }
+        if let Some(message) = &overlay.message {
+            let height = (GLYPH_HEIGHT as i32 + 2) * unit;
+            self.draw_text(message, viewport.left(), viewport.bottom() - height, unit);
+        }
+        if let Some(banner) = overlay.banner {
+            let unit = (scale / 2).max(1);
+            let width = text_width(banner, unit);
+            let height = (GLYPH_HEIGHT as i32 + 2) * unit;
+            self.draw_text(
+                banner,
+                viewport.center().x() - width / 2,
+                viewport.center().y() - height / 2,
+                unit,
+            );
+        }
+    }
+
+    /// Draws the text with the core's font over a box of the background color, `unit` window
+    /// pixels per font pixel.
+    fn draw_text(&mut self, text: &str, x: i32, y: i32, unit: i32) {
+        let background = self.palette.background;
+        let height = (GLYPH_HEIGHT as i32 + 2) * unit;
+        self.canvas
+            .set_draw_color(Color::RGB(background.r, background.g, background.b));
+        if let Err(error_message) = self.canvas.fill_rect(Rect::new(
+            x,
+            y,
+            text_width(text, unit) as u32,
+            height as u32,
+        )) {
+            error!("{}", error_message)
+        }
+
+        let advance = (GLYPH_WIDTH as i32 + 1) * unit;
+        let mut rects = Vec::new();
+        for (index, character) in text.chars().enumerate() {
+            for (row, bits) in glyph(character).iter().enumerate() {
+                for column in 0..GLYPH_WIDTH {
+                    if bits & (0x80 >> column) != 0 {
+                        rects.push(Rect::new(
+                            x + unit + index as i32 * advance + column as i32 * unit,
+                            y + unit + row as i32 * unit,
+                            unit as u32,
+                            unit as u32,
+                        ));
                     }
                 }
             }
         }
-        self.canvas.present()
+        let pixel = self.palette.pixel;
+        self.canvas.set_draw_color(Color::RGB(pixel.r, pixel.g, pixel.b));
+        if let Err(error_message) = self.canvas.fill_rects(&rects) {
+            error!("{}", error_message)
+        }
     }
+
+    /// Draws fast-forward arrows, 5 Chip8 pixels high, in the top right corner.
+    fn draw_fast_forward(&mut self, viewport: Rect) {
+        let unit = (viewport.width() / DISPLAY_WIDTH as u32).max(1) as i32;
+        let right = viewport.right() - unit;
+        let top = viewport.top() + unit;
+        // two arrows of columns getting shorter towards their tips
+        let rects: Vec<Rect> = (0..6)
+            .map(|column| {
+                let arrow_column = column % 3;
+                Rect::new(
+                    right + (column - 6) * unit,
+                    top + arrow_column * unit,
+                    unit as u32,
+                    (5 - 2 * arrow_column) as u32 * unit as u32,
+                )
+            })
+            .collect();
+        let pixel = self.palette.pixel;
+        self.canvas.set_draw_color(Color::RGB(pixel.r, pixel.g, pixel.b));
+        if let Err(error_message) = self.canvas.fill_rects(&rects) {
+            error!("{}", error_message)
+        }
+    }
+}
+
+/// Returns the width in window pixels of the text drawn by [`SdlDisplay::draw_text`].
+fn text_width(text: &str, unit: i32) -> i32 {
+    (text.chars().count() as i32 * (GLYPH_WIDTH as i32 + 1) + 1) * unit
 }
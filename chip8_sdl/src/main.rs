@@ -1,23 +1,36 @@
 mod audio;
 mod display;
 
-use crate::audio::SquareWave;
-use crate::display::SdlDisplay;
+use crate::audio::SdlSquareWave;
+use crate::display::{Indicator, SdlDisplay};
 use anyhow::anyhow;
 use clap::Parser;
+use chip8_core::audio::{SquareWave, WavRecorder, BEEP_FREQUENCY, BEEP_VOLUME, DEFAULT_SAMPLE_RATE};
+use chip8_core::display::DirtyRect;
 use chip8_core::emulator::{Emulator, tick};
-use chip8_core::read::StdFileReader;
+use chip8_core::filter::{DisplayFilter, FilterMode};
+use chip8_core::movie::{Movie, MovieHeader, MoviePlayer, MovieRecorder, Reset};
+use chip8_core::options::EmulatorOptions;
+use chip8_core::command::Command;
+use chip8_core::database::RomDatabase;
+use chip8_core::overlay::{Overlay, OverlayText};
+use chip8_core::palette::{Palette, PaletteChoice, Rgb};
+use chip8_core::profile::{ProfileFormat, Profiler};
+use chip8_core::run::{RunLoop, DEFAULT_FAST_FORWARD_SPEED};
+use chip8_core::recording::{toggle_recording, AnimationFormat, AnimationRecorder};
+use chip8_core::screenshot::{save_screenshot, timestamped_path, ImageFormat};
+use chip8_core::read::open_rom;
+use chip8_core::rom::{KeyHints, RomSettings};
+use log::{info, warn};
 use sdl2::audio::AudioSpecDesired;
 use sdl2::event::Event;
 use sdl2::keyboard::Keycode;
-use sdl2::pixels::Color;
 use std::fs::File;
+use std::io::BufWriter;
+use std::path::{Path, PathBuf};
 use std::thread::sleep;
 use std::time::{Duration};
 
-const BACKGROUND_COLOR: Color = Color::RGB(0, 0, 0);
-const PIXEL_COLOR: Color = Color::RGB(0, 255, 0);
-
 #[derive(Parser, Debug)]
 #[command(
     version = "1.0",
@@ -25,95 +38,482 @@ const PIXEL_COLOR: Color = Color::RGB(0, 255, 0);
     long_about = "A program which emulates the Chip8 system."
 )]
 struct CliArgs {
-    /// The path to the ROM file to emulate.
+    /// The path to the ROM file to emulate, a zip or gzip archive of it, an Octo cartridge
+    /// .gif, or - for stdin.
     rom_path: String,
+    #[command(flatten)]
+    emulator: EmulatorOptions,
+    /// Records the emulator's sound output to the given WAV file.
+    #[arg(long)]
+    record_audio: Option<String>,
+    /// How many times larger than the 64x32 Chip8 display the window is.
+    #[arg(long, default_value_t = 12, value_parser = clap::value_parser!(u32).range(1..))]
+    scale: u32,
+    /// Starts the emulator in fullscreen mode, F11 toggles it at runtime.
+    #[arg(long)]
+    fullscreen: bool,
+    /// The color palette: classic, amber, lcd, high-contrast, octo or BACKGROUND,PIXEL hex colors.
+    /// Defaults to the ROM's `.palette` hint file when present.
+    #[arg(long)]
+    palette: Option<Palette>,
+    /// Overrides the palette's background color, e.g. #000000.
+    #[arg(long)]
+    background_color: Option<Rgb>,
+    /// Overrides the palette's pixel color, e.g. #00FF00.
+    #[arg(long)]
+    pixel_color: Option<Rgb>,
+    /// Reduces flicker: off, or (OR the last two frames), phosphor or phosphor:N (fade over N frames).
+    #[arg(long, default_value = "off")]
+    filter: FilterMode,
+    /// The directory where F12 screenshots and F10 recordings are saved.
+    #[arg(long, default_value = ".")]
+    output_dir: PathBuf,
+    /// The screenshot image format: png, ppm or pbm.
+    #[arg(long, default_value = "png")]
+    screenshot_format: ImageFormat,
+    /// How many times larger than the Chip8 display screenshots are.
+    #[arg(long, default_value_t = 8, value_parser = clap::value_parser!(u32).range(1..))]
+    screenshot_scale: u32,
+    /// The F10 recording format: gif or apng.
+    #[arg(long, default_value = "gif")]
+    animation_format: AnimationFormat,
+    /// How many times larger than the Chip8 display recordings are.
+    #[arg(long, default_value_t = 4, value_parser = clap::value_parser!(u32).range(1..))]
+    animation_scale: u32,
+    /// How many frames run per tick while Tab is held.
+    #[arg(long, default_value_t = DEFAULT_FAST_FORWARD_SPEED, value_parser = clap::value_parser!(u32).range(2..))]
+    fast_forward_speed: u32,
+    /// Shows the frames and instructions per second and the quirks preset from the start, F3
+    /// toggles them.
+    #[arg(long)]
+    overlay: bool,
+    /// Records the key presses into an input movie file, which replays the session exactly.
+    #[arg(long, conflicts_with = "play_movie")]
+    record_movie: Option<PathBuf>,
+    /// Plays an input movie back instead of reading the keyboard.
+    #[arg(long)]
+    play_movie: Option<PathBuf>,
+    /// Profiles the executed instructions and writes the hot spots to the file when the
+    /// emulation ends.
+    #[arg(long)]
+    profile: Option<PathBuf>,
+    /// The profile format: report, or folded stacks for flame graph tools.
+    #[arg(long, default_value = "report")]
+    profile_format: ProfileFormat,
 }
 
+/// Finishes the audio, animation and movie recordings, if any.
+fn finish_recordings(
+    audio_recorder: Option<WavRecorder<BufWriter<File>>>,
+    animation_recorder: &mut Option<AnimationRecorder>,
+    movie_recorder: Option<MovieRecorder<BufWriter<File>>>,
+    args: &CliArgs,
+    palette: Palette,
+) -> Result<(), anyhow::Error> {
+    if let Some(recorder) = audio_recorder {
+        recorder.finish()?;
+    }
+    if let Some(recorder) = movie_recorder {
+        let frames = recorder.frames_recorded();
+        recorder.finish()?;
+        info!("Recorded {frames} frames of input");
+    }
+    if animation_recorder.is_some() {
+        if let Err(error) = toggle_recording(
+            animation_recorder,
+            &args.output_dir,
+            args.animation_format,
+            args.animation_scale as usize,
+            palette,
+        ) {
+            warn!("Could not save the recording: {error}");
+        }
+    }
+    Ok(())
+}
 
+/// Returns the frontend command bound to the key.
+fn command_for_key(keycode: Keycode) -> Option<Command> {
+    match keycode {
+        Keycode::ESCAPE => Some(Command::Quit),
+        Keycode::F3 => Some(Command::ToggleOverlay),
+        Keycode::F5 => Some(Command::SoftReset),
+        Keycode::F6 => Some(Command::HardReset),
+        Keycode::F7 => Some(Command::ReloadRom),
+        Keycode::P | Keycode::PAUSE => Some(Command::TogglePause),
+        Keycode::F8 => Some(Command::FrameAdvance),
+        Keycode::F9 => Some(Command::StepInstruction),
+        Keycode::F10 => Some(Command::ToggleRecording),
+        Keycode::F12 => Some(Command::Screenshot),
+        _ => None,
+    }
+}
+
+/// Loads the ROM of the command line into a new emulator configured for it.
+fn load_emulator(
+    args: &CliArgs,
+    database: &RomDatabase,
+) -> Result<(Emulator, RomSettings), anyhow::Error> {
+    let rom_file = open_rom(Path::new(&args.rom_path))?;
+    let mut emulator = Emulator::new();
+    args.emulator.apply(&mut emulator)?;
+    emulator.load_rom(rom_file.reader)?;
+    let settings =
+        database.configure(&mut emulator, &rom_file.settings, args.emulator.overrides());
+    Ok((emulator, settings))
+}
+
+/// Returns the palette of the command line, or else the one the ROM hints.
+fn resolve_palette(args: &CliArgs, rom_settings: &RomSettings) -> Result<Palette, anyhow::Error> {
+    PaletteChoice {
+        palette: args.palette,
+        background: args.background_color,
+        pixel: args.pixel_color,
+    }
+    .resolve(
+        Path::new(&args.rom_path),
+        rom_settings.palette,
+        Palette::default(),
+    )
+}
+
+/// Loads the ROM again, with the settings of the movie played or recorded if there is one,
+/// and returns the new emulator with the ROM's settings and palette.
+fn reload_emulator(
+    args: &CliArgs,
+    database: &RomDatabase,
+    movie_header: Option<MovieHeader>,
+) -> Result<(Emulator, RomSettings, Palette), anyhow::Error> {
+    let (mut emulator, rom_settings) = load_emulator(args, database)?;
+    // fails when the ROM changed, the movie would not replay
+    if let Some(header) = movie_header {
+        header.apply(&mut emulator)?;
+    }
+    let palette = resolve_palette(args, &rom_settings)?;
+    Ok((emulator, rom_settings, palette))
+}
+
+/// Returns the keypad key the ROM database or cartridge hints for an arrow key, space or enter.
+fn hinted_key(keycode: Keycode, key_hints: &KeyHints) -> Option<u8> {
+    match keycode {
+        Keycode::UP => key_hints.up,
+        Keycode::DOWN => key_hints.down,
+        Keycode::LEFT => key_hints.left,
+        Keycode::RIGHT => key_hints.right,
+        Keycode::SPACE => key_hints.a,
+        Keycode::RETURN => key_hints.b,
+        _ => None,
+    }
+}
 
 fn main() -> Result<(), anyhow::Error> {
     env_logger::init();
     let args = CliArgs::parse();
 
-    let file = File::open(&args.rom_path)?;
+    let database = args.emulator.load_database()?;
+    let (mut emulator, rom_settings) = load_emulator(&args, &database)?;
+    if args.profile.is_some() {
+        emulator.set_profiler(Some(Profiler::new()));
+    }
+    let mut key_hints = rom_settings.keys.unwrap_or_default();
+
+    let mut palette = resolve_palette(&args, &rom_settings)?;
+    let mut display_filter = DisplayFilter::new(args.filter);
 
     let sdl_context = sdl2::init().map_err(|s| anyhow!(s))?;
-    let mut sdl_display_backend: SdlDisplay = SdlDisplay::new(&sdl_context)?;
+    let mut sdl_display_backend: SdlDisplay = SdlDisplay::new(&sdl_context, args.scale, args.fullscreen, palette)?;
 
     let mut event_pump = sdl_context.event_pump().map_err(|s| anyhow!(s))?;
 
     let audio_subsystem = sdl_context.audio().unwrap();
     let desired_spec = AudioSpecDesired {
-        freq: Some(44100),
+        freq: Some(DEFAULT_SAMPLE_RATE as i32),
         channels: Some(1), // mono
         samples: None,     // default sample size
     };
     let audio_device = audio_subsystem
         .open_playback(None, &desired_spec, |spec| {
             // initialize the audio callback
-            SquareWave {
-                phase_inc: 440.0 / spec.freq as f32,
-                phase: 25.0,
-                volume: 0.25,
+            SdlSquareWave {
+                wave: SquareWave::new(BEEP_FREQUENCY, spec.freq as u32, BEEP_VOLUME),
             }
         })
         .unwrap();
 
-    let mut emulator = Emulator::new();
-    emulator.load_rom(StdFileReader::new(file))?;
+    let mut audio_recorder = match &args.record_audio {
+        Some(path) => Some(WavRecorder::new(
+            BufWriter::new(File::create(path)?),
+            DEFAULT_SAMPLE_RATE,
+        )?),
+        None => None,
+    };
+
+    let mut movie_player = match &args.play_movie {
+        Some(path) => {
+            let player = MoviePlayer::new(Movie::load(path)?);
+            player.header().apply(&mut emulator)?;
+            Some(player)
+        }
+        None => None,
+    };
+    let mut movie_recorder = match &args.record_movie {
+        Some(path) => Some(MovieRecorder::create(path, MovieHeader::for_emulator(&emulator))?),
+        None => None,
+    };
 
     sdl_display_backend.clear();
 
+    let mut animation_recorder: Option<AnimationRecorder> = None;
+
     let mut last_tick_time = None;
-    loop {
+    // The window needs to be redrawn after it was resized or exposed.
+    let mut force_redraw = false;
+    let mut run_loop = RunLoop::new(args.fast_forward_speed);
+    let mut indicator = None;
+    let mut overlay = Overlay::new(args.overlay);
+    overlay.set_quirks(emulator.quirks());
+    let mut overlay_text = OverlayText::default();
+    // errors leave the loop with a value so the profile and recordings are still saved
+    let result: Result<(), anyhow::Error> = loop {
         if tick(&mut last_tick_time) {
-            emulator.handle_timers();
-
             let event = event_pump.poll_event();
+            let mut command = None;
             match event {
-                Some(Event::Quit { .. }) => {
-                    println!("Thank you for playing!");
-                    std::process::exit(0);
+                Some(Event::Quit { .. }) => command = Some(Command::Quit),
+                Some(Event::KeyDown {
+                    keycode: Some(Keycode::TAB),
+                    repeat: false,
+                    ..
+                }) => command = Some(Command::FastForward(true)),
+                Some(Event::KeyUp {
+                    keycode: Some(Keycode::TAB),
+                    ..
+                }) => command = Some(Command::FastForward(false)),
+                Some(Event::KeyDown { keycode: Some(keycode), .. }) => {
+                    if let Some(key_command) = command_for_key(keycode) {
+                        command = Some(key_command);
+                    } else {
+                        match keycode {
+                            Keycode::F11 => sdl_display_backend.toggle_fullscreen(),
+                            Keycode::NUM_1 => emulator.handle_input(Some(1)),
+                            Keycode::NUM_2 => emulator.handle_input(Some(2)),
+                            Keycode::NUM_3 => emulator.handle_input(Some(3)),
+                            Keycode::NUM_4 => emulator.handle_input(Some(0xC)),
+                            Keycode::Q => emulator.handle_input(Some(4)),
+                            Keycode::W => emulator.handle_input(Some(5)),
+                            Keycode::E => emulator.handle_input(Some(6)),
+                            Keycode::R => emulator.handle_input(Some(0xD)),
+                            Keycode::A => emulator.handle_input(Some(7)),
+                            Keycode::S => emulator.handle_input(Some(8)),
+                            Keycode::D => emulator.handle_input(Some(9)),
+                            Keycode::F => emulator.handle_input(Some(0xE)),
+                            Keycode::Z => emulator.handle_input(Some(0xA)),
+                            Keycode::X => emulator.handle_input(Some(0)),
+                            Keycode::C => emulator.handle_input(Some(0xB)),
+                            Keycode::V => emulator.handle_input(Some(0xF)),
+                            // the arrow keys, space and enter play the keys the ROM database or cartridge hints
+                            keycode => {
+                                if let Some(key) = hinted_key(keycode, &key_hints) {
+                                    emulator.handle_input(Some(key.into()))
+                                }
+                            }
+                        }
+                    }
                 }
-                Some(Event::KeyDown { keycode, .. }) => match keycode {
-                    Some(Keycode::ESCAPE) => {
-                        println!("Thank you for playing!");
-                        std::process::exit(0);
-                    },
-                    Some(Keycode::NUM_1) => emulator.handle_input(Some(1)),
-                    Some(Keycode::NUM_2) => emulator.handle_input(Some(2)),
-                    Some(Keycode::NUM_3) => emulator.handle_input(Some(3)),
-                    Some(Keycode::NUM_4) => emulator.handle_input(Some(0xC)),
-                    Some(Keycode::Q) => emulator.handle_input(Some(4)),
-                    Some(Keycode::W) => emulator.handle_input(Some(5)),
-                    Some(Keycode::E) => emulator.handle_input(Some(6)),
-                    Some(Keycode::R) => emulator.handle_input(Some(0xD)),
-                    Some(Keycode::A) => emulator.handle_input(Some(7)),
-                    Some(Keycode::S) => emulator.handle_input(Some(8)),
-                    Some(Keycode::D) => emulator.handle_input(Some(9)),
-                    Some(Keycode::F) => emulator.handle_input(Some(0xE)),
-                    Some(Keycode::Z) => emulator.handle_input(Some(0xA)),
-                    Some(Keycode::X) => emulator.handle_input(Some(0)),
-                    Some(Keycode::C) => emulator.handle_input(Some(0xB)),
-                    Some(Keycode::V) => emulator.handle_input(Some(0xF)),
-                    _ => {}
-                },
                 Some(Event::KeyUp { .. }) => emulator.handle_input(None),
+                Some(Event::Window { .. }) => force_redraw = true,
                 _ => {}
             }
 
-            if emulator.should_beep() {
+            if let Some(command) = command {
+                info!("Command: {}", command.description());
+                match command {
+                    Command::Quit => break Ok(()),
+                    Command::SoftReset => {
+                        emulator.soft_reset();
+                        display_filter.reset(emulator.get_display_buffer());
+                        if let Some(recorder) = movie_recorder.as_mut() {
+                            recorder.record_reset(Reset::Soft);
+                        }
+                        overlay.show_message("Soft reset");
+                    }
+                    Command::HardReset => {
+                        if let Err(error) = emulator.reset() {
+                            break Err(error.into());
+                        }
+                        display_filter.reset(emulator.get_display_buffer());
+                        if let Some(recorder) = movie_recorder.as_mut() {
+                            recorder.record_reset(Reset::Hard);
+                        }
+                        overlay.show_message("Hard reset");
+                    }
+                    // stdin cannot be read twice
+                    Command::ReloadRom if args.rom_path == "-" => {
+                        warn!("A ROM read from stdin cannot be reloaded")
+                    }
+                    Command::ReloadRom => {
+                        let movie_header = movie_recorder
+                            .as_ref()
+                            .map(MovieRecorder::header)
+                            .or(movie_player.as_ref().map(MoviePlayer::header))
+                            .copied();
+                        // a ROM which fails to load again leaves the running one alone
+                        match reload_emulator(&args, &database, movie_header) {
+                            Ok((mut reloaded, rom_settings, reloaded_palette)) => {
+                                // the profile goes on over the reloads of the same ROM
+                                reloaded.set_profiler(emulator.take_profiler());
+                                emulator = reloaded;
+                                display_filter.reset(emulator.get_display_buffer());
+                                // the same ROM loaded again replays like a hard reset
+                                if let Some(recorder) = movie_recorder.as_mut() {
+                                    recorder.record_reset(Reset::Hard);
+                                }
+                                key_hints = rom_settings.keys.unwrap_or_default();
+                                palette = reloaded_palette;
+                                sdl_display_backend.set_palette(palette);
+                                sdl_display_backend.clear();
+                                overlay.set_quirks(emulator.quirks());
+                                overlay.show_message("Reloaded the ROM");
+                            }
+                            Err(error) => {
+                                warn!("Could not reload the ROM: {error}");
+                                overlay.show_message(format!("Could not reload the ROM: {error}"));
+                            }
+                        }
+                    }
+                    Command::TogglePause => run_loop.toggle_pause(),
+                    Command::FrameAdvance => run_loop.advance_frame(),
+                    Command::StepInstruction => run_loop.step_instruction(),
+                    Command::FastForward(fast_forwarding) => {
+                        run_loop.set_fast_forward(fast_forwarding)
+                    }
+                    Command::Screenshot => {
+                        let path = timestamped_path(&args.output_dir, args.screenshot_format.extension());
+                        let message = match save_screenshot(
+                            &path,
+                            emulator.get_display_buffer(),
+                            args.screenshot_format,
+                            args.screenshot_scale as usize,
+                            &palette,
+                        ) {
+                            Ok(()) => format!("Saved screenshot to {}", path.display()),
+                            Err(error) => {
+                                warn!("Could not save the screenshot: {error}");
+                                format!("Could not save the screenshot: {error}")
+                            }
+                        };
+                        overlay.show_message(message);
+                    }
+                    Command::ToggleRecording => {
+                        let message = match toggle_recording(
+                            &mut animation_recorder,
+                            &args.output_dir,
+                            args.animation_format,
+                            args.animation_scale as usize,
+                            palette,
+                        ) {
+                            Ok(message) => message,
+                            Err(error) => {
+                                warn!("Could not save the recording: {error}");
+                                format!("Could not save the recording: {error}")
+                            }
+                        };
+                        overlay.show_message(message);
+                    }
+                    Command::ToggleOverlay => overlay.toggle_stats(),
+                }
+            }
+            // a paused emulator keeps rendering, so resets and window changes show
+            let run = match run_loop.run(
+                &mut emulator,
+                |emulator| -> Result<(), anyhow::Error> {
+                    // a playing movie overrides the keyboard
+                    if let Some(player) = movie_player.as_mut() {
+                        match player.next_frame() {
+                            Some(movie_frame) => movie_frame.apply(emulator)?,
+                            None => {
+                                info!("The movie is over, the keyboard is back in control");
+                                movie_player = None;
+                            }
+                        }
+                    }
+                    if let Some(recorder) = movie_recorder.as_mut() {
+                        recorder.record_frame(emulator.key_pressed())?;
+                    }
+                    if let Some(recorder) = audio_recorder.as_mut() {
+                        recorder.record_frame(emulator.should_beep())?;
+                    }
+                    Ok(())
+                },
+                |emulator| {
+                    // fading pixels change the whole display
+                    force_redraw |= display_filter.advance(emulator.get_display_buffer());
+                    if let Some(recorder) = animation_recorder.as_mut() {
+                        recorder.record_frame(emulator.get_display_buffer());
+                    }
+                    Ok(())
+                },
+            ) {
+                Ok(run) => run,
+                Err(error) => break Err(error),
+            };
+            // the draws of a frame stepped into show before it ends
+            if emulator.frame_position() != 0 {
+                display_filter.preview(emulator.get_display_buffer());
+            }
+            if emulator.should_beep() && !run_loop.is_paused() {
                 audio_device.resume();
             } else {
                 audio_device.pause();
             }
 
-            emulator.execute_and_fetch()?;
+            overlay.set_paused(run_loop.is_paused());
+            overlay.record_tick(run.frames, emulator.instructions_executed());
+            let text = overlay.text();
+            if text != overlay_text {
+                sdl_display_backend.set_overlay(text.clone());
+                overlay_text = text;
+                force_redraw = true;
+            }
+
+            let run_indicator = if run_loop.is_paused() {
+                Some(Indicator::Paused)
+            } else if run_loop.is_fast_forwarding() {
+                Some(Indicator::FastForward(run_loop.fast_forward_speed()))
+            } else {
+                None
+            };
+            if run_indicator != indicator {
+                indicator = run_indicator;
+                sdl_display_backend.set_indicator(indicator);
+                force_redraw = true;
+            }
 
-            // render
-            sdl_display_backend.render(&emulator.get_display_buffer());
+            // render only when the display changed
+            let dirty_region = match emulator.take_dirty_region() {
+                _ if force_redraw => Some(DirtyRect::FULL),
+                dirty_region => dirty_region,
+            };
+            if let Some(region) = dirty_region {
+                force_redraw = false;
+                sdl_display_backend.render(display_filter.intensities(), region);
+            }
         } else {
             sleep(Duration::from_millis(1));
         }
+    };
+    if let (Some(profiler), Some(path)) = (emulator.take_profiler(), &args.profile) {
+        profiler.save(path, args.profile_format)?;
+        info!("Saved the profile to {}", path.display());
     }
+    finish_recordings(
+        audio_recorder,
+        &mut animation_recorder,
+        movie_recorder,
+        &args,
+        palette,
+    )?;
+    result?;
+    info!("Thank you for playing!");
+    Ok(())
 }
@@ -0,0 +1,157 @@
+use chip8_core::bus::Bus;
+use chip8_core::emulator::Emulator;
+use chip8_core::movie::{Movie, MoviePlayer};
+use chip8_core::options::EmulatorOptions;
+use chip8_core::read::open_rom;
+use chip8_core::trace::{compare, load_trace, Divergence};
+use clap::Parser;
+use log::info;
+use std::path::{Path, PathBuf};
+use std::process::ExitCode;
+
+#[derive(Parser, Debug)]
+#[command(
+    version = "1.0",
+    about = "Compares Chip8 execution traces.",
+    long_about = "A program which runs a Chip8 ROM and compares its execution trace to a reference \
+                  trace, reporting the first instruction which differs."
+)]
+struct CliArgs {
+    /// The path to the ROM file to emulate, a zip or gzip archive of it or an Octo cartridge .gif.
+    rom_path: String,
+    /// The reference trace, text or binary, written by chip8_headless --trace or converted from
+    /// another emulator's trace.
+    reference: PathBuf,
+    #[command(flatten)]
+    emulator: EmulatorOptions,
+    /// Plays the key presses of an input movie back, with its seed, tick rate and quirks.
+    #[arg(long)]
+    play_movie: Option<PathBuf>,
+    /// Gives up when the reference trace goes on after this many 60 Hz frames.
+    #[arg(long, default_value_t = 36_000)]
+    max_frames: u64,
+}
+
+/// The amount of bytes of memory printed at I and at PC.
+const HEXDUMP_LENGTH: u16 = 16;
+
+/// Returns the bytes of memory from the address on in hex, such as `022A: F0 90 90`.
+fn hexdump(emulator: &Emulator, address: u16) -> String {
+    let bytes: Vec<String> = (0..HEXDUMP_LENGTH)
+        .map(|offset| address.wrapping_add(offset))
+        .map(|address| match emulator.bus().peek(address) {
+            Ok(byte) => format!("{byte:02X}"),
+            Err(_) => "--".to_string(),
+        })
+        .collect();
+    format!("{address:04X}: {}", bytes.join(" "))
+}
+
+/// Prints the registers, timers, call stack and key of the emulator and the memory at I and
+/// PC.
+fn print_state(emulator: &Emulator, frame: u64) {
+    let registers = emulator.registers();
+    let row = |range: std::ops::Range<usize>| {
+        range
+            .map(|register| format!("V{register:X}={:02X}", registers[register]))
+            .collect::<Vec<_>>()
+            .join(" ")
+    };
+    println!(
+        "  PC={:04X} I={:04X} DT={:02X} ST={:02X} frame {frame}, instruction {} of the frame",
+        emulator.program_counter(),
+        emulator.index_register(),
+        emulator.delay_timer(),
+        emulator.sound_timer(),
+        emulator.frame_position()
+    );
+    println!("  {}", row(0..8));
+    println!("  {}", row(8..16));
+    let stack: Vec<String> = emulator
+        .stack_frames()
+        .map(|address| format!("{address:04X}"))
+        .collect();
+    println!(
+        "  stack [{}], key {}",
+        stack.join(" "),
+        emulator
+            .key_pressed()
+            .map_or("none".to_string(), |key| format!("{key:X}"))
+    );
+    println!("  at I  {}", hexdump(emulator, emulator.index_register()));
+    println!("  at PC {}", hexdump(emulator, emulator.program_counter()));
+}
+
+fn main() -> Result<ExitCode, anyhow::Error> {
+    env_logger::init();
+    let args = CliArgs::parse();
+
+    let reference = load_trace(&args.reference)?;
+    info!("Comparing with {} reference instructions", reference.len());
+
+    let rom_file = open_rom(Path::new(&args.rom_path))?;
+    let mut emulator = Emulator::new();
+    args.emulator.apply(&mut emulator)?;
+    emulator.load_rom(rom_file.reader)?;
+    args.emulator.load_database()?.configure(
+        &mut emulator,
+        &rom_file.settings,
+        args.emulator.overrides(),
+    );
+
+    let mut movie_player = match &args.play_movie {
+        Some(path) => {
+            let movie = Movie::load(path)?;
+            movie.header.apply(&mut emulator)?;
+            Some(MoviePlayer::new(movie))
+        }
+        None => None,
+    };
+
+    let comparison = compare(&mut emulator, &reference, args.max_frames, |emulator| {
+        if let Some(movie_frame) = movie_player.as_mut().and_then(MoviePlayer::next_frame) {
+            movie_frame.apply(emulator)?;
+        }
+        Ok(())
+    })?;
+    let frame = comparison.frames;
+    match comparison.divergence {
+        None => {
+            println!(
+                "The traces match for {} instructions over {frame} frames",
+                reference.len()
+            );
+            return Ok(ExitCode::SUCCESS);
+        }
+        Some(Divergence::GaveUp { expected }) => {
+            println!(
+                "Gave up after {frame} frames, before reference instruction {}",
+                expected.number
+            );
+            return Ok(ExitCode::FAILURE);
+        }
+        Some(Divergence::Failed { expected, error }) => {
+            println!(
+                "The emulator stopped at instruction {}: {error}",
+                emulator.instructions_executed()
+            );
+            println!("  expected: {expected}");
+        }
+        Some(Divergence::Differs {
+            expected,
+            actual,
+            last_match,
+        }) => {
+            println!("The traces differ at instruction {}:", expected.number);
+            println!("  expected: {expected}");
+            println!("  actual:   {actual}");
+            match last_match {
+                Some(record) => println!("Last matching instruction:\n  {record}"),
+                None => println!("No instruction matched before"),
+            }
+            println!("State after the instruction:");
+        }
+    }
+    print_state(&emulator, frame);
+    Ok(ExitCode::FAILURE)
+}
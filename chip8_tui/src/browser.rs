@@ -0,0 +1,284 @@
+use chip8_core::database::RomDatabase;
+use chip8_core::emulator::MAX_ROM_SIZE;
+use chip8_core::error::Error;
+use chip8_core::read::{is_rom_path, open_rom, Reader};
+use log::warn;
+use ratatui::buffer::Buffer;
+use ratatui::layout::{Constraint, Layout, Rect};
+use ratatui::style::{Modifier, Style};
+use ratatui::text::Line;
+use ratatui::widgets::{Block, Borders, List, ListState, Paragraph, StatefulWidget, Widget, Wrap};
+use std::path::{Path, PathBuf};
+
+/// A ROM listed by the browser.
+#[derive(Debug, Clone)]
+pub struct RomEntry {
+    pub path: PathBuf,
+    /// The ROM database's title of the ROM, or its file name.
+    pub title: String,
+    /// What the ROM database knows about the ROM, one line per fact.
+    pub details: Vec<String>,
+}
+
+impl RomEntry {
+    fn new(path: PathBuf, database: &RomDatabase) -> Self {
+        let file_name = path
+            .file_name()
+            .map(|name| name.to_string_lossy().into_owned())
+            .unwrap_or_default();
+        let info = read_rom(&path).and_then(|rom| database.lookup_rom(rom));
+        let Some(info) = info else {
+            return RomEntry {
+                path,
+                title: file_name.clone(),
+                details: vec![file_name, "Not in the ROM database".to_string()],
+            };
+        };
+        let mut details = vec![file_name];
+        if !info.authors.is_empty() {
+            details.push(format!("By {}", info.authors.join(", ")));
+        }
+        if let Some(platform) = info.platform {
+            details.push(format!("For {}", platform.id()));
+        }
+        details.extend(info.description.clone());
+        RomEntry {
+            path,
+            title: info.title.clone(),
+            details,
+        }
+    }
+
+    /// Returns the movie path with the ROM's file stem appended, so that every ROM run from the
+    /// browser records its own movie: `run.movie` becomes `run-pong.movie` for `pong.ch8`.
+    pub fn movie_path(&self, movie_path: &Path) -> PathBuf {
+        let mut file_name = movie_path.file_stem().unwrap_or_default().to_os_string();
+        file_name.push("-");
+        file_name.push(self.path.file_stem().unwrap_or_default());
+        if let Some(extension) = movie_path.extension() {
+            file_name.push(".");
+            file_name.push(extension);
+        }
+        movie_path.with_file_name(file_name)
+    }
+}
+
+/// Returns the bytes of the ROM, or None if it cannot be read or does not fit in the memory.
+fn read_rom(path: &Path) -> Option<Vec<u8>> {
+    let mut reader = match open_rom(path) {
+        Ok(rom_file) => rom_file.reader,
+        Err(error) => {
+            warn!("Skipping {}: {error}", path.display());
+            return None;
+        }
+    };
+    let mut rom = Vec::new();
+    let mut buf = [0; 1024];
+    loop {
+        if rom.len() > MAX_ROM_SIZE {
+            let error = Error::RomTooLarge {
+                available: MAX_ROM_SIZE,
+            };
+            warn!("Skipping {}: {error}", path.display());
+            return None;
+        }
+        // one byte more than fits, so a large file or an archive bomb is not read to the end
+        let limit = (MAX_ROM_SIZE + 1 - rom.len()).min(buf.len());
+        match reader.read(&mut buf[..limit]) {
+            Ok(0) => return Some(rom),
+            Ok(amount) => rom.extend_from_slice(&buf[..amount]),
+            Err(error) => {
+                warn!("Skipping {}: {error}", path.display());
+                return None;
+            }
+        }
+    }
+}
+
+/// The launcher which lists the ROMs of a directory to pick one to run.
+pub struct RomBrowser {
+    directory: PathBuf,
+    entries: Vec<RomEntry>,
+    state: ListState,
+    /// Shown below the list, for example why the last ROM failed to run.
+    message: Option<String>,
+}
+
+impl RomBrowser {
+    /// Lists the ROMs in the directory, titled from the ROM database.
+    pub fn new(directory: &Path, database: &RomDatabase) -> Result<Self, anyhow::Error> {
+        let mut paths = Vec::new();
+        for entry in std::fs::read_dir(directory)? {
+            let path = entry?.path();
+            if path.is_file() && is_rom_path(&path) {
+                paths.push(path);
+            }
+        }
+        paths.sort();
+        let entries: Vec<RomEntry> = paths
+            .into_iter()
+            .map(|path| RomEntry::new(path, database))
+            .collect();
+        let mut state = ListState::default();
+        state.select((!entries.is_empty()).then_some(0));
+        Ok(RomBrowser {
+            directory: directory.to_path_buf(),
+            entries,
+            state,
+            message: None,
+        })
+    }
+
+    /// Returns the selected ROM.
+    pub fn selected(&self) -> Option<&RomEntry> {
+        self.entries.get(self.state.selected()?)
+    }
+
+    pub fn select_previous(&mut self) {
+        self.state.select_previous();
+    }
+
+    pub fn select_next(&mut self) {
+        if self
+            .state
+            .selected()
+            .is_some_and(|index| index + 1 < self.entries.len())
+        {
+            self.state.select_next();
+        }
+    }
+
+    pub fn set_message(&mut self, message: Option<String>) {
+        self.message = message;
+    }
+}
+
+impl Widget for &mut RomBrowser {
+    fn render(self, area: Rect, buf: &mut Buffer) {
+        let [list_area, details_area, help_area] = Layout::vertical([
+            Constraint::Min(3),
+            Constraint::Length(6),
+            Constraint::Length(1),
+        ])
+        .areas(area);
+
+        let list = List::new(self.entries.iter().map(|entry| entry.title.as_str()))
+            .block(
+                Block::default()
+                    .title(format!("ROMs in {}", self.directory.display()))
+                    .borders(Borders::ALL),
+            )
+            .highlight_style(Style::new().add_modifier(Modifier::REVERSED))
+            .highlight_symbol("> ");
+        StatefulWidget::render(list, list_area, buf, &mut self.state);
+
+        let mut details: Vec<Line> = match self.selected() {
+            Some(entry) => entry
+                .details
+                .iter()
+                .map(|line| Line::raw(line.as_str()))
+                .collect(),
+            None => vec![Line::raw("No ROMs found")],
+        };
+        if let Some(message) = &self.message {
+            details.insert(0, Line::styled(message.as_str(), Modifier::BOLD));
+        }
+        Paragraph::new(details)
+            .wrap(Wrap { trim: true })
+            .block(Block::default().borders(Borders::ALL))
+            .render(details_area, buf);
+
+        Line::raw("Up/Down: select  Enter: run, Esc returns here  Esc/q: quit")
+            .render(help_area, buf);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Creates an empty directory for a test.
+    fn test_directory(name: &str) -> PathBuf {
+        let directory = std::env::temp_dir().join(name);
+        let _ = std::fs::remove_dir_all(&directory);
+        std::fs::create_dir_all(&directory).unwrap();
+        directory
+    }
+
+    #[test]
+    fn test_browser_lists_only_roms() {
+        let directory = test_directory("chip8_browser_lists");
+        std::fs::write(directory.join("b.ch8"), [0x00, 0xE0]).unwrap();
+        std::fs::write(directory.join("a.ch8"), [0x12, 0x00]).unwrap();
+        std::fs::write(directory.join("notes.txt"), "not a ROM").unwrap();
+        std::fs::create_dir(directory.join("folder.ch8")).unwrap();
+
+        let browser = RomBrowser::new(&directory, &RomDatabase::bundled()).unwrap();
+
+        let titles: Vec<&str> = browser
+            .entries
+            .iter()
+            .map(|entry| entry.title.as_str())
+            .collect();
+        assert_eq!(titles, ["a.ch8", "b.ch8"]);
+        assert_eq!(
+            browser.entries[0].details,
+            ["a.ch8", "Not in the ROM database"]
+        );
+    }
+
+    #[test]
+    fn test_read_rom_skips_large_files() {
+        let directory = test_directory("chip8_browser_large");
+        let fits = directory.join("fits.ch8");
+        let too_large = directory.join("too-large.ch8");
+        std::fs::write(&fits, vec![0xAA; MAX_ROM_SIZE]).unwrap();
+        std::fs::write(&too_large, vec![0xAA; MAX_ROM_SIZE + 1]).unwrap();
+
+        assert_eq!(read_rom(&fits).map(|rom| rom.len()), Some(MAX_ROM_SIZE));
+        assert_eq!(read_rom(&too_large), None);
+    }
+
+    #[test]
+    fn test_browser_selection() {
+        let directory = test_directory("chip8_browser_selection");
+        std::fs::write(directory.join("a.ch8"), [0x12, 0x00]).unwrap();
+        std::fs::write(directory.join("b.ch8"), [0x00, 0xE0]).unwrap();
+        let mut browser = RomBrowser::new(&directory, &RomDatabase::bundled()).unwrap();
+
+        assert_eq!(browser.selected().unwrap().path, directory.join("a.ch8"));
+        browser.select_previous();
+        assert_eq!(browser.selected().unwrap().path, directory.join("a.ch8"));
+        browser.select_next();
+        assert_eq!(browser.selected().unwrap().path, directory.join("b.ch8"));
+        browser.select_next();
+        assert_eq!(browser.selected().unwrap().path, directory.join("b.ch8"));
+        browser.select_previous();
+        assert_eq!(browser.selected().unwrap().path, directory.join("a.ch8"));
+    }
+
+    #[test]
+    fn test_empty_browser_selects_nothing() {
+        let directory = test_directory("chip8_browser_empty");
+        let mut browser = RomBrowser::new(&directory, &RomDatabase::bundled()).unwrap();
+
+        assert!(browser.selected().is_none());
+        browser.select_next();
+        assert!(browser.selected().is_none());
+    }
+
+    #[test]
+    fn test_rom_entry_movie_path() {
+        let entry = RomEntry {
+            path: PathBuf::from("roms/pong.ch8"),
+            title: "Pong".to_string(),
+            details: Vec::new(),
+        };
+
+        assert_eq!(
+            entry.movie_path(Path::new("movies/run.movie")),
+            Path::new("movies/run-pong.movie")
+        );
+        assert_eq!(entry.movie_path(Path::new("run")), Path::new("run-pong"));
+    }
+}
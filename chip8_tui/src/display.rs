@@ -1,9 +1,14 @@
 use ratatui::layout::Rect;
-use ratatui::style::{Style, Stylize};
-use ratatui::widgets::{Block, Borders};
+use ratatui::style::{Modifier, Style};
+use ratatui::text::Line;
+use ratatui::widgets::{Block, Borders, Widget};
 use ratatui::DefaultTerminal;
 
-use chip8_core::display::{DISPLAY_HEIGHT, DISPLAY_WIDTH};
+use chip8_core::display::{Framebuffer, DISPLAY_HEIGHT, DISPLAY_WIDTH};
+use chip8_core::overlay::OverlayText;
+use chip8_core::palette::Palette;
+
+use crate::screen::{terminal_color, RenderMode, Screen};
 
 /// Simple terminal display for the Chip8's chip8_core.
 pub struct TerminalDisplay {}
@@ -21,10 +26,10 @@ impl TerminalDisplay {
         print!("{esc}[2J{esc}[1;1H", esc = 27 as char);
     }
     /// Renders the display data on screen.
-    fn render(&mut self, display_data: &[bool; DISPLAY_WIDTH * DISPLAY_HEIGHT]) {
+    fn render(&mut self, display_data: &Framebuffer) {
         for row in 0..32 {
             for column in 0..64 {
-                if display_data[row * DISPLAY_WIDTH + column] {
+                if display_data.get(column, row) {
                     print!("█")
                 } else {
                     print!(" ")
@@ -38,43 +43,111 @@ impl TerminalDisplay {
 /// Ratatui based TUI display.
 pub struct RatatuiDisplay {
     terminal: DefaultTerminal,
+    /// The colors used to render the display.
+    palette: Palette,
+    /// How the pixels are mapped to terminal cells.
+    mode: RenderMode,
+    /// Shown in the title, such as whether the emulation is paused.
+    status: Option<String>,
+    /// The banner is drawn over the display, the rest on the status line below it.
+    overlay: OverlayText,
 }
 
 impl RatatuiDisplay {
-    pub fn new() -> RatatuiDisplay {
+    pub fn new(palette: Palette, mode: RenderMode) -> RatatuiDisplay {
         RatatuiDisplay {
             terminal: ratatui::init(),
+            palette,
+            mode,
+            status: None,
+            overlay: OverlayText::default(),
         }
     }
 
+    /// Changes the colors of the display, for the next ROM.
+    pub fn set_palette(&mut self, palette: Palette) {
+        self.palette = palette;
+    }
+
+    /// Shows the status in the title from the next render on.
+    pub fn set_status(&mut self, status: Option<String>) {
+        self.status = status;
+    }
+
+    /// Shows the overlay from the next render on.
+    pub fn set_overlay(&mut self, overlay: OverlayText) {
+        self.overlay = overlay;
+    }
+
+    /// Draws a widget over the whole terminal instead of the display, such as the ROM browser.
+    pub fn render_widget(&mut self, widget: impl Widget) {
+        self.terminal
+            .draw(|frame| frame.render_widget(widget, frame.area()))
+            .expect("failed to draw");
+    }
+
     pub fn clear(&mut self) {
         self.terminal.clear().expect("Failed to clear terminal");
     }
 
-    pub fn render(&mut self, display_data: &[bool; DISPLAY_WIDTH * DISPLAY_HEIGHT]) {
+    /// Renders the pixel intensities produced by the display filter.
+    pub fn render(&mut self, intensities: &[u8; DISPLAY_WIDTH * DISPLAY_HEIGHT]) {
+        let palette = self.palette;
+        let mode = self.mode;
+        let overlay = &self.overlay;
+        let title = match &self.status {
+            Some(status) => format!("Chip8 Emulator by nuculabs.dev [{status}]"),
+            None => "Chip8 Emulator by nuculabs.dev".to_string(),
+        };
         self.terminal
             .draw(|frame| {
-                // Render the canvas widget
+                let terminal_area = frame.area();
+                // The border takes one cell on each side.
+                let mode = mode.resolve(
+                    terminal_area.width.saturating_sub(2),
+                    terminal_area.height.saturating_sub(2),
+                );
+                let (width, height) = mode.size();
+                let block = Block::default()
+                    .title(title.as_str())
+                    .borders(Borders::ALL)
+                    .style(Style::new().bg(terminal_color(palette.background)));
+                let area = Rect::new(0, 0, width + 2, height + 2).intersection(terminal_area);
+                let inner_area = block.inner(area);
+
+                frame.render_widget(block, area);
                 frame.render_widget(
-                    Block::default()
-                        .title("Chip8 Emulator by nuculabs.dev")
-                        .borders(Borders::ALL),
-                    Rect::new(
-                        0,
-                        0,
-                        (DISPLAY_WIDTH * 2 + 2) as u16,
-                        (DISPLAY_HEIGHT * 2) as u16,
-                    ),
+                    Screen {
+                        intensities,
+                        palette,
+                        mode,
+                    },
+                    inner_area,
                 );
-                display_data.iter().enumerate().for_each(|(index, pixel)| {
-                    if *pixel {
-                        let x = (index % DISPLAY_WIDTH) as u16;
-                        let y = (index / DISPLAY_WIDTH) as u16;
-                        let area = Rect::new(x * 2, y, 2, 1);
-                        let block = Block::default().style(Style::new().on_white());
-                        frame.render_widget(block, area);
-                    }
-                });
+
+                if let Some(banner) = overlay.banner {
+                    let banner_area = Rect::new(
+                        inner_area.x,
+                        inner_area.y + inner_area.height / 2,
+                        inner_area.width,
+                        1,
+                    )
+                    .intersection(inner_area);
+                    frame.render_widget(
+                        Line::styled(format!(" {banner} "), Modifier::BOLD | Modifier::REVERSED)
+                            .centered(),
+                        banner_area,
+                    );
+                }
+                let status_line: Vec<&str> = overlay
+                    .stats
+                    .iter()
+                    .chain(&overlay.message)
+                    .map(String::as_str)
+                    .collect();
+                let status_area =
+                    Rect::new(0, area.bottom(), area.width, 1).intersection(terminal_area);
+                frame.render_widget(Line::raw(status_line.join("  |  ")), status_area);
             })
             .expect("failed to draw");
     }
@@ -1,17 +1,56 @@
+use chip8_core::command::Command;
+use chip8_core::rom::KeyHints;
 use crossterm::event::{poll, read, Event, KeyCode, KeyEventKind};
 use crossterm::terminal::{disable_raw_mode, enable_raw_mode};
+use std::collections::VecDeque;
 use std::time::Duration;
 
+/// Returns the frontend command bound to the key.
+pub fn command_for_key(code: KeyCode) -> Option<Command> {
+    match code {
+        KeyCode::Esc => Some(Command::Quit),
+        KeyCode::F(3) => Some(Command::ToggleOverlay),
+        KeyCode::F(5) => Some(Command::SoftReset),
+        KeyCode::F(6) => Some(Command::HardReset),
+        KeyCode::F(7) => Some(Command::ReloadRom),
+        KeyCode::Char('p') | KeyCode::Pause => Some(Command::TogglePause),
+        KeyCode::F(8) => Some(Command::FrameAdvance),
+        KeyCode::F(9) => Some(Command::StepInstruction),
+        KeyCode::F(10) => Some(Command::ToggleRecording),
+        KeyCode::F(12) => Some(Command::Screenshot),
+        _ => None,
+    }
+}
+
 /// CrossTermInput implements input events via the crossterm crate.
 #[derive(Clone)]
 pub struct CrossTermInput {
     initialized: bool,
+    /// The commands of the keys pressed since they were last taken.
+    commands: VecDeque<Command>,
+    /// Terminals do not report key releases, so Tab toggles fast-forwarding instead of holding it.
+    fast_forward: bool,
+    /// The keypad keys the arrow keys, space and enter play.
+    key_hints: KeyHints,
+    /// Whether the terminal was resized since it was last taken.
+    resized: bool,
 }
 
 impl CrossTermInput {
     pub fn new() -> Self {
         enable_raw_mode().expect("failed to enable terminal raw mode.");
-        CrossTermInput { initialized: true }
+        CrossTermInput {
+            initialized: true,
+            commands: VecDeque::new(),
+            fast_forward: false,
+            key_hints: KeyHints::default(),
+            resized: false,
+        }
+    }
+
+    /// Maps the arrow keys, space and enter to the keypad keys the ROM database or cartridge hints.
+    pub fn set_key_hints(&mut self, key_hints: KeyHints) {
+        self.key_hints = key_hints;
     }
 
     pub fn get_key_pressed(&mut self) -> Option<u16> {
@@ -21,12 +60,29 @@ impl CrossTermInput {
         if let Ok(true) = poll(Duration::from_millis(1)) {
             let read_result = read();
 
+            if let Ok(Event::Resize(..)) = read_result {
+                self.resized = true;
+                return None;
+            }
             if let Ok(Event::Key(key_event)) = read_result {
                 if key_event.kind == KeyEventKind::Press {
+                    if let Some(command) = command_for_key(key_event.code) {
+                        self.commands.push_back(command);
+                        return None;
+                    }
                     match key_event.code {
-                        KeyCode::Esc => {
-                            return Some(0xFF);
+                        KeyCode::Tab => {
+                            self.fast_forward = !self.fast_forward;
+                            self.commands
+                                .push_back(Command::FastForward(self.fast_forward));
+                            return None;
                         }
+                        KeyCode::Up => return self.key_hints.up.map(u16::from),
+                        KeyCode::Down => return self.key_hints.down.map(u16::from),
+                        KeyCode::Left => return self.key_hints.left.map(u16::from),
+                        KeyCode::Right => return self.key_hints.right.map(u16::from),
+                        KeyCode::Char(' ') => return self.key_hints.a.map(u16::from),
+                        KeyCode::Enter => return self.key_hints.b.map(u16::from),
                         KeyCode::Char(character) => {
                             if let Some(char) = character.to_lowercase().next() {
                                 return match char {
@@ -61,6 +117,36 @@ impl CrossTermInput {
     }
 }
 
+impl CrossTermInput {
+    /// Returns the next command of the keys pressed, once.
+    pub fn take_command(&mut self) -> Option<Command> {
+        self.commands.pop_front()
+    }
+
+    /// Returns true once after the terminal was resized, which garbles the screen.
+    pub fn take_resized(&mut self) -> bool {
+        std::mem::take(&mut self.resized)
+    }
+
+    /// Drops the commands which were not taken yet and stops fast-forwarding, for the next ROM.
+    pub fn clear_commands(&mut self) {
+        self.commands.clear();
+        self.fast_forward = false;
+    }
+
+    /// Waits a little for a key press and returns it, for the menus.
+    pub fn next_key(&mut self) -> Option<KeyCode> {
+        if let Ok(true) = poll(Duration::from_millis(100)) {
+            if let Ok(Event::Key(key_event)) = read() {
+                if key_event.kind == KeyEventKind::Press {
+                    return Some(key_event.code);
+                }
+            }
+        }
+        None
+    }
+}
+
 impl Default for CrossTermInput {
     fn default() -> Self {
         CrossTermInput::new()
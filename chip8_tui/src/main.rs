@@ -1,12 +1,29 @@
+mod browser;
 mod display;
 mod input;
+mod screen;
 
+use crate::browser::RomBrowser;
 use crate::display::RatatuiDisplay;
 use crate::input::CrossTermInput;
+use crate::screen::RenderMode;
 use clap::Parser;
 use chip8_core::emulator::{tick, Emulator};
-use chip8_core::read::StdFileReader;
-use std::fs::File;
+use chip8_core::filter::{DisplayFilter, FilterMode};
+use chip8_core::movie::{Movie, MovieHeader, MoviePlayer, MovieRecorder, Reset};
+use chip8_core::options::EmulatorOptions;
+use chip8_core::command::Command;
+use chip8_core::database::RomDatabase;
+use chip8_core::overlay::{Overlay, OverlayText};
+use chip8_core::palette::{Palette, PaletteChoice, Rgb};
+use chip8_core::run::{RunLoop, DEFAULT_FAST_FORWARD_SPEED};
+use chip8_core::recording::{toggle_recording, AnimationFormat, AnimationRecorder};
+use chip8_core::screenshot::{save_screenshot, timestamped_path, ImageFormat};
+use chip8_core::read::open_rom;
+use chip8_core::rom::RomSettings;
+use crossterm::event::KeyCode;
+use log::{info, warn};
+use std::path::{Path, PathBuf};
 use std::thread::sleep;
 use std::time::Duration;
 
@@ -17,38 +34,408 @@ use std::time::Duration;
     long_about = "A program which emulates the Chip8 system."
 )]
 struct CliArgs {
-    /// The path to the ROM file to emulate.
-    rom_path: String,
+    /// The path to the ROM file to emulate, a zip or gzip archive of it, an Octo cartridge
+    /// .gif, or - for stdin. A directory opens a browser of its ROMs, the default is roms.
+    rom_path: Option<String>,
+    #[command(flatten)]
+    emulator: EmulatorOptions,
+    /// The color palette: classic, amber, lcd, high-contrast, octo or BACKGROUND,PIXEL hex colors.
+    /// Defaults to the ROM's `.palette` hint file when present, else white pixels on black.
+    #[arg(long)]
+    palette: Option<Palette>,
+    /// Overrides the palette's background color, e.g. #000000.
+    #[arg(long)]
+    background_color: Option<Rgb>,
+    /// Overrides the palette's pixel color, e.g. #00FF00.
+    #[arg(long)]
+    pixel_color: Option<Rgb>,
+    /// Reduces flicker: off, or (OR the last two frames), phosphor or phosphor:N (fade over N frames).
+    #[arg(long, default_value = "off")]
+    filter: FilterMode,
+    /// How pixels are drawn: blocks (130x34 terminal), half-blocks (66x18) or braille (34x10).
+    /// The automatic mode picks the densest mode which fits the terminal.
+    #[arg(long, value_enum, default_value_t = RenderMode::Auto)]
+    render_mode: RenderMode,
+    /// The directory where F12 screenshots and F10 recordings are saved.
+    #[arg(long, default_value = ".")]
+    output_dir: PathBuf,
+    /// The screenshot image format: png, ppm or pbm.
+    #[arg(long, default_value = "png")]
+    screenshot_format: ImageFormat,
+    /// How many times larger than the Chip8 display screenshots are.
+    #[arg(long, default_value_t = 8, value_parser = clap::value_parser!(u32).range(1..))]
+    screenshot_scale: u32,
+    /// The F10 recording format: gif or apng.
+    #[arg(long, default_value = "gif")]
+    animation_format: AnimationFormat,
+    /// How many times larger than the Chip8 display recordings are.
+    #[arg(long, default_value_t = 4, value_parser = clap::value_parser!(u32).range(1..))]
+    animation_scale: u32,
+    /// How many frames run per tick while fast-forwarding with Tab.
+    #[arg(long, default_value_t = DEFAULT_FAST_FORWARD_SPEED, value_parser = clap::value_parser!(u32).range(2..))]
+    fast_forward_speed: u32,
+    /// Shows the frames and instructions per second and the quirks preset from the start, F3
+    /// toggles them.
+    #[arg(long)]
+    overlay: bool,
+    /// Records the key presses into an input movie file, which replays the session exactly. In
+    /// the ROM browser every ROM gets its own file, named after the ROM.
+    #[arg(long, conflicts_with = "play_movie")]
+    record_movie: Option<PathBuf>,
+    /// Plays an input movie back instead of reading the keyboard.
+    #[arg(long)]
+    play_movie: Option<PathBuf>,
 }
 
-fn main() -> Result<(), anyhow::Error> {
-    env_logger::init();
-    let args = CliArgs::parse();
+/// Loads the ROM at the path into a new emulator configured for it.
+fn load_emulator(
+    args: &CliArgs,
+    rom_path: &Path,
+    database: &RomDatabase,
+) -> Result<(Emulator, RomSettings), anyhow::Error> {
+    let rom_file = open_rom(rom_path)?;
+    let mut emulator = Emulator::new();
+    args.emulator.apply(&mut emulator)?;
+    emulator.load_rom(rom_file.reader)?;
+    let settings =
+        database.configure(&mut emulator, &rom_file.settings, args.emulator.overrides());
+    Ok((emulator, settings))
+}
 
-    let file = File::open(&args.rom_path)?;
+/// The palette when none is chosen or hinted: white pixels on black, the terminal's usual look.
+const DEFAULT_PALETTE: Palette = Palette::HIGH_CONTRAST;
 
-    let mut emulator = Emulator::new();
-    let mut display = RatatuiDisplay::new();
-    let mut input = CrossTermInput::new();
-    emulator.load_rom(StdFileReader::new(file))?;
+/// Returns the palette of the command line, or else the one the ROM hints.
+fn resolve_palette(
+    args: &CliArgs,
+    rom_path: &Path,
+    rom_settings: &RomSettings,
+) -> Result<Palette, anyhow::Error> {
+    PaletteChoice {
+        palette: args.palette,
+        background: args.background_color,
+        pixel: args.pixel_color,
+    }
+    .resolve(rom_path, rom_settings.palette, DEFAULT_PALETTE)
+}
+
+/// Loads the ROM again, with the settings of the movie played or recorded if there is one,
+/// and returns the new emulator with the ROM's settings and palette.
+fn reload_emulator(
+    args: &CliArgs,
+    rom_path: &Path,
+    database: &RomDatabase,
+    movie_header: Option<MovieHeader>,
+) -> Result<(Emulator, RomSettings, Palette), anyhow::Error> {
+    let (mut emulator, rom_settings) = load_emulator(args, rom_path, database)?;
+    // fails when the ROM changed, the movie would not replay
+    if let Some(header) = movie_header {
+        header.apply(&mut emulator)?;
+    }
+    let palette = resolve_palette(args, rom_path, &rom_settings)?;
+    Ok((emulator, rom_settings, palette))
+}
+
+/// Returns the title's indicator of a paused or fast-forwarding emulation.
+fn run_status(run_loop: &RunLoop) -> Option<String> {
+    if run_loop.is_paused() {
+        Some("PAUSED".to_string())
+    } else if run_loop.is_fast_forwarding() {
+        Some(format!("FAST x{}", run_loop.fast_forward_speed()))
+    } else {
+        None
+    }
+}
+
+/// Emulates the ROM at the path until it is quit, reloading it on request.
+fn run_rom(
+    args: &CliArgs,
+    rom_path: &Path,
+    record_movie: Option<&Path>,
+    database: &RomDatabase,
+    display: &mut RatatuiDisplay,
+    input: &mut CrossTermInput,
+) -> Result<(), anyhow::Error> {
+    let (mut emulator, rom_settings) = load_emulator(args, rom_path, database)?;
+    let mut palette = resolve_palette(args, rom_path, &rom_settings)?;
+    let mut display_filter = DisplayFilter::new(args.filter);
+    display.set_palette(palette);
+    input.set_key_hints(rom_settings.keys.unwrap_or_default());
+    input.clear_commands();
+
+    let mut movie_player = match &args.play_movie {
+        Some(path) => {
+            let player = MoviePlayer::new(Movie::load(path)?);
+            player.header().apply(&mut emulator)?;
+            Some(player)
+        }
+        None => None,
+    };
+    // the movie is written while it is recorded, so it is kept when the process is killed
+    let mut movie_recorder = match record_movie {
+        Some(path) => Some(MovieRecorder::create(path, MovieHeader::for_emulator(&emulator))?),
+        None => None,
+    };
 
     display.clear();
 
+    let mut animation_recorder: Option<AnimationRecorder> = None;
+
+    let mut run_loop = RunLoop::new(args.fast_forward_speed);
+    let mut status = None;
+    let mut overlay = Overlay::new(args.overlay);
+    overlay.set_quirks(emulator.quirks());
+    let mut overlay_text = OverlayText::default();
+    display.set_overlay(overlay_text.clone());
     let mut last_tick_time = None;
-    loop {
+    // errors leave the loop with a value so the recordings are still saved
+    let result: Result<(), anyhow::Error> = 'running: loop {
         if tick(&mut last_tick_time) {
             emulator.handle_input(input.get_key_pressed());
+            while let Some(command) = input.take_command() {
+                info!("Command: {}", command.description());
+                match command {
+                    Command::Quit => break 'running Ok(()),
+                    // stdin cannot be read twice
+                    Command::ReloadRom if rom_path.as_os_str() == "-" => {
+                        warn!("A ROM read from stdin cannot be reloaded")
+                    }
+                    Command::ReloadRom => {
+                        info!("Reloading {}", rom_path.display());
+                        let movie_header = movie_recorder
+                            .as_ref()
+                            .map(MovieRecorder::header)
+                            .or(movie_player.as_ref().map(MoviePlayer::header))
+                            .copied();
+                        // a ROM which fails to load again leaves the running one alone
+                        match reload_emulator(args, rom_path, database, movie_header) {
+                            Ok((reloaded, rom_settings, reloaded_palette)) => {
+                                emulator = reloaded;
+                                display_filter.reset(emulator.get_display_buffer());
+                                // the same ROM loaded again replays like a hard reset
+                                if let Some(recorder) = movie_recorder.as_mut() {
+                                    recorder.record_reset(Reset::Hard);
+                                }
+                                input.set_key_hints(rom_settings.keys.unwrap_or_default());
+                                palette = reloaded_palette;
+                                display.set_palette(palette);
+                                display.clear();
+                                overlay.set_quirks(emulator.quirks());
+                                overlay.show_message("Reloaded the ROM");
+                            }
+                            Err(error) => {
+                                warn!("Could not reload the ROM: {error}");
+                                overlay.show_message(format!("Could not reload the ROM: {error}"));
+                            }
+                        }
+                    }
+                    Command::SoftReset => {
+                        emulator.soft_reset();
+                        display_filter.reset(emulator.get_display_buffer());
+                        if let Some(recorder) = movie_recorder.as_mut() {
+                            recorder.record_reset(Reset::Soft);
+                        }
+                        overlay.show_message("Soft reset");
+                    }
+                    Command::HardReset => {
+                        if let Err(error) = emulator.reset() {
+                            break 'running Err(error.into());
+                        }
+                        display_filter.reset(emulator.get_display_buffer());
+                        if let Some(recorder) = movie_recorder.as_mut() {
+                            recorder.record_reset(Reset::Hard);
+                        }
+                        overlay.show_message("Hard reset");
+                    }
+                    Command::TogglePause => run_loop.toggle_pause(),
+                    Command::FrameAdvance => run_loop.advance_frame(),
+                    Command::StepInstruction => run_loop.step_instruction(),
+                    Command::FastForward(fast_forwarding) => {
+                        run_loop.set_fast_forward(fast_forwarding)
+                    }
+                    Command::Screenshot => {
+                        let path =
+                            timestamped_path(&args.output_dir, args.screenshot_format.extension());
+                        let message = match save_screenshot(
+                            &path,
+                            emulator.get_display_buffer(),
+                            args.screenshot_format,
+                            args.screenshot_scale as usize,
+                            &palette,
+                        ) {
+                            Ok(()) => format!("Saved screenshot to {}", path.display()),
+                            Err(error) => {
+                                warn!("Could not save the screenshot: {error}");
+                                format!("Could not save the screenshot: {error}")
+                            }
+                        };
+                        overlay.show_message(message);
+                    }
+                    Command::ToggleRecording => {
+                        let message = match toggle_recording(
+                            &mut animation_recorder,
+                            &args.output_dir,
+                            args.animation_format,
+                            args.animation_scale as usize,
+                            palette,
+                        ) {
+                            Ok(message) => message,
+                            Err(error) => {
+                                warn!("Could not save the recording: {error}");
+                                format!("Could not save the recording: {error}")
+                            }
+                        };
+                        overlay.show_message(message);
+                    }
+                    Command::ToggleOverlay => overlay.toggle_stats(),
+                }
+            }
+            // a paused emulator keeps rendering, so resets show
+            let mut filter_animated = false;
+            let run = match run_loop.run(
+                &mut emulator,
+                |emulator| -> Result<(), anyhow::Error> {
+                    // a playing movie overrides the keyboard
+                    if let Some(player) = movie_player.as_mut() {
+                        match player.next_frame() {
+                            Some(movie_frame) => movie_frame.apply(emulator)?,
+                            None => {
+                                info!("The movie is over, the keyboard is back in control");
+                                movie_player = None;
+                            }
+                        }
+                    }
+                    if let Some(recorder) = movie_recorder.as_mut() {
+                        recorder.record_frame(emulator.key_pressed())?;
+                    }
+                    Ok(())
+                },
+                |emulator| {
+                    filter_animated |= display_filter.advance(emulator.get_display_buffer());
+                    if emulator.should_beep() {
+                        print!("\x07");
+                    }
+                    if let Some(recorder) = animation_recorder.as_mut() {
+                        recorder.record_frame(emulator.get_display_buffer());
+                    }
+                    Ok(())
+                },
+            ) {
+                Ok(run) => run,
+                Err(error) => break Err(error),
+            };
+            // the draws of a frame stepped into show before it ends
+            if emulator.frame_position() != 0 {
+                display_filter.preview(emulator.get_display_buffer());
+            }
 
-            if emulator.should_beep() {
-                print!("\x07");
+            overlay.set_paused(run_loop.is_paused());
+            overlay.record_tick(run.frames, emulator.instructions_executed());
+            let text = overlay.text();
+            let overlay_changed = text != overlay_text;
+            if overlay_changed {
+                display.set_overlay(text.clone());
+                overlay_text = text;
             }
 
-            emulator.execute_and_fetch()?;
+            let run_status = run_status(&run_loop);
+            let status_changed = run_status != status;
+            if status_changed {
+                display.set_status(run_status.clone());
+                status = run_status;
+            }
 
-            // render
-            display.render(&emulator.get_display_buffer());
+            // render only when the display changed, and all of it after a resize garbled it
+            let resized = input.take_resized();
+            if resized {
+                display.clear();
+            }
+            if emulator.take_dirty_region().is_some()
+                || resized
+                || filter_animated
+                || status_changed
+                || overlay_changed
+            {
+                display.render(display_filter.intensities());
+            }
         } else {
             sleep(Duration::from_millis(1));
         }
+    };
+    if animation_recorder.is_some() {
+        if let Err(error) = toggle_recording(
+            &mut animation_recorder,
+            &args.output_dir,
+            args.animation_format,
+            args.animation_scale as usize,
+            palette,
+        ) {
+            warn!("Could not save the recording: {error}");
+        }
+    }
+    if let Some(recorder) = movie_recorder {
+        recorder.finish()?;
+    }
+    result
+}
+
+/// Lists the ROMs of the directory and runs the picked ones until the browser is quit.
+fn browse_roms(
+    args: &CliArgs,
+    directory: &Path,
+    database: &RomDatabase,
+    display: &mut RatatuiDisplay,
+    input: &mut CrossTermInput,
+) -> Result<(), anyhow::Error> {
+    let mut browser = RomBrowser::new(directory, database)?;
+    loop {
+        display.render_widget(&mut browser);
+        match input.next_key() {
+            Some(KeyCode::Up) => browser.select_previous(),
+            Some(KeyCode::Down) => browser.select_next(),
+            Some(KeyCode::Enter) => {
+                let Some(entry) = browser.selected().cloned() else {
+                    continue;
+                };
+                info!("Running {}", entry.path.display());
+                let record_movie = args
+                    .record_movie
+                    .as_deref()
+                    .map(|path| entry.movie_path(path));
+                // a ROM which fails to run shows its error in the browser
+                let result = run_rom(
+                    args,
+                    &entry.path,
+                    record_movie.as_deref(),
+                    database,
+                    display,
+                    input,
+                );
+                browser.set_message(
+                    result
+                        .err()
+                        .map(|error| format!("{} stopped: {error}", entry.title)),
+                );
+                display.clear();
+            }
+            Some(KeyCode::Esc) | Some(KeyCode::Char('q')) => return Ok(()),
+            _ => {}
+        }
+    }
+}
+
+fn main() -> Result<(), anyhow::Error> {
+    env_logger::init();
+    let args = CliArgs::parse();
+
+    let database = args.emulator.load_database()?;
+    let rom_path = PathBuf::from(args.rom_path.as_deref().unwrap_or("roms"));
+    let mut display = RatatuiDisplay::new(DEFAULT_PALETTE, args.render_mode);
+    let mut input = CrossTermInput::new();
+    if rom_path.is_dir() {
+        browse_roms(&args, &rom_path, &database, &mut display, &mut input)
+    } else {
+        let record_movie = args.record_movie.as_deref();
+        run_rom(&args, &rom_path, record_movie, &database, &mut display, &mut input)
     }
 }
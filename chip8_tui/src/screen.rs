@@ -0,0 +1,193 @@
+use chip8_core::display::{DISPLAY_HEIGHT, DISPLAY_WIDTH};
+use chip8_core::palette::{Palette, Rgb};
+use clap::ValueEnum;
+use ratatui::buffer::Buffer;
+use ratatui::layout::Rect;
+use ratatui::style::Color;
+use ratatui::widgets::Widget;
+
+/// Selects how the Chip8 pixels are mapped to terminal cells.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum RenderMode {
+    /// Picks the densest mode which fits the terminal.
+    Auto,
+    /// Each pixel is two cells wide, needs 128x32 cells.
+    Blocks,
+    /// Each cell holds two pixels stacked with ▀, needs 64x16 cells.
+    HalfBlocks,
+    /// Each cell holds 2x4 pixels as a braille character, needs 32x8 cells.
+    Braille,
+}
+
+impl RenderMode {
+    /// Returns the amount of cells the display needs in this mode.
+    pub fn size(&self) -> (u16, u16) {
+        match self {
+            RenderMode::Auto | RenderMode::Blocks => {
+                (DISPLAY_WIDTH as u16 * 2, DISPLAY_HEIGHT as u16)
+            }
+            RenderMode::HalfBlocks => (DISPLAY_WIDTH as u16, DISPLAY_HEIGHT as u16 / 2),
+            RenderMode::Braille => (DISPLAY_WIDTH as u16 / 2, DISPLAY_HEIGHT as u16 / 4),
+        }
+    }
+
+    /// Resolves the automatic mode to the densest mode which fits in the available area.
+    pub fn resolve(self, width: u16, height: u16) -> RenderMode {
+        if self != RenderMode::Auto {
+            return self;
+        }
+        [RenderMode::Blocks, RenderMode::HalfBlocks]
+            .into_iter()
+            .find(|mode| {
+                let (mode_width, mode_height) = mode.size();
+                mode_width <= width && mode_height <= height
+            })
+            .unwrap_or(RenderMode::Braille)
+    }
+}
+
+/// The bits of the braille dots, indexed by row and column inside the cell.
+const BRAILLE_DOTS: [[u8; 2]; 4] = [[0x01, 0x08], [0x02, 0x10], [0x04, 0x20], [0x40, 0x80]];
+
+/// The first braille character, which has no dots raised.
+const BRAILLE_BASE: u32 = 0x2800;
+
+/// Widget which draws the Chip8 display into the terminal buffer in a single pass.
+pub struct Screen<'a> {
+    pub intensities: &'a [u8; DISPLAY_WIDTH * DISPLAY_HEIGHT],
+    pub palette: Palette,
+    /// The render mode, already resolved from [`RenderMode::Auto`].
+    pub mode: RenderMode,
+}
+
+impl Screen<'_> {
+    fn intensity(&self, x: usize, y: usize) -> u8 {
+        self.intensities[y * DISPLAY_WIDTH + x]
+    }
+
+    fn color(&self, intensity: u8) -> Color {
+        terminal_color(self.palette.blend(intensity))
+    }
+}
+
+/// Converts a palette color into a terminal color.
+pub fn terminal_color(color: Rgb) -> Color {
+    Color::Rgb(color.r, color.g, color.b)
+}
+
+impl Widget for Screen<'_> {
+    fn render(self, area: Rect, buf: &mut Buffer) {
+        let (width, height) = self.mode.size();
+        let area = area.intersection(Rect::new(area.x, area.y, width, height));
+        for row in 0..area.height {
+            for column in 0..area.width {
+                let cell = &mut buf[(area.x + column, area.y + row)];
+                let (x, y) = (column as usize, row as usize);
+                match self.mode {
+                    RenderMode::Auto | RenderMode::Blocks => {
+                        cell.set_char(' ')
+                            .set_bg(self.color(self.intensity(x / 2, y)));
+                    }
+                    RenderMode::HalfBlocks => {
+                        cell.set_char('▀')
+                            .set_fg(self.color(self.intensity(x, y * 2)))
+                            .set_bg(self.color(self.intensity(x, y * 2 + 1)));
+                    }
+                    RenderMode::Braille => {
+                        let mut dots = 0u8;
+                        let mut brightest = 0u8;
+                        for (dot_row, row_dots) in BRAILLE_DOTS.iter().enumerate() {
+                            for (dot_column, dot) in row_dots.iter().enumerate() {
+                                let intensity = self.intensity(x * 2 + dot_column, y * 4 + dot_row);
+                                if intensity > 0 {
+                                    dots |= dot;
+                                    brightest = brightest.max(intensity);
+                                }
+                            }
+                        }
+                        let character = char::from_u32(BRAILLE_BASE + dots as u32).unwrap_or(' ');
+                        cell.set_char(character)
+                            .set_fg(self.color(brightest))
+                            .set_bg(self.color(0));
+                    }
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const PIXEL: Color = Color::Rgb(0, 255, 0);
+    const BACKGROUND: Color = Color::Rgb(0, 0, 0);
+
+    /// Renders the lit pixels in the mode into a buffer of the mode's size.
+    fn render(mode: RenderMode, lit: &[(usize, usize)]) -> Buffer {
+        let mut intensities = [0; DISPLAY_WIDTH * DISPLAY_HEIGHT];
+        for &(x, y) in lit {
+            intensities[y * DISPLAY_WIDTH + x] = 255;
+        }
+        let (width, height) = mode.size();
+        let mut buffer = Buffer::empty(Rect::new(0, 0, width, height));
+        Screen {
+            intensities: &intensities,
+            palette: Palette::CLASSIC_GREEN,
+            mode,
+        }
+        .render(buffer.area, &mut buffer);
+        buffer
+    }
+
+    #[test]
+    fn test_render_mode_resolve() {
+        assert_eq!(RenderMode::Auto.resolve(128, 32), RenderMode::Blocks);
+        assert_eq!(RenderMode::Auto.resolve(127, 40), RenderMode::HalfBlocks);
+        assert_eq!(RenderMode::Auto.resolve(64, 16), RenderMode::HalfBlocks);
+        assert_eq!(RenderMode::Auto.resolve(63, 16), RenderMode::Braille);
+        assert_eq!(RenderMode::Auto.resolve(10, 5), RenderMode::Braille);
+        assert_eq!(RenderMode::Braille.resolve(200, 100), RenderMode::Braille);
+        assert_eq!(RenderMode::Blocks.resolve(10, 5), RenderMode::Blocks);
+    }
+
+    #[test]
+    fn test_render_blocks() {
+        let buffer = render(RenderMode::Blocks, &[(1, 0)]);
+
+        assert_eq!(buffer[(1, 0)].bg, BACKGROUND);
+        assert_eq!(buffer[(2, 0)].bg, PIXEL);
+        assert_eq!(buffer[(3, 0)].bg, PIXEL);
+        assert_eq!(buffer[(3, 0)].symbol(), " ");
+        assert_eq!(buffer[(4, 0)].bg, BACKGROUND);
+    }
+
+    #[test]
+    fn test_render_half_blocks() {
+        let buffer = render(RenderMode::HalfBlocks, &[(0, 0), (1, 1), (63, 31)]);
+
+        let cell = &buffer[(0, 0)];
+        assert_eq!(cell.symbol(), "▀");
+        assert_eq!((cell.fg, cell.bg), (PIXEL, BACKGROUND));
+        let cell = &buffer[(1, 0)];
+        assert_eq!((cell.fg, cell.bg), (BACKGROUND, PIXEL));
+        let cell = &buffer[(63, 15)];
+        assert_eq!((cell.fg, cell.bg), (BACKGROUND, PIXEL));
+        let cell = &buffer[(2, 0)];
+        assert_eq!((cell.fg, cell.bg), (BACKGROUND, BACKGROUND));
+    }
+
+    #[test]
+    fn test_render_braille() {
+        let buffer = render(RenderMode::Braille, &[(0, 0), (1, 3), (2, 1), (63, 31)]);
+
+        let cell = &buffer[(0, 0)];
+        assert_eq!(cell.symbol(), "⢁");
+        assert_eq!((cell.fg, cell.bg), (PIXEL, BACKGROUND));
+        assert_eq!(buffer[(1, 0)].symbol(), "⠂");
+        assert_eq!(buffer[(31, 7)].symbol(), "⢀");
+        let cell = &buffer[(2, 0)];
+        assert_eq!(cell.symbol(), "⠀");
+        assert_eq!(cell.fg, BACKGROUND);
+    }
+}